@@ -18,9 +18,11 @@ use zksync_config::{
 };
 use zksync_core::{
     api_server::{
+        execution_sandbox::{OversizedResponsePolicy, ResponseSizeLimit},
         tx_sender::TxSenderConfig,
         web3::{state::InternalApiConfig, Namespace},
     },
+    consensus::FetcherConfig,
     temp_config_store::decode_yaml_repr,
 };
 #[cfg(test)]
@@ -196,6 +198,28 @@ pub(crate) struct OptionalENConfig {
     /// This option can be tweaked down if the API server is running out of memory.
     #[serde(default = "OptionalENConfig::default_vm_concurrency_limit")]
     pub vm_concurrency_limit: usize,
+    /// Number of VM instances out of `vm_concurrency_limit` that are reserved for interactive calls
+    /// (transaction submission and gas estimation), so that they aren't starved by background calls (e.g., `eth_call`).
+    /// Must not exceed `vm_concurrency_limit`.
+    #[serde(default)]
+    pub vm_concurrency_reserved_permits: usize,
+    /// Wait time for a VM instance in milliseconds, after which the wait is logged as slow.
+    /// If set to 0, slow waits are not logged.
+    #[serde(default = "OptionalENConfig::default_vm_slow_acquire_threshold_ms")]
+    vm_slow_acquire_threshold_ms: u64,
+    /// Maximum number of calls waiting for a VM instance. Once this number is reached, calls that cannot get
+    /// an instance immediately are rejected rather than queued. If not specified, the queue is not limited.
+    pub vm_max_queue_depth: Option<usize>,
+    /// Maximum size of the data returned by `eth_call` in bytes. If not specified, the size is not limited.
+    pub eth_call_max_response_size: Option<usize>,
+    /// Whether to truncate the data returned by `eth_call` to `eth_call_max_response_size` bytes.
+    /// If not set, calls returning more data fail with an error.
+    #[serde(default)]
+    pub eth_call_truncate_oversized_responses: bool,
+    /// Ceiling on the estimated cost of a single `eth_call` in gas. Calls to contracts are estimated as the gas
+    /// limit declared by the caller (or the maximum `eth_call` gas limit if not declared); calls exceeding
+    /// the ceiling are rejected before execution. If not specified, the cost of calls is not limited.
+    pub eth_call_max_cost: Option<u64>,
     /// Smart contract bytecode cache size for the API server. Default value is 128 MiB.
     #[serde(default = "OptionalENConfig::default_factory_deps_cache_size_mb")]
     factory_deps_cache_size_mb: usize,
@@ -326,6 +350,16 @@ pub(crate) struct OptionalENConfig {
 
     /// If set, l1 batches will be pruned after they are that long
     pub pruning_data_retention_hours: Option<u64>,
+
+    // Consensus fetcher config
+    /// Maximum allowed difference in seconds between the timestamp of a miniblock fetched from the main node
+    /// and the local wall-clock time. Miniblocks with timestamps further in the future are rejected.
+    /// If not specified, the difference is not checked.
+    fetcher_max_timestamp_skew_sec: Option<u64>,
+    /// Threshold in milliseconds for applying a fetched miniblock. If applying a miniblock (e.g., because
+    /// the state keeper lags) takes longer, miniblocks are fetched one at a time until applying becomes fast again.
+    /// If not specified, fetching is never throttled.
+    fetcher_backpressure_threshold_ms: Option<u64>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize)]
@@ -393,6 +427,10 @@ impl OptionalENConfig {
         2_048
     }
 
+    const fn default_vm_slow_acquire_threshold_ms() -> u64 {
+        100
+    }
+
     const fn default_factory_deps_cache_size_mb() -> usize {
         128
     }
@@ -523,6 +561,29 @@ impl OptionalENConfig {
         self.max_response_body_size_mb * BYTES_IN_MEGABYTE
     }
 
+    pub fn vm_slow_acquire_threshold(&self) -> Option<Duration> {
+        let threshold_ms = self.vm_slow_acquire_threshold_ms;
+        (threshold_ms > 0).then(|| Duration::from_millis(threshold_ms))
+    }
+
+    pub fn fetcher_max_timestamp_skew(&self) -> Option<Duration> {
+        self.fetcher_max_timestamp_skew_sec.map(Duration::from_secs)
+    }
+
+    pub fn fetcher_backpressure_threshold(&self) -> Option<Duration> {
+        self.fetcher_backpressure_threshold_ms
+            .map(Duration::from_millis)
+    }
+
+    /// Returns parameters of fetching blocks from the main node.
+    pub fn fetcher_config(&self) -> FetcherConfig {
+        FetcherConfig {
+            max_timestamp_skew: self.fetcher_max_timestamp_skew(),
+            backpressure_threshold: self.fetcher_backpressure_threshold(),
+            ..FetcherConfig::default()
+        }
+    }
+
     pub fn healthcheck_slow_time_limit(&self) -> Option<Duration> {
         self.healthcheck_slow_time_limit_ms
             .map(Duration::from_millis)
@@ -828,6 +889,18 @@ impl From<ExternalNodeConfig> for TxSenderConfig {
             max_pubdata_per_batch: config.remote.max_pubdata_per_batch,
             // Does not matter for EN.
             whitelisted_tokens_for_aa: Default::default(),
+            all_tokens_allowed_for_aa: false,
+            response_size_limit: config.optional.eth_call_max_response_size.map(|max_size| {
+                ResponseSizeLimit {
+                    max_size,
+                    policy: if config.optional.eth_call_truncate_oversized_responses {
+                        OversizedResponsePolicy::Truncate
+                    } else {
+                        OversizedResponsePolicy::Error
+                    },
+                }
+            }),
+            max_call_cost: config.optional.eth_call_max_cost,
         }
     }
 }
@@ -17,6 +17,15 @@ fn parsing_optional_config_from_empty_env() {
     assert_eq!(config.max_nonce_ahead, 50);
     assert_eq!(config.estimate_gas_scale_factor, 1.2);
     assert_eq!(config.vm_concurrency_limit, 2_048);
+    assert_eq!(config.vm_concurrency_reserved_permits, 0);
+    assert_eq!(
+        config.vm_slow_acquire_threshold(),
+        Some(Duration::from_millis(100))
+    );
+    assert_eq!(config.vm_max_queue_depth, None);
+    assert_eq!(config.eth_call_max_response_size, None);
+    assert!(!config.eth_call_truncate_oversized_responses);
+    assert_eq!(config.eth_call_max_cost, None);
     assert_eq!(config.factory_deps_cache_size(), 128 * BYTES_IN_MEGABYTE);
     assert_eq!(config.latest_values_cache_size(), 128 * BYTES_IN_MEGABYTE);
     assert_eq!(config.merkle_tree_multi_get_chunk_size, 500);
@@ -29,6 +38,8 @@ fn parsing_optional_config_from_empty_env() {
         config.l1_batch_commit_data_generator_mode,
         L1BatchCommitDataGeneratorMode::Rollup
     );
+    assert_eq!(config.fetcher_max_timestamp_skew(), None);
+    assert_eq!(config.fetcher_backpressure_threshold(), None);
 }
 
 #[test]
@@ -44,12 +55,20 @@ fn parsing_optional_config_from_env() {
         ("EN_MAX_NONCE_AHEAD", "100"),
         ("EN_ESTIMATE_GAS_SCALE_FACTOR", "1.5"),
         ("EN_VM_CONCURRENCY_LIMIT", "1000"),
+        ("EN_VM_CONCURRENCY_RESERVED_PERMITS", "100"),
+        ("EN_VM_SLOW_ACQUIRE_THRESHOLD_MS", "0"),
+        ("EN_VM_MAX_QUEUE_DEPTH", "500"),
+        ("EN_ETH_CALL_MAX_RESPONSE_SIZE", "65536"),
+        ("EN_ETH_CALL_TRUNCATE_OVERSIZED_RESPONSES", "true"),
+        ("EN_ETH_CALL_MAX_COST", "10000000"),
         ("EN_FACTORY_DEPS_CACHE_SIZE_MB", "64"),
         ("EN_LATEST_VALUES_CACHE_SIZE_MB", "50"),
         ("EN_MERKLE_TREE_MULTI_GET_CHUNK_SIZE", "1000"),
         ("EN_MERKLE_TREE_BLOCK_CACHE_SIZE_MB", "32"),
         ("EN_MAX_RESPONSE_BODY_SIZE_MB", "1"),
         ("EN_L1_BATCH_COMMIT_DATA_GENERATOR_MODE", "Validium"),
+        ("EN_FETCHER_MAX_TIMESTAMP_SKEW_SEC", "600"),
+        ("EN_FETCHER_BACKPRESSURE_THRESHOLD_MS", "1000"),
     ];
     let env_vars = env_vars
         .into_iter()
@@ -69,6 +88,12 @@ fn parsing_optional_config_from_env() {
     assert_eq!(config.max_nonce_ahead, 100);
     assert_eq!(config.estimate_gas_scale_factor, 1.5);
     assert_eq!(config.vm_concurrency_limit, 1_000);
+    assert_eq!(config.vm_concurrency_reserved_permits, 100);
+    assert_eq!(config.vm_slow_acquire_threshold(), None);
+    assert_eq!(config.vm_max_queue_depth, Some(500));
+    assert_eq!(config.eth_call_max_response_size, Some(65_536));
+    assert!(config.eth_call_truncate_oversized_responses);
+    assert_eq!(config.eth_call_max_cost, Some(10_000_000));
     assert_eq!(config.factory_deps_cache_size(), 64 * BYTES_IN_MEGABYTE);
     assert_eq!(config.latest_values_cache_size(), 50 * BYTES_IN_MEGABYTE);
     assert_eq!(config.merkle_tree_multi_get_chunk_size, 1_000);
@@ -81,4 +106,12 @@ fn parsing_optional_config_from_env() {
         config.l1_batch_commit_data_generator_mode,
         L1BatchCommitDataGeneratorMode::Validium
     );
+    assert_eq!(
+        config.fetcher_max_timestamp_skew(),
+        Some(Duration::from_secs(600))
+    );
+    assert_eq!(
+        config.fetcher_backpressure_threshold(),
+        Some(Duration::from_secs(1))
+    );
 }
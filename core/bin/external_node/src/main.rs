@@ -16,7 +16,7 @@ use zksync_config::configs::{
 };
 use zksync_core::{
     api_server::{
-        execution_sandbox::VmConcurrencyLimiter,
+        execution_sandbox::{AaTokenWhitelist, VmConcurrencyLimiter},
         healthcheck::HealthCheckHandle,
         tree::{TreeApiClient, TreeApiHttpClient},
         tx_sender::{proxy::TxProxy, ApiContracts, TxSenderBuilder},
@@ -220,6 +220,7 @@ async fn run_core(
     .await?;
 
     task_handles.push(tokio::spawn({
+        let fetcher_config = config.optional.fetcher_config();
         let config = config.consensus.clone();
         let secrets =
             config::read_consensus_secrets().context("config::read_consensus_secrets()")?;
@@ -237,6 +238,8 @@ async fn run_core(
 
         let pool = connection_pool.clone();
         let sync_state = sync_state.clone();
+        let fetcher_health = consensus::FetcherHealth::default();
+        app_health.insert_custom_component(Arc::new(fetcher_health.clone()));
         let main_node_client = main_node_client.clone();
         let mut stop_receiver = stop_receiver.clone();
         async move {
@@ -252,6 +255,8 @@ async fn run_core(
                     cfg,
                     pool,
                     sync_state,
+                    fetcher_health,
+                    fetcher_config,
                     main_node_client,
                     action_queue_sender,
                 ));
@@ -431,7 +436,14 @@ async fn run_api(
     };
 
     let max_concurrency = config.optional.vm_concurrency_limit;
-    let (vm_concurrency_limiter, vm_barrier) = VmConcurrencyLimiter::new(max_concurrency);
+    let reserved_permits = config.optional.vm_concurrency_reserved_permits;
+    let (vm_concurrency_limiter, vm_barrier) = VmConcurrencyLimiter::with_reserved_permits(
+        max_concurrency,
+        reserved_permits,
+        config.optional.vm_slow_acquire_threshold(),
+    );
+    let vm_concurrency_limiter =
+        vm_concurrency_limiter.with_max_queue_depth(config.optional.vm_max_queue_depth);
     let mut storage_caches = PostgresStorageCaches::new(
         config.optional.factory_deps_cache_size() as u64,
         config.optional.initial_writes_cache_size() as u64,
@@ -446,14 +458,14 @@ async fn run_api(
     });
     task_handles.extend(cache_update_handle);
 
-    let whitelisted_tokens_for_aa_cache = Arc::new(RwLock::new(Vec::new()));
+    let whitelisted_tokens_for_aa_cache = Arc::new(RwLock::new(AaTokenWhitelist::default()));
     let whitelisted_tokens_for_aa_cache_clone = whitelisted_tokens_for_aa_cache.clone();
     let mut stop_receiver_for_task = stop_receiver.clone();
     task_handles.push(task::spawn(async move {
         while !*stop_receiver_for_task.borrow_and_update() {
             match main_node_client.whitelisted_tokens_for_aa().await {
                 Ok(tokens) => {
-                    *whitelisted_tokens_for_aa_cache_clone.write().await = tokens;
+                    *whitelisted_tokens_for_aa_cache_clone.write().await = tokens.into();
                 }
                 Err(jsonrpsee::core::client::Error::Call(error))
                     if error.code() == jsonrpsee::types::error::METHOD_NOT_FOUND_CODE =>
@@ -68,6 +68,28 @@ pub struct Web3JsonRpcConfig {
     /// This option can be tweaked down if the API server is running out of memory.
     /// If not set, the VM concurrency limit will be efficiently disabled.
     pub vm_concurrency_limit: Option<usize>,
+    /// Number of VM instances out of `vm_concurrency_limit` that are reserved for interactive calls
+    /// (transaction submission and gas estimation), so that they aren't starved by background calls (e.g., `eth_call`).
+    /// Must not exceed `vm_concurrency_limit`.
+    /// If not set, no instances are reserved.
+    pub vm_concurrency_reserved_permits: Option<usize>,
+    /// Wait time for a VM instance in milliseconds, after which the wait is logged as slow. The default value
+    /// is 100 ms. If set to 0, slow waits are not logged.
+    pub vm_slow_acquire_threshold_ms: Option<u64>,
+    /// Maximum number of calls waiting for a VM instance. Once this number is reached, calls that cannot get
+    /// an instance immediately are rejected rather than queued. If not set, the queue is not limited.
+    pub vm_max_queue_depth: Option<usize>,
+    /// Maximum size of the data returned by `eth_call` in bytes. If not set, the size is not limited.
+    pub eth_call_max_response_size: Option<usize>,
+    /// Whether to truncate the data returned by `eth_call` to `eth_call_max_response_size` bytes. If not set,
+    /// calls returning more data fail with an error.
+    #[serde(default)]
+    pub eth_call_truncate_oversized_responses: bool,
+    /// Ceiling on the estimated cost of a single `eth_call` in gas. Calls to contracts are estimated as the gas
+    /// limit declared by the caller (or the maximum `eth_call` gas limit if not declared); calls exceeding
+    /// the ceiling are rejected before execution, and admitted calls are executed with the declared gas limit.
+    /// If not set, the cost of calls is not limited.
+    pub eth_call_max_cost: Option<u64>,
     /// Smart contract cache size in MiBs. The default value is 128 MiB.
     pub factory_deps_cache_size_mb: Option<usize>,
     /// Initial writes cache size in MiBs. The default value is 32 MiB.
@@ -96,6 +118,11 @@ pub struct Web3JsonRpcConfig {
     /// (additionally to natively bridged tokens).
     #[serde(default)]
     pub whitelisted_tokens_for_aa: Vec<Address>,
+    /// Whether any token used by an approval-based paymaster may be used by account abstraction during validation
+    /// of the paid transaction, regardless of `whitelisted_tokens_for_aa`. Intended for permissive environments,
+    /// such as testnets.
+    #[serde(default)]
+    pub all_tokens_allowed_for_aa: bool,
 }
 
 impl Web3JsonRpcConfig {
@@ -122,6 +149,12 @@ impl Web3JsonRpcConfig {
             max_tx_size: 1000000,
             vm_execution_cache_misses_limit: Default::default(),
             vm_concurrency_limit: Default::default(),
+            vm_concurrency_reserved_permits: Default::default(),
+            vm_slow_acquire_threshold_ms: Default::default(),
+            vm_max_queue_depth: Default::default(),
+            eth_call_max_response_size: Default::default(),
+            eth_call_truncate_oversized_responses: false,
+            eth_call_max_cost: Default::default(),
             factory_deps_cache_size_mb: Default::default(),
             initial_writes_cache_size_mb: Default::default(),
             latest_values_cache_size_mb: Default::default(),
@@ -133,6 +166,7 @@ impl Web3JsonRpcConfig {
             mempool_cache_size: Default::default(),
             tree_api_url: None,
             whitelisted_tokens_for_aa: Default::default(),
+            all_tokens_allowed_for_aa: false,
         }
     }
 
@@ -175,6 +209,15 @@ impl Web3JsonRpcConfig {
         self.vm_concurrency_limit.unwrap_or(2_048)
     }
 
+    pub fn vm_concurrency_reserved_permits(&self) -> usize {
+        self.vm_concurrency_reserved_permits.unwrap_or(0)
+    }
+
+    pub fn vm_slow_acquire_threshold(&self) -> Option<Duration> {
+        let threshold_ms = self.vm_slow_acquire_threshold_ms.unwrap_or(100);
+        (threshold_ms > 0).then(|| Duration::from_millis(threshold_ms))
+    }
+
     /// Returns the size of factory dependencies cache in bytes.
     pub fn factory_deps_cache_size(&self) -> usize {
         self.factory_deps_cache_size_mb.unwrap_or(128) * super::BYTES_IN_MEGABYTE
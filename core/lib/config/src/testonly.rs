@@ -68,6 +68,12 @@ impl Distribution<configs::api::Web3JsonRpcConfig> for EncodeDist {
             max_tx_size: self.sample(rng),
             vm_execution_cache_misses_limit: self.sample(rng),
             vm_concurrency_limit: self.sample(rng),
+            vm_concurrency_reserved_permits: self.sample(rng),
+            vm_slow_acquire_threshold_ms: self.sample(rng),
+            vm_max_queue_depth: self.sample(rng),
+            eth_call_max_response_size: self.sample(rng),
+            eth_call_truncate_oversized_responses: self.sample(rng),
+            eth_call_max_cost: self.sample(rng),
             factory_deps_cache_size_mb: self.sample(rng),
             initial_writes_cache_size_mb: self.sample(rng),
             latest_values_cache_size_mb: self.sample(rng),
@@ -79,6 +85,7 @@ impl Distribution<configs::api::Web3JsonRpcConfig> for EncodeDist {
             mempool_cache_update_interval: self.sample(rng),
             mempool_cache_size: self.sample(rng),
             whitelisted_tokens_for_aa: self.sample_range(rng).map(|_| rng.gen()).collect(),
+            all_tokens_allowed_for_aa: self.sample(rng),
         }
     }
 }
@@ -4,6 +4,7 @@ pub mod proto;
 mod tests;
 
 use anyhow::{anyhow, Context as _};
+use prost::Message as _;
 use zksync_consensus_roles::validator;
 use zksync_protobuf::{required, ProtoFmt, ProtoRepr};
 use zksync_types::{
@@ -90,6 +91,12 @@ impl ProtoFmt for Payload {
     }
 }
 
+/// Returns the length of the protobuf encoding of a transaction (as used in [`Payload`]). Unlike
+/// `zksync_protobuf::repr::encode()`, this doesn't materialize the encoded bytes.
+pub fn encoded_transaction_len(tx: &Transaction) -> usize {
+    proto::Transaction::build(tx).encoded_len()
+}
+
 impl Payload {
     pub fn decode(payload: &validator::Payload) -> anyhow::Result<Self> {
         zksync_protobuf::decode(&payload.0)
@@ -18,6 +18,21 @@ fn test_encoding() {
     );
 }
 
+#[test]
+fn encoded_transaction_len_matches_encoding() {
+    let txs: [Transaction; 3] = [
+        mock_l1_execute().into(),
+        mock_l2_transaction().into(),
+        mock_protocol_upgrade_transaction().into(),
+    ];
+    for tx in &txs {
+        assert_eq!(
+            super::encoded_transaction_len(tx),
+            encode::<super::proto::Transaction>(tx).len()
+        );
+    }
+}
+
 fn encode_decode<P, C>(msg: P::Type)
 where
     P: ProtoRepr,
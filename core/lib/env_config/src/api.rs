@@ -77,6 +77,12 @@ mod tests {
                 max_tx_size: 1000000,
                 vm_execution_cache_misses_limit: None,
                 vm_concurrency_limit: Some(512),
+                vm_concurrency_reserved_permits: Some(32),
+                vm_slow_acquire_threshold_ms: Some(250),
+                vm_max_queue_depth: Some(1000),
+                eth_call_max_response_size: Some(1_048_576),
+                eth_call_truncate_oversized_responses: true,
+                eth_call_max_cost: Some(10_000_000),
                 factory_deps_cache_size_mb: Some(128),
                 initial_writes_cache_size_mb: Some(32),
                 latest_values_cache_size_mb: Some(256),
@@ -91,6 +97,7 @@ mod tests {
                     addr("0x0000000000000000000000000000000000000001"),
                     addr("0x0000000000000000000000000000000000000002"),
                 ],
+                all_tokens_allowed_for_aa: true,
             },
             prometheus: PrometheusConfig {
                 listener_port: 3312,
@@ -128,6 +135,13 @@ mod tests {
             API_WEB3_JSON_RPC_ESTIMATE_GAS_ACCEPTABLE_OVERESTIMATION=1000
             API_WEB3_JSON_RPC_MAX_TX_SIZE=1000000
             API_WEB3_JSON_RPC_VM_CONCURRENCY_LIMIT=512
+            API_WEB3_JSON_RPC_VM_CONCURRENCY_RESERVED_PERMITS=32
+            API_WEB3_JSON_RPC_VM_SLOW_ACQUIRE_THRESHOLD_MS=250
+            API_WEB3_JSON_RPC_VM_MAX_QUEUE_DEPTH=1000
+            API_WEB3_JSON_RPC_ETH_CALL_MAX_RESPONSE_SIZE=1048576
+            API_WEB3_JSON_RPC_ETH_CALL_TRUNCATE_OVERSIZED_RESPONSES=true
+            API_WEB3_JSON_RPC_ETH_CALL_MAX_COST=10000000
+            API_WEB3_JSON_RPC_ALL_TOKENS_ALLOWED_FOR_AA=true
             API_WEB3_JSON_RPC_FACTORY_DEPS_CACHE_SIZE_MB=128
             API_WEB3_JSON_RPC_INITIAL_WRITES_CACHE_SIZE_MB=32
             API_WEB3_JSON_RPC_LATEST_VALUES_CACHE_SIZE_MB=256
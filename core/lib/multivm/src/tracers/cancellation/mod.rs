@@ -0,0 +1,31 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::glue::tracers::IntoOldVmTracer;
+
+pub mod vm_1_4_1;
+pub mod vm_1_4_2;
+pub mod vm_boojum_integration;
+pub mod vm_latest;
+pub mod vm_refunds_enhancement;
+pub mod vm_virtual_blocks;
+
+/// Tracer stopping the VM execution once the shared cancellation flag is set, e.g. from another thread.
+#[derive(Debug, Clone)]
+pub struct CancellationTracer {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationTracer {
+    pub fn new(cancelled: Arc<AtomicBool>) -> Self {
+        Self { cancelled }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl IntoOldVmTracer for CancellationTracer {}
@@ -0,0 +1,28 @@
+use zksync_state::WriteStorage;
+
+use crate::{
+    interface::{
+        tracer::{TracerExecutionStatus, TracerExecutionStopReason},
+        traits::tracers::dyn_tracers::vm_1_4_1::DynTracer,
+        Halt,
+    },
+    tracers::cancellation::CancellationTracer,
+    vm_1_4_2::{BootloaderState, HistoryMode, SimpleMemory, VmTracer, ZkSyncVmState},
+};
+
+impl<S, H: HistoryMode> DynTracer<S, SimpleMemory<H>> for CancellationTracer {}
+
+impl<S: WriteStorage, H: HistoryMode> VmTracer<S, H> for CancellationTracer {
+    fn finish_cycle(
+        &mut self,
+        _state: &mut ZkSyncVmState<S, H>,
+        _bootloader_state: &mut BootloaderState,
+    ) -> TracerExecutionStatus {
+        if self.is_cancelled() {
+            return TracerExecutionStatus::Stop(TracerExecutionStopReason::Abort(
+                Halt::TracerCustom("Execution cancelled".to_string()),
+            ));
+        }
+        TracerExecutionStatus::Continue
+    }
+}
@@ -0,0 +1,21 @@
+use zksync_state::WriteStorage;
+
+use crate::{
+    interface::dyn_tracers::vm_1_3_3::DynTracer,
+    tracers::cancellation::CancellationTracer,
+    vm_virtual_blocks::{
+        ExecutionEndTracer, ExecutionProcessing, HistoryMode, SimpleMemory, VmTracer,
+    },
+};
+
+impl<H: HistoryMode> ExecutionEndTracer<H> for CancellationTracer {
+    fn should_stop_execution(&self) -> bool {
+        self.is_cancelled()
+    }
+}
+
+impl<S, H: HistoryMode> DynTracer<S, SimpleMemory<H>> for CancellationTracer {}
+
+impl<S: WriteStorage, H: HistoryMode> ExecutionProcessing<S, H> for CancellationTracer {}
+
+impl<S: WriteStorage, H: HistoryMode> VmTracer<S, H> for CancellationTracer {}
@@ -82,6 +82,26 @@ impl ProtoRepr for proto::Web3JsonRpc {
                 .map(|x| x.try_into())
                 .transpose()
                 .context("vm_concurrency_limit")?,
+            vm_concurrency_reserved_permits: self
+                .vm_concurrency_reserved_permits
+                .map(|x| x.try_into())
+                .transpose()
+                .context("vm_concurrency_reserved_permits")?,
+            vm_slow_acquire_threshold_ms: self.vm_slow_acquire_threshold_ms,
+            vm_max_queue_depth: self
+                .vm_max_queue_depth
+                .map(|x| x.try_into())
+                .transpose()
+                .context("vm_max_queue_depth")?,
+            eth_call_max_response_size: self
+                .eth_call_max_response_size
+                .map(|x| x.try_into())
+                .transpose()
+                .context("eth_call_max_response_size")?,
+            eth_call_truncate_oversized_responses: self
+                .eth_call_truncate_oversized_responses
+                .unwrap_or(false),
+            eth_call_max_cost: self.eth_call_max_cost,
             factory_deps_cache_size_mb: self
                 .factory_deps_cache_size_mb
                 .map(|x| x.try_into())
@@ -127,6 +147,7 @@ impl ProtoRepr for proto::Web3JsonRpc {
                 .map(|(i, k)| parse_h160(k).context(i))
                 .collect::<Result<Vec<_>, _>>()
                 .context("account_pks")?,
+            all_tokens_allowed_for_aa: self.all_tokens_allowed_for_aa.unwrap_or(false),
         })
     }
     fn build(this: &Self::Type) -> Self {
@@ -159,6 +180,16 @@ impl ProtoRepr for proto::Web3JsonRpc {
                 .vm_execution_cache_misses_limit
                 .map(|x| x.try_into().unwrap()),
             vm_concurrency_limit: this.vm_concurrency_limit.map(|x| x.try_into().unwrap()),
+            vm_concurrency_reserved_permits: this
+                .vm_concurrency_reserved_permits
+                .map(|x| x.try_into().unwrap()),
+            vm_slow_acquire_threshold_ms: this.vm_slow_acquire_threshold_ms,
+            vm_max_queue_depth: this.vm_max_queue_depth.map(|x| x.try_into().unwrap()),
+            eth_call_max_response_size: this
+                .eth_call_max_response_size
+                .map(|x| x.try_into().unwrap()),
+            eth_call_truncate_oversized_responses: Some(this.eth_call_truncate_oversized_responses),
+            eth_call_max_cost: this.eth_call_max_cost,
             factory_deps_cache_size_mb: this
                 .factory_deps_cache_size_mb
                 .map(|x| x.try_into().unwrap()),
@@ -182,6 +213,7 @@ impl ProtoRepr for proto::Web3JsonRpc {
                 .iter()
                 .map(|k| format!("{:?}", k))
                 .collect(),
+            all_tokens_allowed_for_aa: Some(this.all_tokens_allowed_for_aa),
         }
     }
 }
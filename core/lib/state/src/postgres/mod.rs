@@ -342,6 +342,46 @@ impl PostgresStorageCaches {
             values.command_sender.send(to_miniblock).ok();
         }
     }
+
+    /// Preloads values for the specified storage `keys` into the VM storage values cache, so that the first reads
+    /// of known hot state don't hit Postgres. If the values cache is not configured, this is a no-op.
+    ///
+    /// Values are loaded for the miniblock the cache is currently valid for. Thus, this method is most useful
+    /// once the cache was updated to a recent miniblock; otherwise, primed values may be evicted by the next update.
+    ///
+    /// # Errors
+    ///
+    /// Propagates Postgres errors.
+    pub async fn prime(
+        &self,
+        keys: &[StorageKey],
+        connection: &mut Connection<'_, Core>,
+    ) -> anyhow::Result<()> {
+        let Some(values) = &self.values else {
+            return Ok(());
+        };
+        let miniblock_number = values.cache.valid_for();
+        let hashed_keys: Vec<_> = keys.iter().map(StorageKey::hashed_key).collect();
+        let stored_values = connection
+            .storage_logs_dal()
+            .get_storage_values(&hashed_keys, miniblock_number)
+            .await
+            .context("failed loading storage values to prime cache")?;
+
+        for (key, hashed_key) in keys.iter().zip(&hashed_keys) {
+            let value = stored_values
+                .get(hashed_key)
+                .copied()
+                .flatten()
+                .unwrap_or_default();
+            values.cache.insert(miniblock_number, *key, value);
+        }
+        tracing::debug!(
+            "Primed VM storage values cache with {} keys for miniblock #{miniblock_number}",
+            keys.len()
+        );
+        Ok(())
+    }
 }
 
 /// An asynchronous task that updates the VM storage values cache.
@@ -571,6 +571,148 @@ async fn using_values_cache() {
         .unwrap();
 }
 
+/// Regression test checking that the values cache updated to the latest miniblock doesn't leak values into
+/// reads at historical miniblocks, and vice versa.
+fn test_values_cache_for_historical_reads(pool: &ConnectionPool<Core>, rt_handle: Handle) {
+    let mut caches = PostgresStorageCaches::new(1_024, 1_024);
+    let task = caches.configure_storage_values_cache(1_024 * 1_024, pool.clone());
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let update_task_handle = tokio::task::spawn(task.run(stop_receiver));
+    let values_cache = caches.values.as_ref().unwrap().cache.clone();
+
+    let mut connection = rt_handle.block_on(pool.connection()).unwrap();
+    rt_handle.block_on(prepare_postgres(&mut connection));
+    let initial_logs = gen_storage_logs(0..20);
+    let modified_key = initial_logs[0].key;
+    let unmodified_key = initial_logs[1].key;
+    for number in 1..=2 {
+        let logs = vec![StorageLog::new_write_log(
+            modified_key,
+            H256::repeat_byte(number),
+        )];
+        rt_handle.block_on(create_miniblock(
+            &mut connection,
+            MiniblockNumber(number.into()),
+            logs,
+        ));
+    }
+    caches.schedule_values_update(MiniblockNumber(2));
+    rt_handle.block_on(wait_for_cache_update(&values_cache, MiniblockNumber(2)));
+
+    let expected_values = [
+        (MiniblockNumber(2), H256::repeat_byte(2)),
+        (MiniblockNumber(1), H256::repeat_byte(1)),
+        (MiniblockNumber(0), initial_logs[0].value),
+        (MiniblockNumber(2), H256::repeat_byte(2)),
+        (MiniblockNumber(1), H256::repeat_byte(1)),
+    ];
+    for (miniblock_number, expected_value) in expected_values {
+        let mut storage =
+            PostgresStorage::new(rt_handle.clone(), connection, miniblock_number, true)
+                .with_caches(caches.clone());
+        assert_eq!(
+            storage.read_value(&modified_key),
+            expected_value,
+            "{miniblock_number}"
+        );
+        assert_eq!(
+            storage.read_value(&unmodified_key),
+            initial_logs[1].value,
+            "{miniblock_number}"
+        );
+        connection = storage.connection;
+    }
+
+    // Values are only cached for the latest miniblock.
+    values_cache
+        .assertions(MiniblockNumber(2))
+        .assert_entries(&[
+            (modified_key, Some(H256::repeat_byte(2))),
+            (unmodified_key, Some(initial_logs[1].value)),
+        ]);
+    values_cache
+        .assertions(MiniblockNumber(1))
+        .assert_entries(&[(modified_key, None), (unmodified_key, None)]);
+
+    stop_sender.send_replace(true);
+    rt_handle
+        .block_on(update_task_handle)
+        .expect("update task panicked")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn using_values_cache_for_historical_reads() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let handle = Handle::current();
+    tokio::task::spawn_blocking(move || test_values_cache_for_historical_reads(&pool, handle))
+        .await
+        .unwrap();
+}
+
+fn test_priming_values_cache(pool: &ConnectionPool<Core>, rt_handle: Handle) {
+    let mut caches = PostgresStorageCaches::new(1_024, 1_024);
+    let _task = caches.configure_storage_values_cache(1_024 * 1_024, pool.clone());
+    let values_cache = caches.values.as_ref().unwrap().cache.clone();
+    let assertions = values_cache.assertions(MiniblockNumber(0));
+
+    let mut connection = rt_handle.block_on(pool.connection()).unwrap();
+    rt_handle.block_on(prepare_postgres(&mut connection));
+
+    let initial_logs = gen_storage_logs(0..20);
+    let non_existing_key = gen_storage_logs(100..120)[0].key;
+    let primed_keys = [initial_logs[0].key, initial_logs[1].key, non_existing_key];
+    assertions.assert_entries(&primed_keys.map(|key| (key, None)));
+
+    rt_handle
+        .block_on(caches.prime(&primed_keys, &mut connection))
+        .unwrap();
+    assertions.assert_entries(&[
+        (initial_logs[0].key, Some(initial_logs[0].value)),
+        (initial_logs[1].key, Some(initial_logs[1].value)),
+        (non_existing_key, Some(H256::zero())),
+    ]);
+
+    // Overwrite values in Postgres without updating the cache. Primed keys should still be served from the cache.
+    let overwritten_logs: Vec<_> = primed_keys
+        .iter()
+        .map(|&key| StorageLog::new_write_log(key, H256::repeat_byte(0xff)))
+        .collect();
+    rt_handle
+        .block_on(
+            connection
+                .storage_logs_dal()
+                .append_storage_logs(MiniblockNumber(0), &[(H256::zero(), overwritten_logs)]),
+        )
+        .unwrap();
+
+    let mut storage =
+        PostgresStorage::new(rt_handle, connection, MiniblockNumber(0), false).with_caches(caches);
+    assert_eq!(
+        storage.read_value(&initial_logs[0].key),
+        initial_logs[0].value
+    );
+    assert_eq!(
+        storage.read_value(&initial_logs[1].key),
+        initial_logs[1].value
+    );
+    assert_eq!(storage.read_value(&non_existing_key), H256::zero());
+    // A non-primed key is read from Postgres.
+    assert_eq!(
+        storage.read_value(&initial_logs[2].key),
+        initial_logs[2].value
+    );
+}
+
+#[tokio::test]
+async fn priming_values_cache() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let handle = Handle::current();
+    tokio::task::spawn_blocking(move || test_priming_values_cache(&pool, handle))
+        .await
+        .unwrap();
+}
+
 /// (Sort of) fuzzes [`ValuesCache`] by comparing outputs of [`PostgresStorage`] with and without caching
 /// on randomly generated `read_value()` queries.
 fn mini_fuzz_values_cache_inner(
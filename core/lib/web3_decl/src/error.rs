@@ -29,6 +29,13 @@ pub enum Web3Error {
     ProxyError(#[from] EnrichedClientError),
     #[error("{0}")]
     SubmitTransactionError(String, Vec<u8>),
+    /// Transaction validation error with a specific error code and machine-readable data describing the cause.
+    #[error("{message}")]
+    TransactionValidationError {
+        message: String,
+        code: i32,
+        data: serde_json::Value,
+    },
     #[error("Failed to serialize transaction: {0}")]
     SerializationError(#[from] SerializationTransactionError),
     #[error("More than four topics in filter")]
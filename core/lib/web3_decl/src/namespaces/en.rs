@@ -22,6 +22,16 @@ pub trait EnNamespace {
         include_transactions: bool,
     ) -> RpcResult<Option<en::SyncBlock>>;
 
+    /// Returns blocks in the range `[from..=to]` in ascending order. The response may be truncated: it is capped
+    /// by a server-side limit on the range size, and stops at the first block missing on the server.
+    #[method(name = "syncL2BlockRange")]
+    async fn sync_l2_block_range(
+        &self,
+        from: MiniblockNumber,
+        to: MiniblockNumber,
+        include_transactions: bool,
+    ) -> RpcResult<Vec<en::SyncBlock>>;
+
     #[method(name = "consensusGenesis")]
     async fn consensus_genesis(&self) -> RpcResult<Option<en::ConsensusGenesis>>;
 
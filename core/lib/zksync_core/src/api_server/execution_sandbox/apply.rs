@@ -6,7 +6,10 @@
 //!
 //! This module is intended to be blocking.
 
-use std::time::{Duration, Instant};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant},
+};
 
 use anyhow::Context as _;
 use multivm::{
@@ -31,11 +34,12 @@ use zksync_types::{
     AccountTreeId, L1BatchNumber, MiniblockNumber, Nonce, ProtocolVersionId, StorageKey,
     Transaction, H256, U256,
 };
-use zksync_utils::{h256_to_u256, time::seconds_since_epoch, u256_to_h256};
+use zksync_utils::{h256_to_u256, u256_to_h256};
 
 use super::{
+    pending_l1_batch_timestamp,
     vm_metrics::{self, SandboxStage, SANDBOX_METRICS},
-    BlockArgs, TxExecutionArgs, TxSharedArgs, VmPermit,
+    BlockArgs, SandboxExecutionError, TxExecutionArgs, TxSharedArgs, VmPermit,
 };
 
 type BoxedVm<'a> = Box<VmInstance<StorageView<PostgresStorage<'a>>, HistoryDisabled>>;
@@ -303,6 +307,9 @@ pub(super) fn apply_vm_in_sandbox<T>(
     let stage_started_at = Instant::now();
     let span = tracing::debug_span!("initialization").entered();
 
+    if !vm_permit.is_runtime_alive() {
+        return Err(SandboxExecutionError::Cancelled.into());
+    }
     let rt_handle = vm_permit.rt_handle();
     let connection = rt_handle
         .block_on(connection_pool.connection_tagged("api"))
@@ -330,7 +337,14 @@ pub(super) fn apply_vm_in_sandbox<T>(
         tx.nonce().unwrap_or(Nonce(0))
     );
     let execution_latency = SANDBOX_METRICS.sandbox[&SandboxStage::Execution].start();
-    let result = apply(&mut vm, tx);
+    // Storage accesses block on the runtime behind `vm_permit`, which panics if the runtime is shutting down.
+    // Such panics are expected and are converted to a cancellation error; other panics are propagated.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| apply(&mut vm, tx)));
+    if !vm_permit.is_runtime_alive() {
+        tracing::info!("Runtime is shutting down; cancelling VM execution for {tx_id}");
+        return Err(SandboxExecutionError::Cancelled.into());
+    }
+    let result = result.unwrap_or_else(panic::resume_unwind);
     let vm_execution_took = execution_latency.observe();
 
     let memory_metrics = vm.record_vm_memory_metrics();
@@ -430,6 +444,10 @@ impl BlockArgs {
         &self,
         connection: &mut Connection<'_, Core>,
     ) -> anyhow::Result<ResolvedBlockInfo> {
+        debug_assert!(
+            self.vm_compatible,
+            "block args created without L1 batch timestamp cannot be used for VM execution: {self:?}"
+        );
         let (state_l2_block_number, vm_l1_batch_number, l1_batch_timestamp);
 
         let miniblock_header = if self.is_pending_miniblock() {
@@ -445,16 +463,17 @@ impl BlockArgs {
                 .context("no miniblocks in storage")?;
 
             state_l2_block_number = sealed_miniblock_header.number;
-            // Timestamp of the next L1 batch must be greater than the timestamp of the last miniblock.
-            l1_batch_timestamp = seconds_since_epoch().max(sealed_miniblock_header.timestamp + 1);
+            // Timestamp of the next L1 batch must be greater than the timestamp of the last miniblock. The timestamp
+            // precomputed by `BlockArgs::pending_with_timestamp()` may be outdated if miniblocks were sealed since then.
+            l1_batch_timestamp = match self.l1_batch_timestamp_s {
+                Some(timestamp) => timestamp.max(sealed_miniblock_header.timestamp + 1),
+                None => pending_l1_batch_timestamp(Some(sealed_miniblock_header.timestamp)),
+            };
             sealed_miniblock_header
         } else {
-            vm_l1_batch_number = connection
-                .storage_web3_dal()
-                .resolve_l1_batch_number_of_miniblock(self.resolved_block_number)
-                .await
-                .context("failed resolving L1 batch for miniblock")?
-                .expected_l1_batch();
+            vm_l1_batch_number = self
+                .resolved_l1_batch_number
+                .context("L1 batch number is `None` for non-pending block args")?;
             l1_batch_timestamp = self
                 .l1_batch_timestamp_s
                 .context("L1 batch timestamp is `None` for non-pending block args")?;
@@ -26,6 +26,17 @@ pub(crate) enum SandboxExecutionError {
         that caused this error. Error description: {0}"
     )]
     UnexpectedVMBehavior(String),
+    #[error(
+        "Call is too expensive: estimated cost {estimate} gas exceeds the limit of {max_cost} gas"
+    )]
+    TooExpensive { estimate: u64, max_cost: u64 },
+    #[error("Call return data of {size} bytes exceeds the limit of {max_size} bytes")]
+    ResponseTooLarge { size: usize, max_size: usize },
+    #[error("Execution was cancelled because the server is shutting down")]
+    Cancelled,
+    /// Catch-all internal error (e.g., database error) that should not be exposed to the caller.
+    #[error("Internal error")]
+    Internal(#[from] anyhow::Error),
 }
 
 impl From<Halt> for SandboxExecutionError {
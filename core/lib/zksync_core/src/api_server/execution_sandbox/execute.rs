@@ -1,22 +1,32 @@
 //! Implementation of "executing" methods, e.g. `eth_call`.
 
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
 use anyhow::Context as _;
 use multivm::{
-    interface::{TxExecutionMode, VmExecutionResultAndLogs, VmInterface},
-    tracers::StorageInvocations,
+    interface::{ExecutionResult, TxExecutionMode, VmExecutionResultAndLogs, VmInterface},
+    tracers::{CancellationTracer, StorageInvocations},
     vm_latest::constants::ETH_CALL_GAS_LIMIT,
     MultiVMTracer,
 };
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use tracing::{span, Level};
 use zksync_dal::{ConnectionPool, Core};
 use zksync_types::{
-    fee::TransactionExecutionMetrics, l2::L2Tx, ExecuteTransactionCommon, Nonce,
-    PackedEthSignature, Transaction, U256,
+    api::CallTracerConfig, fee::TransactionExecutionMetrics, l2::L2Tx, vm_trace::Call,
+    ExecuteTransactionCommon, Nonce, PackedEthSignature, Transaction, U256,
 };
 
 #[cfg(test)]
 use super::testonly::MockTransactionExecutor;
-use super::{apply, vm_metrics, ApiTracer, BlockArgs, TxSharedArgs, VmPermit};
+use super::{
+    apply, vm_metrics, ApiTracer, ApiTracerOutput, BlockArgs, CompositeApiTracer,
+    SandboxExecutionError, TxSharedArgs, VmPermit,
+};
 
 #[derive(Debug)]
 pub(crate) struct TxExecutionArgs {
@@ -76,6 +86,39 @@ impl TxExecutionArgs {
     }
 }
 
+/// Bridges a [`CancellationToken`] to a flag observed by [`CancellationTracer`] in the VM loop, which runs
+/// on a blocking thread. The bridging task is aborted once the flag is dropped.
+#[derive(Debug)]
+pub(super) struct CancellationFlag {
+    flag: Arc<AtomicBool>,
+    bridge_task: JoinHandle<()>,
+}
+
+impl CancellationFlag {
+    /// Creates a flag for the specified `token`. Must be called in the context of a Tokio runtime.
+    pub fn new(token: CancellationToken) -> Self {
+        let flag = Arc::new(AtomicBool::new(token.is_cancelled()));
+        let bridge_task = tokio::spawn({
+            let flag = flag.clone();
+            async move {
+                token.cancelled().await;
+                flag.store(true, Ordering::Relaxed);
+            }
+        });
+        Self { flag, bridge_task }
+    }
+
+    pub fn tracer(&self) -> CancellationTracer {
+        CancellationTracer::new(self.flag.clone())
+    }
+}
+
+impl Drop for CancellationFlag {
+    fn drop(&mut self) {
+        self.bridge_task.abort();
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct TransactionExecutionOutput {
     /// Output of the VM.
@@ -86,6 +129,91 @@ pub(crate) struct TransactionExecutionOutput {
     pub are_published_bytecodes_ok: bool,
 }
 
+/// Returns the gas limit an `eth_call` is executed with: the limit declared by the caller capped by
+/// [`ETH_CALL_GAS_LIMIT`], or the cap itself if the caller hasn't declared a limit.
+fn eth_call_gas_limit(tx: &L2Tx) -> u64 {
+    let declared_limit = tx.common_data.fee.gas_limit;
+    if declared_limit.is_zero() {
+        ETH_CALL_GAS_LIMIT.into()
+    } else {
+        declared_limit.min(ETH_CALL_GAS_LIMIT.into()).as_u64()
+    }
+}
+
+/// Estimates the cost of an `eth_call` in gas without executing it.
+///
+/// A call to a contract is estimated as the gas limit it is executed with once admitted, which is an upper bound
+/// on the gas it can use. A call to an address without deployed code doesn't execute any contract code, so it is
+/// estimated as free.
+fn estimate_eth_call_cost(tx: &L2Tx, target_has_code: bool) -> u64 {
+    if target_has_code {
+        eth_call_gas_limit(tx)
+    } else {
+        0
+    }
+}
+
+/// Checks whether an `eth_call` can be admitted for execution given the per-call cost ceiling. Intended to be called
+/// before acquiring a [`VmPermit`], so that a few huge calls cannot saturate the VM concurrency limiter.
+///
+/// Returns the gas limit an admitted call must be executed with (see [`TransactionExecutor::execute_tx_eth_call()`]),
+/// so that the call cannot use more gas than estimated.
+pub(crate) fn check_eth_call_cost(
+    tx: &L2Tx,
+    target_has_code: bool,
+    max_cost: u64,
+) -> Result<Option<u64>, SandboxExecutionError> {
+    let estimate = estimate_eth_call_cost(tx, target_has_code);
+    if estimate > max_cost {
+        return Err(SandboxExecutionError::TooExpensive { estimate, max_cost });
+    }
+    Ok(target_has_code.then_some(estimate))
+}
+
+/// Action taken if the return data of an `eth_call` exceeds [`ResponseSizeLimit::max_size`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversizedResponsePolicy {
+    /// Truncate the return data to the maximum size.
+    Truncate,
+    /// Fail the call with an error.
+    Error,
+}
+
+/// Limit on the size of the return data of an `eth_call`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseSizeLimit {
+    /// Maximum size of the return data in bytes.
+    pub max_size: usize,
+    pub policy: OversizedResponsePolicy,
+}
+
+impl ResponseSizeLimit {
+    /// Enforces this limit on the return data of a successful execution. Reverts and halts are not affected.
+    fn enforce(&self, result: &mut ExecutionResult) -> Result<(), SandboxExecutionError> {
+        let ExecutionResult::Success { output } = result else {
+            return Ok(());
+        };
+        let size = output.len();
+        if size <= self.max_size {
+            return Ok(());
+        }
+        match self.policy {
+            OversizedResponsePolicy::Truncate => {
+                tracing::debug!(
+                    "Truncating call return data of {size} bytes to {} bytes",
+                    self.max_size
+                );
+                output.truncate(self.max_size);
+                Ok(())
+            }
+            OversizedResponsePolicy::Error => Err(SandboxExecutionError::ResponseTooLarge {
+                size,
+                max_size: self.max_size,
+            }),
+        }
+    }
+}
+
 /// Executor of transactions.
 #[derive(Debug)]
 pub(crate) enum TransactionExecutor {
@@ -124,6 +252,12 @@ impl TransactionExecutor {
             .as_ref()
             .map_or(0, |deps| deps.len() as u16);
 
+        let runtime_shutdown_flag = vm_permit.runtime_shutdown_flag();
+        let cancellation = CancellationToken::new();
+        // Stops the VM if this future is dropped (e.g., because the client has disconnected), so that the permit
+        // is released early.
+        let _cancel_on_drop = cancellation.clone().drop_guard();
+        let cancellation_flag = CancellationFlag::new(cancellation);
         let (published_bytecodes, execution_result) = tokio::task::spawn_blocking(move || {
             let span = span!(Level::DEBUG, "execute_in_sandbox").entered();
             let result = apply::apply_vm_in_sandbox(
@@ -137,10 +271,15 @@ impl TransactionExecutor {
                 |vm, tx| {
                     let storage_invocation_tracer =
                         StorageInvocations::new(execution_args.missed_storage_invocation_limit);
+                    let cancellation_tracer = CancellationTracer::new(runtime_shutdown_flag);
                     let custom_tracers: Vec<_> = custom_tracers
                         .into_iter()
                         .map(|tracer| tracer.into_boxed())
-                        .chain(vec![storage_invocation_tracer.into_tracer_pointer()])
+                        .chain(vec![
+                            storage_invocation_tracer.into_tracer_pointer(),
+                            cancellation_tracer.into_tracer_pointer(),
+                            cancellation_flag.tracer().into_tracer_pointer(),
+                        ])
                         .collect();
                     vm.inspect_transaction_with_bytecode_compression(
                         custom_tracers.into(),
@@ -164,6 +303,8 @@ impl TransactionExecutor {
         })
     }
 
+    /// Executes `tx` as an `eth_call`. If `gas_limit` is not specified, the call is executed with
+    /// [`ETH_CALL_GAS_LIMIT`] regardless of the gas limit declared in `tx`.
     #[allow(clippy::too_many_arguments)]
     pub async fn execute_tx_eth_call(
         &self,
@@ -173,6 +314,8 @@ impl TransactionExecutor {
         mut tx: L2Tx,
         block_args: BlockArgs,
         vm_execution_cache_misses_limit: Option<usize>,
+        gas_limit: Option<u64>,
+        response_size_limit: Option<ResponseSizeLimit>,
         custom_tracers: Vec<ApiTracer>,
     ) -> anyhow::Result<VmExecutionResultAndLogs> {
         let enforced_base_fee = tx.common_data.fee.max_fee_per_gas.as_u64();
@@ -186,8 +329,8 @@ impl TransactionExecutor {
         // Protection against infinite-loop eth_calls and alike:
         // limiting the amount of gas the call can use.
         // We can't use `BLOCK_ERGS_LIMIT` here since the VM itself has some overhead.
-        tx.common_data.fee.gas_limit = ETH_CALL_GAS_LIMIT.into();
-        let output = self
+        tx.common_data.fee.gas_limit = gas_limit.unwrap_or(ETH_CALL_GAS_LIMIT.into()).into();
+        let mut output = self
             .execute_tx_in_sandbox(
                 vm_permit,
                 shared_args,
@@ -199,6 +342,71 @@ impl TransactionExecutor {
                 custom_tracers,
             )
             .await?;
+        if let Some(limit) = response_size_limit {
+            limit.enforce(&mut output.vm.result)?;
+        }
         Ok(output.vm)
     }
+
+    /// Executes `tx` in the same way as [`Self::execute_tx_eth_call()`] and returns the top-level call
+    /// with the call trace (e.g., for `debug_traceCall`). Reverts are recorded in the returned call;
+    /// halts are returned as errors.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn trace_call(
+        &self,
+        vm_permit: VmPermit,
+        shared_args: TxSharedArgs,
+        connection_pool: ConnectionPool<Core>,
+        tx: L2Tx,
+        block_args: BlockArgs,
+        vm_execution_cache_misses_limit: Option<usize>,
+        response_size_limit: Option<ResponseSizeLimit>,
+        tracer_config: CallTracerConfig,
+    ) -> Result<Call, SandboxExecutionError> {
+        // Tracing calls is not needed if we only need the top call.
+        let mut tracers = CompositeApiTracer::default();
+        if !tracer_config.only_top_call {
+            tracers.push(ApiTracer::CallTracer(Arc::default()));
+        }
+
+        let result = self
+            .execute_tx_eth_call(
+                vm_permit,
+                shared_args,
+                connection_pool,
+                tx.clone(),
+                block_args,
+                vm_execution_cache_misses_limit,
+                None,
+                response_size_limit,
+                tracers.tracers(),
+            )
+            .await
+            .map_err(|err| match err.downcast::<SandboxExecutionError>() {
+                Ok(err) => err,
+                Err(err) => SandboxExecutionError::Internal(err),
+            })?;
+
+        let (output, revert_reason) = match result.result {
+            ExecutionResult::Success { output } => (output, None),
+            ExecutionResult::Revert { output } => (vec![], Some(output.to_string())),
+            ExecutionResult::Halt { reason } => return Err(reason.into()),
+        };
+        let calls = tracers
+            .into_outputs()
+            .into_iter()
+            .find_map(|output| match output {
+                ApiTracerOutput::CallTracer(calls) => calls,
+            })
+            .unwrap_or_default();
+        Ok(Call::new_high_level(
+            tx.common_data.fee.gas_limit.as_u64(),
+            result.statistics.gas_used,
+            tx.execute.value,
+            tx.execute.calldata,
+            output,
+            revert_reason,
+            calls,
+        ))
+    }
 }
@@ -1,26 +1,38 @@
 use std::{
-    sync::{Arc, RwLock},
+    cell::Cell,
+    future::{self, Future},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
     time::{Duration, Instant},
 };
 
 use anyhow::Context as _;
+use multivm::vm_latest::constants::BATCH_COMPUTATIONAL_GAS_LIMIT;
 use rand::{thread_rng, Rng};
-use tokio::runtime::Handle;
+use tokio::{runtime::Handle, sync::OwnedSemaphorePermit};
+use vise::{EncodeLabelSet, EncodeLabelValue};
 use zksync_dal::{pruning_dal::PruningInfo, Connection, Core, CoreDal, DalError};
 use zksync_state::PostgresStorageCaches;
+use zksync_system_constants::L1_GAS_PER_PUBDATA_BYTE;
 use zksync_types::{
-    api, fee_model::BatchFeeInput, AccountTreeId, Address, L1BatchNumber, L2ChainId,
-    MiniblockNumber,
+    api, fee_model::BatchFeeInput, AccountTreeId, L1BatchNumber, L2ChainId, MiniblockNumber,
 };
+use zksync_utils::time::seconds_since_epoch;
 
-use self::vm_metrics::SandboxStage;
+use self::vm_metrics::{CacheRefresh, SandboxQuery, SandboxStage};
 pub(super) use self::{
     error::SandboxExecutionError,
-    execute::{TransactionExecutor, TxExecutionArgs},
-    tracers::ApiTracer,
-    validate::ValidationError,
+    execute::{check_eth_call_cost, TransactionExecutor, TxExecutionArgs},
+    tracers::{ApiTracer, ApiTracerOutput, CompositeApiTracer},
+    validate::{AaTokenPolicy, ValidationError},
     vm_metrics::{SubmitTxStage, SANDBOX_METRICS},
 };
+pub use self::{
+    execute::{OversizedResponsePolicy, ResponseSizeLimit},
+    validate::AaTokenWhitelist,
+};
 use super::tx_sender::MultiVMBaseSystemContracts;
 
 // Note: keep the modules private, and instead re-export functions that make public interface.
@@ -35,6 +47,17 @@ mod tracers;
 mod validate;
 mod vm_metrics;
 
+/// Priority tier of a [`VmConcurrencyLimiter`] caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+#[metrics(label = "priority", rename_all = "snake_case")]
+pub enum VmPriority {
+    /// Latency-sensitive calls (e.g., gas estimation or transaction submission). Such callers can use permits
+    /// reserved for them in addition to shared permits.
+    Interactive,
+    /// Calls that can tolerate waiting (e.g., `eth_call` or tracing). Such callers can only use shared permits.
+    Background,
+}
+
 /// Permit to invoke VM code.
 ///
 /// Any publicly-facing method that invokes VM is expected to accept a reference to this structure,
@@ -43,13 +66,151 @@ mod vm_metrics;
 pub struct VmPermit {
     /// A handle to the runtime that is used to query the VM storage.
     rt_handle: Handle,
-    _permit: Arc<tokio::sync::OwnedSemaphorePermit>,
+    rt_liveness: RuntimeLiveness,
+    priority: VmPriority,
+    _permit: Arc<TrackedPermit>,
 }
 
 impl VmPermit {
     fn rt_handle(&self) -> &Handle {
         &self.rt_handle
     }
+
+    /// Checks whether the runtime behind [`Self::rt_handle()`] is alive, i.e. hasn't started shutting down.
+    /// Once the runtime is shutting down, blocking on futures using it may panic.
+    fn is_runtime_alive(&self) -> bool {
+        !self.rt_liveness.is_shut_down()
+    }
+
+    /// Returns a flag that is set once the runtime behind [`Self::rt_handle()`] starts shutting down.
+    fn runtime_shutdown_flag(&self) -> Arc<AtomicBool> {
+        self.rt_liveness.shut_down.clone()
+    }
+
+    /// Returns the priority tier this permit was acquired with.
+    pub fn priority(&self) -> VmPriority {
+        self.priority
+    }
+}
+
+/// Observes shutdown of a Tokio runtime. Tokio doesn't expose the runtime state via [`Handle`], so the shutdown
+/// is detected with a sentinel task spawned on the runtime: the runtime drops all its tasks when shutting down.
+#[derive(Debug, Clone)]
+struct RuntimeLiveness {
+    shut_down: Arc<AtomicBool>,
+}
+
+impl RuntimeLiveness {
+    fn new(rt_handle: &Handle) -> Self {
+        let shut_down = Arc::new(AtomicBool::new(false));
+        let sentinel = RuntimeSentinel(shut_down.clone());
+        rt_handle.spawn(async move {
+            let _sentinel = sentinel;
+            future::pending::<()>().await
+        });
+        Self { shut_down }
+    }
+
+    fn is_shut_down(&self) -> bool {
+        self.shut_down.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug)]
+struct RuntimeSentinel(Arc<AtomicBool>);
+
+impl Drop for RuntimeSentinel {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Semaphore permit that reports its hold time to [`PermitStats`] once dropped.
+#[derive(Debug)]
+struct TrackedPermit {
+    _permit: OwnedSemaphorePermit,
+    priority: VmPriority,
+    acquired_at: Instant,
+    /// Hold time after which the permit is reported as slow.
+    slow_threshold: Option<Duration>,
+    stats: Arc<PermitStats>,
+}
+
+impl Drop for TrackedPermit {
+    fn drop(&mut self) {
+        let hold_time = self.acquired_at.elapsed();
+        SANDBOX_METRICS.sandbox_permit_hold_time[&self.priority].observe(hold_time);
+        self.stats.record_hold_time(hold_time);
+        if self
+            .slow_threshold
+            .is_some_and(|threshold| hold_time > threshold)
+        {
+            tracing::warn!(
+                "VM permit with {:?} priority was held for {hold_time:?}, which exceeds the slow permit threshold {:?}",
+                self.priority,
+                self.slow_threshold
+            );
+            SANDBOX_METRICS.sandbox_slow_permits[&self.priority].inc();
+            self.stats.slow_permits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Accounting shared between a [`VmConcurrencyLimiter`] and the permits it has issued.
+#[derive(Debug, Default)]
+struct PermitStats {
+    /// Number of callers currently waiting for a permit.
+    waiting: AtomicUsize,
+    /// Exponential moving average of permit hold times in microseconds; 0 if no permits were dropped yet.
+    avg_hold_time_us: AtomicU64,
+    /// Number of dropped permits that were held longer than the slow permit threshold.
+    slow_permits: AtomicUsize,
+    /// Number of permit acquisitions that waited longer than the starvation threshold.
+    starved_acquisitions: AtomicUsize,
+    /// Number of permit acquisitions that waited longer than the slow acquisition threshold.
+    slow_acquisitions: AtomicUsize,
+}
+
+impl PermitStats {
+    /// Weight of the previous average in the moving average, as `1 - 1 / HOLD_TIME_SMOOTHING`.
+    const HOLD_TIME_SMOOTHING: u64 = 8;
+
+    fn record_hold_time(&self, hold_time: Duration) {
+        let sample = u64::try_from(hold_time.as_micros())
+            .unwrap_or(u64::MAX)
+            .max(1);
+        self.avg_hold_time_us
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |avg| {
+                Some(if avg == 0 {
+                    sample
+                } else {
+                    avg - avg / Self::HOLD_TIME_SMOOTHING + sample / Self::HOLD_TIME_SMOOTHING
+                })
+            })
+            .ok();
+    }
+
+    fn avg_hold_time(&self) -> Duration {
+        Duration::from_micros(self.avg_hold_time_us.load(Ordering::Relaxed))
+    }
+}
+
+/// Tracks a caller waiting for a permit; decrements the waiting counter on drop, so that
+/// cancelled acquisitions are accounted for as well.
+#[derive(Debug)]
+struct WaitingGuard<'a>(&'a AtomicUsize);
+
+impl<'a> WaitingGuard<'a> {
+    fn new(waiting: &'a AtomicUsize) -> Self {
+        waiting.fetch_add(1, Ordering::Relaxed);
+        Self(waiting)
+    }
+}
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 /// Barrier-like synchronization primitive allowing to close a [`VmConcurrencyLimiter`] it's attached to
@@ -57,6 +218,7 @@ impl VmPermit {
 #[derive(Debug, Clone)]
 pub struct VmConcurrencyBarrier {
     limiter: Arc<tokio::sync::Semaphore>,
+    reserved_limiter: Arc<tokio::sync::Semaphore>,
     max_concurrency: usize,
 }
 
@@ -64,26 +226,65 @@ impl VmConcurrencyBarrier {
     /// Shuts down the related VM concurrency limiter so that it won't issue new permits.
     pub fn close(&self) {
         self.limiter.close();
+        self.reserved_limiter.close();
         tracing::info!("VM concurrency limiter closed");
     }
 
-    /// Waits until all permits issued by the VM concurrency limiter are dropped.
+    /// Waits until all permits issued by the VM concurrency limiter are dropped. Weighted permits are accounted for
+    /// by their weight, i.e., the wait finishes once all semaphore permits are returned.
     pub async fn wait_until_stopped(self) {
-        const POLL_INTERVAL: Duration = Duration::from_millis(50);
+        self.assert_closed();
+        self.wait_for_permits().await;
+    }
 
+    /// Same as [`Self::wait_until_stopped()`], but gives up once the specified `deadline` elapses.
+    ///
+    /// # Errors
+    ///
+    /// Returns the number of permits that are still not dropped if the deadline has elapsed. A weighted permit
+    /// contributes its weight to this number.
+    pub async fn wait_until_stopped_with_deadline(self, deadline: Duration) -> Result<(), usize> {
+        self.assert_closed();
+        if tokio::time::timeout(deadline, self.wait_for_permits())
+            .await
+            .is_ok()
+        {
+            return Ok(());
+        }
+        let remaining_permits = self.remaining_permits();
+        tracing::warn!(
+            "Deadline {deadline:?} elapsed while waiting until all VM permits are dropped; \
+             currently remaining: {remaining_permits} / {}",
+            self.max_concurrency
+        );
+        Err(remaining_permits)
+    }
+
+    fn assert_closed(&self) {
         assert!(
-            self.limiter.is_closed(),
+            self.limiter.is_closed() && self.reserved_limiter.is_closed(),
             "Cannot wait on non-closed VM concurrency limiter"
         );
+    }
+
+    /// Returns the number of semaphore permits currently held. Since a weighted [`VmPermit`] holds all its permits
+    /// until it's dropped, this is the sum of weights of alive permits.
+    fn remaining_permits(&self) -> usize {
+        let current_permits =
+            self.limiter.available_permits() + self.reserved_limiter.available_permits();
+        self.max_concurrency.saturating_sub(current_permits)
+    }
+
+    async fn wait_for_permits(&self) {
+        const POLL_INTERVAL: Duration = Duration::from_millis(50);
 
         loop {
-            let current_permits = self.limiter.available_permits();
+            let remaining_permits = self.remaining_permits();
             tracing::debug!(
-                "Waiting until all VM permits are dropped; currently remaining: {} / {}",
-                self.max_concurrency - current_permits,
+                "Waiting until all VM permits are dropped; currently remaining: {remaining_permits} / {}",
                 self.max_concurrency
             );
-            if current_permits == self.max_concurrency {
+            if remaining_permits == 0 {
                 return;
             }
             tokio::time::sleep(POLL_INTERVAL).await;
@@ -91,6 +292,21 @@ impl VmConcurrencyBarrier {
     }
 }
 
+/// Error acquiring a [`VmPermit`] from [`VmConcurrencyLimiter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum AcquireError {
+    /// The limiter was closed via [`VmConcurrencyBarrier::close()`], either before or while waiting for a permit.
+    #[error("node is shutting down")]
+    ShuttingDown,
+    /// No permit has become available within the timeout passed to [`VmConcurrencyLimiter::acquire_timeout()`].
+    #[error("timed out waiting for a VM permit")]
+    Timeout,
+    /// No permit is available, and the number of callers waiting for a permit has reached the limit
+    /// set via [`VmConcurrencyLimiter::with_max_queue_depth()`].
+    #[error("too many callers waiting for a VM permit")]
+    Overloaded,
+}
+
 /// Synchronization primitive that limits the number of concurrent VM executions.
 /// This is required to prevent the server from being overloaded with the VM calls.
 ///
@@ -100,55 +316,319 @@ impl VmConcurrencyBarrier {
 /// Note that the actual limit on the number of VMs is a minimum of the limit in this structure,
 /// *and* the size of the blocking tokio threadpool. So, even if the limit is set to 1024, but
 /// tokio is configured to have no more than 512 blocking threads, the actual limit will be 512.
+///
+/// A part of permits may be reserved for [`VmPriority::Interactive`] callers, so that they are not starved
+/// by [`VmPriority::Background`] ones.
 #[derive(Debug)]
 pub struct VmConcurrencyLimiter {
-    /// Semaphore that limits the number of concurrent VM executions.
+    /// Semaphore with permits shared by all callers.
     limiter: Arc<tokio::sync::Semaphore>,
+    /// Semaphore with permits reserved for interactive callers.
+    reserved_limiter: Arc<tokio::sync::Semaphore>,
+    /// Total number of permits, including reserved ones.
+    max_concurrency: usize,
+    /// Number of permits reserved for interactive callers.
+    reserved_permits: usize,
+    /// Hold time after which permits are reported as slow.
+    slow_permit_threshold: Option<Duration>,
+    /// Wait time after which permit acquisitions are reported as starved.
+    starvation_threshold: Option<Duration>,
+    /// Wait time after which permit acquisitions are logged as slow.
+    slow_acquire_threshold: Option<Duration>,
+    /// Maximum number of callers waiting for a permit; further callers are rejected.
+    max_queue_depth: Option<usize>,
+    stats: Arc<PermitStats>,
     rt_handle: Handle,
+    rt_liveness: RuntimeLiveness,
 }
 
 impl VmConcurrencyLimiter {
+    /// Default hold time after which permits are reported as slow.
+    const DEFAULT_SLOW_PERMIT_THRESHOLD: Duration = Duration::from_secs(10);
+    /// Default wait time after which permit acquisitions are reported as starved.
+    const DEFAULT_STARVATION_THRESHOLD: Duration = Duration::from_secs(1);
+
     /// Creates a limiter together with a barrier allowing to control its shutdown.
-    pub fn new(max_concurrency: usize) -> (Self, VmConcurrencyBarrier) {
+    ///
+    /// Permit acquisitions waiting longer than `slow_acquire_threshold` are logged as slow (at the warning level,
+    /// with structured `available_permits`, `waited_ms` and `max_concurrency` fields). `None` disables logging.
+    pub fn new(
+        max_concurrency: usize,
+        slow_acquire_threshold: Option<Duration>,
+    ) -> (Self, VmConcurrencyBarrier) {
+        Self::with_reserved_permits(max_concurrency, 0, slow_acquire_threshold)
+    }
+
+    /// Creates a limiter with `reserved_permits` out of `max_concurrency` permits only available
+    /// to [`VmPriority::Interactive`] callers. See [`Self::new()`] for `slow_acquire_threshold`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reserved_permits` exceeds `max_concurrency`.
+    pub fn with_reserved_permits(
+        max_concurrency: usize,
+        reserved_permits: usize,
+        slow_acquire_threshold: Option<Duration>,
+    ) -> (Self, VmConcurrencyBarrier) {
+        assert!(
+            reserved_permits <= max_concurrency,
+            "Number of reserved VM permits ({reserved_permits}) exceeds max concurrency ({max_concurrency})"
+        );
         tracing::info!(
-            "Initializing the VM concurrency limiter with max concurrency {max_concurrency}"
+            "Initializing the VM concurrency limiter with max concurrency {max_concurrency} \
+             ({reserved_permits} permits reserved for interactive calls)"
         );
-        let limiter = Arc::new(tokio::sync::Semaphore::new(max_concurrency));
+        let limiter = Arc::new(tokio::sync::Semaphore::new(
+            max_concurrency - reserved_permits,
+        ));
+        let reserved_limiter = Arc::new(tokio::sync::Semaphore::new(reserved_permits));
+        let rt_handle = Handle::current();
+        let rt_liveness = RuntimeLiveness::new(&rt_handle);
 
         let this = Self {
             limiter: Arc::clone(&limiter),
-            rt_handle: Handle::current(),
+            reserved_limiter: Arc::clone(&reserved_limiter),
+            max_concurrency,
+            reserved_permits,
+            slow_permit_threshold: Some(Self::DEFAULT_SLOW_PERMIT_THRESHOLD),
+            starvation_threshold: Some(Self::DEFAULT_STARVATION_THRESHOLD),
+            slow_acquire_threshold,
+            max_queue_depth: None,
+            stats: Arc::default(),
+            rt_handle,
+            rt_liveness,
         };
         let barrier = VmConcurrencyBarrier {
             limiter,
+            reserved_limiter,
             max_concurrency,
         };
         (this, barrier)
     }
 
-    /// Waits until there is a free slot in the concurrency limiter.
+    /// Sets the hold time after which permits are reported as slow (by logging a warning and incrementing a metric
+    /// once the permit is dropped). `None` disables reporting. By default, permits held longer than 10 seconds are reported.
+    pub fn with_slow_permit_threshold(mut self, threshold: Option<Duration>) -> Self {
+        self.slow_permit_threshold = threshold;
+        self
+    }
+
+    /// Sets the wait time after which permit acquisitions are reported as starved (by incrementing a metric).
+    /// `None` disables reporting. By default, acquisitions waiting longer than 1 second are reported.
+    pub fn with_starvation_threshold(mut self, threshold: Option<Duration>) -> Self {
+        self.starvation_threshold = threshold;
+        self
+    }
+
+    /// Sets the maximum number of callers waiting for a permit. If no permit is available and this number
+    /// of callers is already waiting, acquiring a permit fails immediately with [`AcquireError::Overloaded`],
+    /// so that excess load is shed rather than queued until it times out. `None` (the default) doesn't limit
+    /// the queue.
+    pub fn with_max_queue_depth(mut self, max_queue_depth: Option<usize>) -> Self {
+        self.max_queue_depth = max_queue_depth;
+        self
+    }
+
+    fn available_permits(&self) -> usize {
+        self.limiter.available_permits() + self.reserved_limiter.available_permits()
+    }
+
+    /// Checks whether a caller with the specified priority can acquire `weight` permits without waiting.
+    /// Background callers can only use shared permits, and a permit is never split between shared and reserved ones.
+    fn has_permits_for(&self, priority: VmPriority, weight: u32) -> bool {
+        let fits =
+            |semaphore: &tokio::sync::Semaphore| semaphore.available_permits() >= weight as usize;
+        match priority {
+            VmPriority::Interactive => fits(&self.reserved_limiter) || fits(&self.limiter),
+            VmPriority::Background => fits(&self.limiter),
+        }
+    }
+
+    /// Waits until there is a free slot in the concurrency limiter for a caller with the specified priority.
     /// Returns a permit that should be dropped when the VM execution is finished.
-    pub async fn acquire(&self) -> Option<VmPermit> {
-        let available_permits = self.limiter.available_permits();
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AcquireError::ShuttingDown`] if the limiter is closed, including if it's closed while waiting.
+    pub async fn acquire(&self, priority: VmPriority) -> Result<VmPermit, AcquireError> {
+        self.acquire_weighted(priority, 1).await
+    }
+
+    /// Same as [`Self::acquire()`], but acquires `weight` permits at once, e.g. for calls that run the VM
+    /// multiple times. All permits are released once the returned [`VmPermit`] (and all its clones) are dropped.
+    ///
+    /// The weight is clamped to `1..=capacity`, where `capacity` is the maximum number of permits available
+    /// to the caller (i.e., either shared or reserved ones; a permit is never split between them).
+    pub async fn acquire_weighted(
+        &self,
+        priority: VmPriority,
+        weight: u32,
+    ) -> Result<VmPermit, AcquireError> {
+        self.acquire_inner(priority, weight, Duration::MAX).await
+    }
+
+    /// Same as [`Self::acquire()`], but gives up waiting for a permit after the specified `timeout`,
+    /// returning [`AcquireError::Timeout`].
+    pub async fn acquire_timeout(
+        &self,
+        priority: VmPriority,
+        timeout: Duration,
+    ) -> Result<VmPermit, AcquireError> {
+        self.acquire_inner(priority, 1, timeout).await
+    }
+
+    fn clamp_weight(&self, priority: VmPriority, weight: u32) -> u32 {
+        let shared_permits = self.max_concurrency - self.reserved_permits;
+        let capacity = match priority {
+            VmPriority::Interactive => shared_permits.max(self.reserved_permits),
+            VmPriority::Background => shared_permits,
+        };
+        let capacity = u32::try_from(capacity).unwrap_or(u32::MAX).max(1);
+        weight.clamp(1, capacity)
+    }
+
+    async fn acquire_inner(
+        &self,
+        priority: VmPriority,
+        weight: u32,
+        timeout: Duration,
+    ) -> Result<VmPermit, AcquireError> {
+        let weight = self.clamp_weight(priority, weight);
+        let available_permits = self.available_permits();
         SANDBOX_METRICS
             .sandbox_execution_permits
             .observe(available_permits);
 
+        if let Some(max_queue_depth) = self.max_queue_depth {
+            // The number of waiting callers is approximate, since it's not synchronized with acquiring permits.
+            let waiting = self.stats.waiting.load(Ordering::Relaxed);
+            if waiting >= max_queue_depth && !self.has_permits_for(priority, weight) {
+                SANDBOX_METRICS.sandbox_permit_acquire_rejections[&priority].inc();
+                tracing::debug!(
+                    "Rejected VM permit acquisition: {waiting} callers are waiting for a permit (max: {max_queue_depth})"
+                );
+                return Err(AcquireError::Overloaded);
+            }
+        }
+
         let latency = SANDBOX_METRICS.sandbox[&SandboxStage::VmConcurrencyLimiterAcquire].start();
-        let permit = Arc::clone(&self.limiter).acquire_owned().await.ok()?;
-        let elapsed = latency.observe();
-        // We don't want to emit too many logs.
-        if elapsed > Duration::from_millis(10) {
+        let waiting_guard = WaitingGuard::new(&self.stats.waiting);
+        let acquire_permit = async {
+            match priority {
+                VmPriority::Interactive => self.acquire_interactive(weight).await,
+                VmPriority::Background => Arc::clone(&self.limiter)
+                    .acquire_many_owned(weight)
+                    .await
+                    .ok(),
+            }
+        };
+        let Ok(permit) = tokio::time::timeout(timeout, acquire_permit).await else {
+            SANDBOX_METRICS.sandbox_permit_acquire_timeouts.inc();
             tracing::debug!(
-                "Permit is obtained. Available permits: {available_permits}. Took {elapsed:?}"
+                "Timed out waiting for a VM permit after {timeout:?}. Available permits: {available_permits}"
             );
+            return Err(AcquireError::Timeout);
+        };
+        let permit = permit.ok_or(AcquireError::ShuttingDown)?;
+        drop(waiting_guard);
+        let elapsed = latency.observe();
+        SANDBOX_METRICS.sandbox_permit_acquire_latency[&priority].observe(elapsed);
+        if self
+            .starvation_threshold
+            .map_or(false, |threshold| elapsed > threshold)
+        {
+            SANDBOX_METRICS.sandbox_starved_permit_acquisitions[&priority].inc();
+            self.stats
+                .starved_acquisitions
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(threshold) = self
+            .slow_acquire_threshold
+            .filter(|&threshold| elapsed > threshold)
+        {
+            self.report_slow_acquire(priority, available_permits, elapsed, threshold);
         }
 
-        Some(VmPermit {
+        Ok(VmPermit {
             rt_handle: self.rt_handle.clone(),
-            _permit: Arc::new(permit),
+            rt_liveness: self.rt_liveness.clone(),
+            priority,
+            _permit: Arc::new(TrackedPermit {
+                _permit: permit,
+                priority,
+                acquired_at: Instant::now(),
+                slow_threshold: self.slow_permit_threshold,
+                stats: Arc::clone(&self.stats),
+            }),
         })
     }
+
+    fn report_slow_acquire(
+        &self,
+        priority: VmPriority,
+        available_permits: usize,
+        elapsed: Duration,
+        threshold: Duration,
+    ) {
+        self.stats.slow_acquisitions.fetch_add(1, Ordering::Relaxed);
+        let waited_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        let span = tracing::warn_span!(
+            "slow_vm_permit_acquire",
+            available_permits,
+            waited_ms,
+            max_concurrency = self.max_concurrency,
+            ?priority
+        );
+        let _entered = span.enter();
+        tracing::warn!("Waited {elapsed:?} for a VM permit (threshold: {threshold:?})");
+    }
+
+    /// Acquires either `weight` reserved or `weight` shared permits, whichever become available first. Reserved permits
+    /// are preferred, so that shared ones remain available for background callers.
+    ///
+    /// The caller is only queued on semaphores that have at least `weight` permits in total. Semaphores are fair
+    /// and hand out permits to queued callers one by one, so a caller queued for more permits than a semaphore has
+    /// would hold its permits and block all subsequent callers forever.
+    async fn acquire_interactive(&self, weight: u32) -> Option<OwnedSemaphorePermit> {
+        if let Ok(permit) = Arc::clone(&self.reserved_limiter).try_acquire_many_owned(weight) {
+            return Some(permit);
+        }
+
+        let weight_usize = weight as usize;
+        let fits_reserved = weight_usize <= self.reserved_permits;
+        let fits_shared = weight_usize <= self.max_concurrency - self.reserved_permits;
+        let reserved = Arc::clone(&self.reserved_limiter).acquire_many_owned(weight);
+        let shared = Arc::clone(&self.limiter).acquire_many_owned(weight);
+        match (fits_reserved, fits_shared) {
+            (true, true) => tokio::select! {
+                permit = reserved => permit.ok(),
+                permit = shared => permit.ok(),
+            },
+            (true, false) => reserved.await.ok(),
+            // If the weight fits neither semaphore (i.e., the limiter has no permits at all), the caller is queued
+            // on shared permits, like background callers.
+            (false, _) => shared.await.ok(),
+        }
+    }
+
+    /// Estimates how long a call to [`Self::acquire()`] made right now would wait for a permit.
+    ///
+    /// The estimate is based on the number of callers already queued for a permit and the moving average
+    /// of recent permit hold times: every `max_concurrency` callers ahead in the queue are assumed to take
+    /// one average hold time to be served. If no permits were released yet, the estimate is zero.
+    pub fn estimated_wait(&self) -> Duration {
+        let available_permits = self.available_permits();
+        let waiting = self.stats.waiting.load(Ordering::Relaxed);
+        if available_permits > waiting || self.max_concurrency == 0 {
+            return Duration::ZERO;
+        }
+        // Position of a new caller in the queue, including the caller itself.
+        let queue_position = waiting - available_permits + 1;
+        let rounds = queue_position.div_ceil(self.max_concurrency);
+        self.stats
+            .avg_hold_time()
+            .saturating_mul(u32::try_from(rounds).unwrap_or(u32::MAX))
+    }
 }
 
 async fn get_pending_state(
@@ -164,101 +644,353 @@ async fn get_pending_state(
     Ok((block_id, resolved_block_number))
 }
 
+/// Returns the expected timestamp of the pending L1 batch given the timestamp of the last sealed miniblock.
+/// The timestamp of the next L1 batch must be greater than the timestamp of the last miniblock.
+fn pending_l1_batch_timestamp(last_sealed_miniblock_timestamp: Option<u64>) -> u64 {
+    let now = seconds_since_epoch();
+    last_sealed_miniblock_timestamp.map_or(now, |timestamp| now.max(timestamp + 1))
+}
+
 /// Arguments for VM execution not specific to a particular transaction.
 #[derive(Debug, Clone)]
 pub(crate) struct TxSharedArgs {
     pub operator_account: AccountTreeId,
     pub fee_input: BatchFeeInput,
     pub base_system_contracts: MultiVMBaseSystemContracts,
+    /// Storage caches shared among executions. Executions on historical blocks may use the same caches
+    /// as ones on the latest block: cached storage values are timestamped with the miniblock they were loaded for,
+    /// and are only used for miniblocks for which they are known to be valid.
     pub caches: PostgresStorageCaches,
     pub validation_computational_gas_limit: u32,
     pub chain_id: L2ChainId,
-    pub whitelisted_tokens_for_aa: Vec<Address>,
+    pub aa_token_policy: AaTokenPolicy,
+}
+
+/// Errors returned by [`TxSharedArgsBuilder::build()`].
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub(crate) enum TxSharedArgsError {
+    #[error("fair L2 gas price in fee input is zero")]
+    ZeroL2GasPrice,
+    #[error(
+        "pubdata price derived from L1 gas price {l1_gas_price} in fee input overflows; \
+         the L1 gas price is likely misconfigured"
+    )]
+    PubdataPriceOverflow { l1_gas_price: u64 },
+    #[error("validation computational gas limit is zero")]
+    ZeroValidationGasLimit,
 }
 
 impl TxSharedArgs {
+    /// Creates a builder for shared args with the specified fee input. All other fields except for base system
+    /// contracts have default values; the latter are supplied in [`TxSharedArgsBuilder::build()`].
+    pub fn builder(fee_input: BatchFeeInput) -> TxSharedArgsBuilder {
+        TxSharedArgsBuilder::new(fee_input)
+    }
+
     #[cfg(test)]
     pub fn mock(base_system_contracts: MultiVMBaseSystemContracts) -> Self {
+        Self::builder(BatchFeeInput::l1_pegged(55, 555))
+            .with_validation_computational_gas_limit(u32::MAX)
+            .build(base_system_contracts)
+            .unwrap()
+    }
+}
+
+/// Builder for [`TxSharedArgs`].
+#[derive(Debug)]
+pub(crate) struct TxSharedArgsBuilder {
+    operator_account: AccountTreeId,
+    fee_input: BatchFeeInput,
+    caches: Option<PostgresStorageCaches>,
+    validation_computational_gas_limit: u32,
+    chain_id: L2ChainId,
+    aa_token_policy: AaTokenPolicy,
+}
+
+impl TxSharedArgsBuilder {
+    fn new(fee_input: BatchFeeInput) -> Self {
         Self {
             operator_account: AccountTreeId::default(),
-            fee_input: BatchFeeInput::l1_pegged(55, 555),
-            base_system_contracts,
-            caches: PostgresStorageCaches::new(1, 1),
-            validation_computational_gas_limit: u32::MAX,
+            fee_input,
+            caches: None,
+            validation_computational_gas_limit: BATCH_COMPUTATIONAL_GAS_LIMIT,
             chain_id: L2ChainId::default(),
-            whitelisted_tokens_for_aa: Vec::new(),
+            aa_token_policy: AaTokenPolicy::default(),
+        }
+    }
+
+    pub fn with_operator_account(mut self, operator_account: AccountTreeId) -> Self {
+        self.operator_account = operator_account;
+        self
+    }
+
+    /// Sets storage caches used during execution. If not set, minimal caches are created on [`Self::build()`].
+    pub fn with_caches(mut self, caches: PostgresStorageCaches) -> Self {
+        self.caches = Some(caches);
+        self
+    }
+
+    pub fn with_validation_computational_gas_limit(mut self, limit: u32) -> Self {
+        self.validation_computational_gas_limit = limit;
+        self
+    }
+
+    pub fn with_chain_id(mut self, chain_id: L2ChainId) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    pub fn with_aa_token_policy(mut self, policy: AaTokenPolicy) -> Self {
+        self.aa_token_policy = policy;
+        self
+    }
+
+    /// Builds shared args, checking that the fee input and gas limits are sane before they are passed to the VM.
+    /// Invalid values would otherwise lead to confusing VM halts (or panics).
+    ///
+    /// Executions in past blocks use the fee input of the executed block instead of the fee input supplied here.
+    /// Historical fee inputs are not checked; e.g., the genesis block has zero gas prices.
+    pub fn build(
+        self,
+        base_system_contracts: MultiVMBaseSystemContracts,
+    ) -> Result<TxSharedArgs, TxSharedArgsError> {
+        if self.fee_input.fair_l2_gas_price() == 0 {
+            return Err(TxSharedArgsError::ZeroL2GasPrice);
+        }
+        if let BatchFeeInput::L1Pegged(input) = &self.fee_input {
+            if input
+                .l1_gas_price
+                .checked_mul(L1_GAS_PER_PUBDATA_BYTE.into())
+                .is_none()
+            {
+                return Err(TxSharedArgsError::PubdataPriceOverflow {
+                    l1_gas_price: input.l1_gas_price,
+                });
+            }
+        }
+        if self.validation_computational_gas_limit == 0 {
+            return Err(TxSharedArgsError::ZeroValidationGasLimit);
         }
+
+        Ok(TxSharedArgs {
+            operator_account: self.operator_account,
+            fee_input: self.fee_input,
+            base_system_contracts,
+            caches: self
+                .caches
+                .unwrap_or_else(|| PostgresStorageCaches::new(1, 1)),
+            validation_computational_gas_limit: self.validation_computational_gas_limit,
+            chain_id: self.chain_id,
+            aa_token_policy: self.aa_token_policy,
+        })
+    }
+}
+
+/// Semantics of [`api::BlockNumber::Earliest`] on a node with pruned blocks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EarliestBlockMode {
+    /// `Earliest` always refers to the genesis miniblock, so it's considered pruned if the genesis miniblock is pruned.
+    #[default]
+    Genesis,
+    /// `Earliest` refers to the first retained miniblock when creating [`BlockArgs`]. Useful for tooling iterating
+    /// from the earliest block (e.g., block explorers).
+    FirstRetained,
+}
+
+/// Configuration of caching pruning info (i.e., the first retained L1 batch and miniblock) in the API server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockStartInfoConfig {
+    /// Age after which the cached pruning info is considered expired.
+    pub max_cache_age: Duration,
+    /// Upper bound of a random delay added to `max_cache_age`. We make max age a bit random so that
+    /// all threads don't start refreshing cache at the same time.
+    pub max_random_delay: Duration,
+    /// Latency threshold for individual DB queries issued by the sandbox (e.g., when loading pruning info
+    /// or resolving block arguments). Queries exceeding it are logged and reported in metrics. `None` disables
+    /// the reporting.
+    pub slow_query_threshold: Option<Duration>,
+    /// Semantics of the earliest block when creating [`BlockArgs`].
+    pub earliest_block_mode: EarliestBlockMode,
+}
+
+impl Default for BlockStartInfoConfig {
+    fn default() -> Self {
+        Self {
+            max_cache_age: Duration::from_secs(20),
+            max_random_delay: Duration::from_millis(100),
+            slow_query_threshold: Some(Duration::from_secs(1)),
+            earliest_block_mode: EarliestBlockMode::Genesis,
+        }
+    }
+}
+
+/// Awaits a DB query issued by the sandbox, reporting it if it takes longer than `threshold`.
+async fn observe_query<T>(
+    kind: SandboxQuery,
+    threshold: Option<Duration>,
+    query: impl Future<Output = T>,
+) -> T {
+    let started_at = Instant::now();
+    let output = query.await;
+    let elapsed = started_at.elapsed();
+    if threshold.map_or(false, |threshold| elapsed > threshold) {
+        tracing::warn!(
+            "Slow sandbox DB query {kind:?} took {elapsed:?} (threshold: {threshold:?})"
+        );
+        SANDBOX_METRICS.sandbox_slow_queries[&kind].inc();
     }
+    output
 }
 
 #[derive(Debug, Clone, Copy)]
 struct BlockStartInfoInner {
     info: PruningInfo,
-    cached_at: Instant,
+    /// `None` if the info isn't loaded from the storage yet (e.g., because the node isn't initialized).
+    /// Such info is always considered expired.
+    cached_at: Option<Instant>,
 }
 
 impl BlockStartInfoInner {
-    const MAX_CACHE_AGE: Duration = Duration::from_secs(20);
-    // We make max age a bit random so that all threads don't start refreshing cache at the same time
-    const MAX_RANDOM_DELAY: Duration = Duration::from_millis(100);
-
-    fn is_expired(&self, now: Instant) -> bool {
-        if let Some(expired_for) = (now - self.cached_at).checked_sub(Self::MAX_CACHE_AGE) {
-            if expired_for > Self::MAX_RANDOM_DELAY {
+    fn is_expired(&self, now: Instant, config: &BlockStartInfoConfig) -> bool {
+        let Some(cached_at) = self.cached_at else {
+            return true;
+        };
+        if let Some(expired_for) = (now - cached_at).checked_sub(config.max_cache_age) {
+            if expired_for > config.max_random_delay {
                 return true; // The cache is definitely expired, regardless of the randomness below
             }
             // Minimize access to RNG, which could be mildly costly
-            expired_for > thread_rng().gen_range(Duration::ZERO..=Self::MAX_RANDOM_DELAY)
+            expired_for > random_delay(config.max_random_delay)
         } else {
-            false // `now` is close to `self.cached_at`; the cache isn't expired
+            false // `now` is close to `cached_at`; the cache isn't expired
         }
     }
 }
 
+/// Checks whether `err` is caused by a missing DB table, e.g. if DB migrations weren't applied yet.
+fn is_undefined_table_error(err: &DalError) -> bool {
+    const UNDEFINED_TABLE_CODE: &str = "42P01";
+
+    err.inner()
+        .as_database_error()
+        .and_then(|err| err.code())
+        .map_or(false, |code| code == UNDEFINED_TABLE_CODE)
+}
+
+/// Returns a random duration in `0..=max`. Uses a thread-local xorshift PRNG seeded once per thread, which avoids
+/// the per-call overhead of [`thread_rng()`] (cloning the `Rc` handle and checking whether to reseed) on a hot path
+/// and is sufficient to decorrelate cache expiration among threads.
+fn random_delay(max: Duration) -> Duration {
+    thread_local! {
+        // The state must be non-zero; otherwise, xorshift only produces zeros.
+        static RNG_STATE: Cell<u64> = Cell::new(thread_rng().gen::<u64>() | 1);
+    }
+
+    let random = RNG_STATE.with(|state| {
+        // xorshift64* generator
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    });
+    let max_nanos = u64::try_from(max.as_nanos()).unwrap_or(u64::MAX);
+    // Modulo bias is negligible since `max_nanos` is much smaller than `u64::MAX` in practice.
+    Duration::from_nanos(random % max_nanos.saturating_add(1))
+}
+
 /// Information about first L1 batch / miniblock in the node storage.
 #[derive(Debug, Clone)]
 pub(crate) struct BlockStartInfo {
     cached_pruning_info: Arc<RwLock<BlockStartInfoInner>>,
+    config: BlockStartInfoConfig,
 }
 
 impl BlockStartInfo {
-    pub async fn new(storage: &mut Connection<'_, Core>) -> anyhow::Result<Self> {
-        let info = storage.pruning_dal().get_pruning_info().await?;
+    /// Loads the pruning info from the storage. If the node isn't initialized yet (i.e., the pruning log table
+    /// doesn't exist), assumes that no blocks are pruned rather than failing; such info is refreshed on the next access.
+    /// An empty pruning log is handled in the same way by the DAL.
+    pub async fn new(
+        storage: &mut Connection<'_, Core>,
+        config: BlockStartInfoConfig,
+    ) -> anyhow::Result<Self> {
+        let latency = SANDBOX_METRICS.sandbox[&SandboxStage::PruningInfoFetch].start();
+        let info = observe_query(
+            SandboxQuery::PruningInfo,
+            config.slow_query_threshold,
+            storage.pruning_dal().get_pruning_info(),
+        )
+        .await;
+        latency.observe();
+
+        let now = Instant::now();
+        let (info, cached_at) = match info {
+            Ok(info) => (info, Some(now)),
+            Err(err) if is_undefined_table_error(&err) => {
+                tracing::warn!(
+                    "Pruning info is unavailable, probably because the node isn't initialized yet ({err}); \
+                     assuming that no blocks are pruned"
+                );
+                // Leave the cached info uninitialized, so that it's reloaded once the node is initialized.
+                (PruningInfo::default(), None)
+            }
+            Err(err) => return Err(err.into()),
+        };
         Ok(Self {
-            cached_pruning_info: Arc::new(RwLock::new(BlockStartInfoInner {
-                info,
-                cached_at: Instant::now(),
-            })),
+            cached_pruning_info: Arc::new(RwLock::new(BlockStartInfoInner { info, cached_at })),
+            config,
+        })
+    }
+
+    // The cached info is plain data that is replaced atomically under the lock, so it cannot be left
+    // in an inconsistent state by a panicking thread. Thus, it's safe to recover from lock poisoning.
+    fn read_inner(&self) -> RwLockReadGuard<'_, BlockStartInfoInner> {
+        self.cached_pruning_info.read().unwrap_or_else(|err| {
+            tracing::warn!("BlockStartInfo lock is poisoned; recovering the cached pruning info");
+            PoisonError::into_inner(err)
+        })
+    }
+
+    fn write_inner(&self) -> RwLockWriteGuard<'_, BlockStartInfoInner> {
+        self.cached_pruning_info.write().unwrap_or_else(|err| {
+            tracing::warn!("BlockStartInfo lock is poisoned; recovering the cached pruning info");
+            PoisonError::into_inner(err)
         })
     }
 
     fn copy_inner(&self) -> BlockStartInfoInner {
-        *self
-            .cached_pruning_info
-            .read()
-            .expect("BlockStartInfo is poisoned")
+        *self.read_inner()
     }
 
     async fn update_cache(
         &self,
         storage: &mut Connection<'_, Core>,
         now: Instant,
-    ) -> anyhow::Result<PruningInfo> {
-        let info = storage.pruning_dal().get_pruning_info().await?;
+    ) -> anyhow::Result<(PruningInfo, CacheRefresh)> {
+        let latency = SANDBOX_METRICS.sandbox[&SandboxStage::PruningInfoFetch].start();
+        let info = observe_query(
+            SandboxQuery::PruningInfo,
+            self.config.slow_query_threshold,
+            storage.pruning_dal().get_pruning_info(),
+        )
+        .await?;
+        latency.observe();
 
-        let mut new_cached_pruning_info = self
-            .cached_pruning_info
-            .write()
-            .expect("BlockStartInfo is poisoned");
-        Ok(if new_cached_pruning_info.cached_at < now {
+        let mut new_cached_pruning_info = self.write_inner();
+        // `None` (i.e., uninitialized info) compares less than any `Some(_)`, so it's always replaced.
+        Ok(if new_cached_pruning_info.cached_at < Some(now) {
             *new_cached_pruning_info = BlockStartInfoInner {
                 info,
-                cached_at: now,
+                cached_at: Some(now),
             };
-            info
+            (info, CacheRefresh::Updated)
         } else {
             // Got a newer cache already; no need to update it again.
-            new_cached_pruning_info.info
+            (
+                new_cached_pruning_info.info,
+                CacheRefresh::NewerCachePresent,
+            )
         })
     }
 
@@ -268,24 +1000,39 @@ impl BlockStartInfo {
     ) -> anyhow::Result<PruningInfo> {
         let inner = self.copy_inner();
         let now = Instant::now();
-        if inner.is_expired(now) {
+        if inner.is_expired(now, &self.config) {
             // Multiple threads may execute this query if we're very unlucky
-            self.update_cache(storage, now).await
+            let (info, refresh) = self.update_cache(storage, now).await?;
+            SANDBOX_METRICS.block_start_info_cache_misses[&refresh].inc();
+            Ok(info)
         } else {
+            SANDBOX_METRICS.block_start_info_cache_hits.inc();
             Ok(inner.info)
         }
     }
 
+    fn first_retained_miniblock(pruning_info: &PruningInfo) -> MiniblockNumber {
+        let last_block = pruning_info.last_soft_pruned_miniblock;
+        if let Some(MiniblockNumber(last_block)) = last_block {
+            return MiniblockNumber(last_block + 1);
+        }
+        MiniblockNumber(0)
+    }
+
+    fn first_retained_l1_batch(pruning_info: &PruningInfo) -> L1BatchNumber {
+        let last_batch = pruning_info.last_soft_pruned_l1_batch;
+        if let Some(L1BatchNumber(last_block)) = last_batch {
+            return L1BatchNumber(last_block + 1);
+        }
+        L1BatchNumber(0)
+    }
+
     pub async fn first_miniblock(
         &self,
         storage: &mut Connection<'_, Core>,
     ) -> anyhow::Result<MiniblockNumber> {
         let cached_pruning_info = self.get_pruning_info(storage).await?;
-        let last_block = cached_pruning_info.last_soft_pruned_miniblock;
-        if let Some(MiniblockNumber(last_block)) = last_block {
-            return Ok(MiniblockNumber(last_block + 1));
-        }
-        Ok(MiniblockNumber(0))
+        Ok(Self::first_retained_miniblock(&cached_pruning_info))
     }
 
     pub async fn first_l1_batch(
@@ -293,44 +1040,84 @@ impl BlockStartInfo {
         storage: &mut Connection<'_, Core>,
     ) -> anyhow::Result<L1BatchNumber> {
         let cached_pruning_info = self.get_pruning_info(storage).await?;
-        let last_batch = cached_pruning_info.last_soft_pruned_l1_batch;
-        if let Some(L1BatchNumber(last_block)) = last_batch {
-            return Ok(L1BatchNumber(last_block + 1));
-        }
-        Ok(L1BatchNumber(0))
+        Ok(Self::first_retained_l1_batch(&cached_pruning_info))
     }
 
     /// Checks whether a block with the specified ID is pruned and returns an error if it is.
-    /// The `Err` variant wraps the first non-pruned miniblock.
+    /// The `Err` variant wraps the first non-pruned miniblock and L1 batch. Block hashes are resolved
+    /// to miniblock numbers first; hashes that don't correspond to a stored miniblock are not considered pruned.
     pub async fn ensure_not_pruned_block(
         &self,
         block: api::BlockId,
         storage: &mut Connection<'_, Core>,
     ) -> Result<(), BlockArgsError> {
-        let first_miniblock = self
-            .first_miniblock(storage)
+        // Use a single pruning info snapshot, so that the returned miniblock and L1 batch are consistent.
+        let pruning_info = self
+            .get_pruning_info(storage)
             .await
             .map_err(BlockArgsError::Database)?;
-        match block {
-            api::BlockId::Number(api::BlockNumber::Number(number))
-                if number < first_miniblock.0.into() =>
-            {
-                Err(BlockArgsError::Pruned(first_miniblock))
+        let first_miniblock = Self::first_retained_miniblock(&pruning_info);
+        let is_pruned = match block {
+            api::BlockId::Number(api::BlockNumber::Number(number)) => {
+                number < first_miniblock.0.into()
             }
-            api::BlockId::Number(api::BlockNumber::Earliest)
-                if first_miniblock > MiniblockNumber(0) =>
-            {
-                Err(BlockArgsError::Pruned(first_miniblock))
+            api::BlockId::Number(api::BlockNumber::Earliest) => {
+                first_miniblock > MiniblockNumber(0)
             }
-            _ => Ok(()),
+            api::BlockId::Hash(_) => {
+                // Soft-pruned miniblocks are still present in Postgres, so they can be resolved by hash.
+                let resolved_number = observe_query(
+                    SandboxQuery::ResolveBlockId,
+                    self.config.slow_query_threshold,
+                    storage.blocks_web3_dal().resolve_block_id(block),
+                )
+                .await
+                .map_err(DalError::generalize)?;
+                resolved_number.map_or(false, |number| number < first_miniblock)
+            }
+            _ => false,
+        };
+        if is_pruned {
+            return Err(BlockArgsError::Pruned {
+                first_miniblock,
+                first_l1_batch: Self::first_retained_l1_batch(&pruning_info),
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks whether any miniblock in the range `from..=to` is pruned and returns an error if it is.
+    /// Unlike calling [`Self::ensure_not_pruned_block()`] for each miniblock, pruning info is only read once.
+    pub async fn ensure_range_not_pruned(
+        &self,
+        from: MiniblockNumber,
+        to: MiniblockNumber,
+        storage: &mut Connection<'_, Core>,
+    ) -> Result<(), BlockArgsError> {
+        let pruning_info = self
+            .get_pruning_info(storage)
+            .await
+            .map_err(BlockArgsError::Database)?;
+        let first_miniblock = Self::first_retained_miniblock(&pruning_info);
+        if from.min(to) < first_miniblock {
+            return Err(BlockArgsError::Pruned {
+                first_miniblock,
+                first_l1_batch: Self::first_retained_l1_batch(&pruning_info),
+            });
         }
+        Ok(())
     }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub(crate) enum BlockArgsError {
-    #[error("Block is pruned; first retained block is {0}")]
-    Pruned(MiniblockNumber),
+    #[error(
+        "Block is pruned; first retained block is {first_miniblock} (first retained L1 batch is {first_l1_batch})"
+    )]
+    Pruned {
+        first_miniblock: MiniblockNumber,
+        first_l1_batch: L1BatchNumber,
+    },
     #[error("Block is missing, but can appear in the future")]
     Missing,
     #[error("Database error")]
@@ -343,6 +1130,10 @@ pub(crate) struct BlockArgs {
     block_id: api::BlockId,
     resolved_block_number: MiniblockNumber,
     l1_batch_timestamp_s: Option<u64>,
+    /// L1 batch that the resolved miniblock belongs to or will belong to. `None` for the pending block.
+    resolved_l1_batch_number: Option<L1BatchNumber>,
+    /// Marker set to `false` for args created with [`Self::new_without_timestamp()`], which cannot be used for VM execution.
+    vm_compatible: bool,
 }
 
 impl BlockArgs {
@@ -352,59 +1143,161 @@ impl BlockArgs {
             block_id,
             resolved_block_number,
             l1_batch_timestamp_s: None,
+            resolved_l1_batch_number: None,
+            vm_compatible: true,
         })
     }
 
-    /// Loads block information from DB.
+    /// Same as [`Self::pending()`], but also computes the expected timestamp of the pending L1 batch, so that
+    /// the VM environment is fully specified by the returned args.
+    pub(crate) async fn pending_with_timestamp(
+        connection: &mut Connection<'_, Core>,
+    ) -> anyhow::Result<Self> {
+        let mut this = Self::pending(connection).await?;
+        let last_sealed_miniblock_timestamp = connection
+            .blocks_dal()
+            .get_last_sealed_miniblock_header()
+            .await?
+            .map(|header| header.timestamp);
+        this.l1_batch_timestamp_s =
+            Some(pending_l1_batch_timestamp(last_sealed_miniblock_timestamp));
+        Ok(this)
+    }
+
+    /// Loads block information from DB. This is the only constructor for non-pending blocks that can be used
+    /// for VM execution.
     pub async fn new(
         connection: &mut Connection<'_, Core>,
         block_id: api::BlockId,
         start_info: &BlockStartInfo,
     ) -> Result<Self, BlockArgsError> {
-        // We need to check that `block_id` is present in Postgres or can be present in the future
-        // (i.e., it does not refer to a pruned block). If called for a pruned block, the returned value
-        // (specifically, `l1_batch_timestamp_s`) will be nonsensical.
-        start_info
-            .ensure_not_pruned_block(block_id, connection)
-            .await?;
-
-        if block_id == api::BlockId::Number(api::BlockNumber::Pending) {
-            return Ok(BlockArgs::pending(connection).await?);
+        let latency = SANDBOX_METRICS.sandbox[&SandboxStage::BlockArgsResolution].start();
+        // Checking that `block_id` is not pruned and resolving it is shared with the cheap constructor.
+        let block_args = Self::new_without_timestamp(connection, block_id, start_info).await?;
+        if block_args.vm_compatible {
+            // Pending block args are always fully loaded.
+            latency.observe();
+            return Ok(block_args);
         }
+        let resolved_block_number = block_args.resolved_block_number;
 
-        let resolved_block_number = connection
-            .blocks_web3_dal()
-            .resolve_block_id(block_id)
-            .await
-            .map_err(DalError::generalize)?;
-        let Some(resolved_block_number) = resolved_block_number else {
-            return Err(BlockArgsError::Missing);
-        };
+        let slow_query_threshold = start_info.config.slow_query_threshold;
+        let stage_latency = SANDBOX_METRICS.sandbox[&SandboxStage::L1BatchResolution].start();
+        let l1_batch = observe_query(
+            SandboxQuery::ResolveL1BatchNumber,
+            slow_query_threshold,
+            connection
+                .storage_web3_dal()
+                .resolve_l1_batch_number_of_miniblock(resolved_block_number),
+        )
+        .await
+        .with_context(|| {
+            format!("failed resolving L1 batch number of miniblock #{resolved_block_number}")
+        })?;
+        stage_latency.observe();
+
+        let stage_latency = SANDBOX_METRICS.sandbox[&SandboxStage::L1BatchTimestampFetch].start();
+        let l1_batch_timestamp = observe_query(
+            SandboxQuery::L1BatchTimestamp,
+            slow_query_threshold,
+            connection
+                .blocks_web3_dal()
+                .get_expected_l1_batch_timestamp(&l1_batch),
+        )
+        .await
+        .map_err(DalError::generalize)?
+        .context("missing timestamp for non-pending block")?;
+        stage_latency.observe();
+        latency.observe();
 
-        let l1_batch = connection
-            .storage_web3_dal()
-            .resolve_l1_batch_number_of_miniblock(resolved_block_number)
-            .await
-            .with_context(|| {
-                format!("failed resolving L1 batch number of miniblock #{resolved_block_number}")
-            })?;
-        let l1_batch_timestamp = connection
-            .blocks_web3_dal()
-            .get_expected_l1_batch_timestamp(&l1_batch)
-            .await
-            .map_err(DalError::generalize)?
-            .context("missing timestamp for non-pending block")?;
         Ok(Self {
             block_id,
             resolved_block_number,
             l1_batch_timestamp_s: Some(l1_batch_timestamp),
+            resolved_l1_batch_number: Some(l1_batch.expected_l1_batch()),
+            vm_compatible: true,
         })
     }
 
+    /// Loads block information from DB without resolving the L1 batch timestamp, which saves 2 DB queries
+    /// for non-pending blocks. The returned args are only suitable for callers that need
+    /// [`Self::resolved_block_number()`]; they **must not** be used for VM execution (use [`Self::new()`] instead).
+    ///
+    /// If [`EarliestBlockMode::FirstRetained`] is configured for `start_info`, the earliest block is replaced
+    /// with the first retained miniblock.
+    pub async fn new_without_timestamp(
+        connection: &mut Connection<'_, Core>,
+        block_id: api::BlockId,
+        start_info: &BlockStartInfo,
+    ) -> Result<Self, BlockArgsError> {
+        let block_id = match block_id {
+            api::BlockId::Number(api::BlockNumber::Earliest)
+                if start_info.config.earliest_block_mode == EarliestBlockMode::FirstRetained =>
+            {
+                let first_miniblock = start_info.first_miniblock(connection).await?;
+                api::BlockId::Number(api::BlockNumber::Number(first_miniblock.0.into()))
+            }
+            _ => block_id,
+        };
+
+        // We need to check that `block_id` is present in Postgres or can be present in the future
+        // (i.e., it does not refer to a pruned block).
+        let resolved_block_number = if let api::BlockId::Hash(_) = block_id {
+            // Resolve the hash only once and check the resolved number instead.
+            let number = Self::resolve_block_number(connection, block_id, start_info).await?;
+            let number_id = api::BlockId::Number(api::BlockNumber::Number(number.0.into()));
+            start_info
+                .ensure_not_pruned_block(number_id, connection)
+                .await?;
+            number
+        } else {
+            start_info
+                .ensure_not_pruned_block(block_id, connection)
+                .await?;
+            if block_id == api::BlockId::Number(api::BlockNumber::Pending) {
+                return Ok(BlockArgs::pending(connection).await?);
+            }
+            Self::resolve_block_number(connection, block_id, start_info).await?
+        };
+        Ok(Self {
+            block_id,
+            resolved_block_number,
+            l1_batch_timestamp_s: None,
+            resolved_l1_batch_number: None,
+            vm_compatible: false,
+        })
+    }
+
+    async fn resolve_block_number(
+        connection: &mut Connection<'_, Core>,
+        block_id: api::BlockId,
+        start_info: &BlockStartInfo,
+    ) -> Result<MiniblockNumber, BlockArgsError> {
+        let resolved_block_number = observe_query(
+            SandboxQuery::ResolveBlockId,
+            start_info.config.slow_query_threshold,
+            connection.blocks_web3_dal().resolve_block_id(block_id),
+        )
+        .await
+        .map_err(DalError::generalize)?;
+        resolved_block_number.ok_or(BlockArgsError::Missing)
+    }
+
     pub fn resolved_block_number(&self) -> MiniblockNumber {
         self.resolved_block_number
     }
 
+    /// Returns the L1 batch that the resolved miniblock belongs to (or will belong to, if the batch is not sealed yet).
+    /// Returns `None` for the pending block, and for args created with [`Self::new_without_timestamp()`].
+    pub fn l1_batch_number(&self) -> Option<L1BatchNumber> {
+        self.resolved_l1_batch_number
+    }
+
+    /// Checks whether these args always resolve to the latest sealed miniblock (or to the pending miniblock
+    /// on top of it), which makes them eligible for caching decisions based on the latest sealed state.
+    ///
+    /// `Finalized` is intentionally excluded: it resolves to the last miniblock of the last L1 batch executed on L1,
+    /// which generally lags behind the latest sealed miniblock.
     pub fn resolves_to_latest_sealed_miniblock(&self) -> bool {
         matches!(
             self.block_id,
@@ -1,7 +1,26 @@
 //! Tests for the VM execution sandbox.
 
+use std::collections::{HashMap, HashSet};
+
 use assert_matches::assert_matches;
+use multivm::{
+    interface::{ExecutionResult, VmInterface},
+    tracers::{validator::ValidationTracerParams, CancellationTracer},
+    MultiVMTracer,
+};
+use tokio_util::sync::CancellationToken;
+use zksync_contracts::read_bytecode;
 use zksync_dal::ConnectionPool;
+use zksync_types::{
+    block::MiniblockHeader,
+    ethabi::{self, ParamType, Token},
+    get_code_key, get_is_account_key,
+    l2::L2Tx,
+    transaction_request::PaymasterParams,
+    utils::storage_key_for_eth_balance,
+    Address, MiniblockNumber, StorageLog, Transaction, H256, TRUSTED_TOKEN_SLOTS, U256,
+};
+use zksync_utils::{bytecode::hash_bytecode, u256_to_h256};
 
 use super::*;
 use crate::{
@@ -31,8 +50,11 @@ async fn creating_block_args() {
     );
     assert_eq!(pending_block_args.resolved_block_number, MiniblockNumber(2));
     assert_eq!(pending_block_args.l1_batch_timestamp_s, None);
+    assert_eq!(pending_block_args.l1_batch_number(), None);
 
-    let start_info = BlockStartInfo::new(&mut storage).await.unwrap();
+    let start_info = BlockStartInfo::new(&mut storage, BlockStartInfoConfig::default())
+        .await
+        .unwrap();
     assert_eq!(
         start_info.first_miniblock(&mut storage).await.unwrap(),
         MiniblockNumber(0)
@@ -52,6 +74,8 @@ async fn creating_block_args() {
         latest_block_args.l1_batch_timestamp_s,
         Some(miniblock.timestamp)
     );
+    // The miniblock isn't attached to a sealed L1 batch, so it belongs to the pending batch.
+    assert_eq!(latest_block_args.l1_batch_number(), Some(L1BatchNumber(1)));
 
     let earliest_block = api::BlockId::Number(api::BlockNumber::Earliest);
     let earliest_block_args = BlockArgs::new(&mut storage, earliest_block, &start_info)
@@ -63,6 +87,10 @@ async fn creating_block_args() {
         MiniblockNumber(0)
     );
     assert_eq!(earliest_block_args.l1_batch_timestamp_s, Some(0));
+    assert_eq!(
+        earliest_block_args.l1_batch_number(),
+        Some(L1BatchNumber(0))
+    );
 
     let missing_block = api::BlockId::Number(100.into());
     let err = BlockArgs::new(&mut storage, missing_block, &start_info)
@@ -71,6 +99,126 @@ async fn creating_block_args() {
     assert_matches!(err, BlockArgsError::Missing);
 }
 
+#[tokio::test]
+async fn creating_block_args_without_timestamp() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    storage
+        .blocks_dal()
+        .insert_miniblock(&create_miniblock(1))
+        .await
+        .unwrap();
+    let start_info = BlockStartInfo::new(&mut storage, BlockStartInfoConfig::default())
+        .await
+        .unwrap();
+
+    let latest_block = api::BlockId::Number(api::BlockNumber::Latest);
+    let block_args = BlockArgs::new_without_timestamp(&mut storage, latest_block, &start_info)
+        .await
+        .unwrap();
+    assert_eq!(block_args.block_id, latest_block);
+    assert_eq!(block_args.resolved_block_number(), MiniblockNumber(1));
+    assert_eq!(block_args.l1_batch_timestamp_s, None);
+    assert_eq!(block_args.l1_batch_number(), None);
+    assert!(!block_args.vm_compatible);
+
+    let pending_block = api::BlockId::Number(api::BlockNumber::Pending);
+    let block_args = BlockArgs::new_without_timestamp(&mut storage, pending_block, &start_info)
+        .await
+        .unwrap();
+    assert_eq!(block_args.resolved_block_number(), MiniblockNumber(2));
+    assert!(block_args.vm_compatible);
+
+    let missing_block = api::BlockId::Number(100.into());
+    let err = BlockArgs::new_without_timestamp(&mut storage, missing_block, &start_info)
+        .await
+        .unwrap_err();
+    assert_matches!(err, BlockArgsError::Missing);
+}
+
+#[test]
+fn block_args_resolving_to_latest_sealed_miniblock() {
+    let block_numbers = [
+        (api::BlockNumber::Pending, true),
+        (api::BlockNumber::Latest, true),
+        (api::BlockNumber::Committed, true),
+        (api::BlockNumber::Finalized, false),
+        (api::BlockNumber::Earliest, false),
+        (api::BlockNumber::Number(1.into()), false),
+    ];
+    for (block_number, expected) in block_numbers {
+        let block_args = BlockArgs {
+            block_id: api::BlockId::Number(block_number),
+            resolved_block_number: MiniblockNumber(1),
+            l1_batch_timestamp_s: Some(1),
+            resolved_l1_batch_number: Some(L1BatchNumber(1)),
+            vm_compatible: true,
+        };
+        assert_eq!(
+            block_args.resolves_to_latest_sealed_miniblock(),
+            expected,
+            "{block_number:?}"
+        );
+    }
+
+    let block_args = BlockArgs {
+        block_id: api::BlockId::Hash(H256::repeat_byte(1)),
+        resolved_block_number: MiniblockNumber(1),
+        l1_batch_timestamp_s: Some(1),
+        resolved_l1_batch_number: Some(L1BatchNumber(1)),
+        vm_compatible: true,
+    };
+    assert!(!block_args.resolves_to_latest_sealed_miniblock());
+}
+
+#[tokio::test]
+async fn creating_pending_block_args_with_timestamp() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+
+    let now = seconds_since_epoch();
+    let pending_block_args = BlockArgs::pending_with_timestamp(&mut storage)
+        .await
+        .unwrap();
+    assert_eq!(pending_block_args.resolved_block_number, MiniblockNumber(1));
+    assert_eq!(pending_block_args.l1_batch_number(), None);
+    let timestamp = pending_block_args.l1_batch_timestamp_s.unwrap();
+    assert!(
+        (now..=seconds_since_epoch()).contains(&timestamp),
+        "now={now}, timestamp={timestamp}"
+    );
+
+    // The timestamp must exceed the timestamp of the last sealed miniblock, even if it's in the future.
+    let future_timestamp = seconds_since_epoch() + 1_000;
+    let miniblock = MiniblockHeader {
+        timestamp: future_timestamp,
+        ..create_miniblock(1)
+    };
+    storage
+        .blocks_dal()
+        .insert_miniblock(&miniblock)
+        .await
+        .unwrap();
+    let pending_block_args = BlockArgs::pending_with_timestamp(&mut storage)
+        .await
+        .unwrap();
+    assert_eq!(pending_block_args.resolved_block_number, MiniblockNumber(2));
+    assert_eq!(
+        pending_block_args.l1_batch_timestamp_s,
+        Some(future_timestamp + 1)
+    );
+
+    // The plain constructor leaves the timestamp unset.
+    let pending_block_args = BlockArgs::pending(&mut storage).await.unwrap();
+    assert_eq!(pending_block_args.l1_batch_timestamp_s, None);
+}
+
 #[tokio::test]
 async fn creating_block_args_after_snapshot_recovery() {
     let pool = ConnectionPool::<Core>::test_pool().await;
@@ -89,7 +237,9 @@ async fn creating_block_args_after_snapshot_recovery() {
     );
     assert_eq!(pending_block_args.l1_batch_timestamp_s, None);
 
-    let start_info = BlockStartInfo::new(&mut storage).await.unwrap();
+    let start_info = BlockStartInfo::new(&mut storage, BlockStartInfoConfig::default())
+        .await
+        .unwrap();
     assert_eq!(
         start_info.first_miniblock(&mut storage).await.unwrap(),
         snapshot_recovery.miniblock_number + 1
@@ -115,7 +265,12 @@ async fn creating_block_args_after_snapshot_recovery() {
         let err = BlockArgs::new(&mut storage, pruned_block, &start_info)
             .await
             .unwrap_err();
-        assert_matches!(err, BlockArgsError::Pruned(_));
+        assert_matches!(
+            err,
+            BlockArgsError::Pruned { first_miniblock, first_l1_batch }
+                if first_miniblock == snapshot_recovery.miniblock_number + 1
+                    && first_l1_batch == snapshot_recovery.l1_batch_number + 1
+        );
     }
 
     let missing_blocks = [
@@ -152,7 +307,12 @@ async fn creating_block_args_after_snapshot_recovery() {
         let err = BlockArgs::new(&mut storage, pruned_block, &start_info)
             .await
             .unwrap_err();
-        assert_matches!(err, BlockArgsError::Pruned(_));
+        assert_matches!(
+            err,
+            BlockArgsError::Pruned { first_miniblock, first_l1_batch }
+                if first_miniblock == snapshot_recovery.miniblock_number + 1
+                    && first_l1_batch == snapshot_recovery.l1_batch_number + 1
+        );
     }
     for missing_block in missing_blocks {
         let missing_block = api::BlockId::Number(missing_block);
@@ -163,6 +323,375 @@ async fn creating_block_args_after_snapshot_recovery() {
     }
 }
 
+#[tokio::test]
+async fn creating_block_args_for_pruned_block_hash() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    for number in 1..=3 {
+        storage
+            .blocks_dal()
+            .insert_miniblock(&create_miniblock(number))
+            .await
+            .unwrap();
+    }
+    storage
+        .pruning_dal()
+        .soft_prune_batches_range(L1BatchNumber(0), MiniblockNumber(1))
+        .await
+        .unwrap();
+
+    let start_info = BlockStartInfo::new(&mut storage, BlockStartInfoConfig::default())
+        .await
+        .unwrap();
+    for pruned_number in [0, 1] {
+        let pruned_block = api::BlockId::Hash(create_miniblock(pruned_number).hash);
+        let err = BlockArgs::new(&mut storage, pruned_block, &start_info)
+            .await
+            .unwrap_err();
+        assert_matches!(
+            err,
+            BlockArgsError::Pruned { first_miniblock, first_l1_batch }
+                if first_miniblock == MiniblockNumber(2) && first_l1_batch == L1BatchNumber(1)
+        );
+    }
+
+    let retained_block = api::BlockId::Hash(create_miniblock(2).hash);
+    start_info
+        .ensure_not_pruned_block(retained_block, &mut storage)
+        .await
+        .unwrap();
+    let block_args = BlockArgs::new_without_timestamp(&mut storage, retained_block, &start_info)
+        .await
+        .unwrap();
+    assert_eq!(block_args.resolved_block_number(), MiniblockNumber(2));
+    let unknown_block = api::BlockId::Hash(H256::repeat_byte(0xff));
+    start_info
+        .ensure_not_pruned_block(unknown_block, &mut storage)
+        .await
+        .unwrap();
+    let err = BlockArgs::new(&mut storage, unknown_block, &start_info)
+        .await
+        .unwrap_err();
+    assert_matches!(err, BlockArgsError::Missing);
+}
+
+#[tokio::test]
+async fn creating_block_args_for_earliest_block_on_pruned_node() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    for number in 1..=3 {
+        storage
+            .blocks_dal()
+            .insert_miniblock(&create_miniblock(number))
+            .await
+            .unwrap();
+    }
+    storage
+        .pruning_dal()
+        .soft_prune_batches_range(L1BatchNumber(0), MiniblockNumber(1))
+        .await
+        .unwrap();
+
+    let earliest_block = api::BlockId::Number(api::BlockNumber::Earliest);
+    let start_info = BlockStartInfo::new(&mut storage, BlockStartInfoConfig::default())
+        .await
+        .unwrap();
+    let err = BlockArgs::new(&mut storage, earliest_block, &start_info)
+        .await
+        .unwrap_err();
+    assert_matches!(
+        err,
+        BlockArgsError::Pruned { first_miniblock, .. } if first_miniblock == MiniblockNumber(2)
+    );
+
+    let config = BlockStartInfoConfig {
+        earliest_block_mode: EarliestBlockMode::FirstRetained,
+        ..BlockStartInfoConfig::default()
+    };
+    let start_info = BlockStartInfo::new(&mut storage, config).await.unwrap();
+    let block_args = BlockArgs::new(&mut storage, earliest_block, &start_info)
+        .await
+        .unwrap();
+    assert_eq!(block_args.resolved_block_number(), MiniblockNumber(2));
+    assert_eq!(
+        block_args.block_id,
+        api::BlockId::Number(api::BlockNumber::Number(2.into()))
+    );
+    let block_args = BlockArgs::new_without_timestamp(&mut storage, earliest_block, &start_info)
+        .await
+        .unwrap();
+    assert_eq!(block_args.resolved_block_number(), MiniblockNumber(2));
+}
+
+#[tokio::test]
+async fn ensuring_miniblock_range_is_not_pruned() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    for number in 1..=3 {
+        storage
+            .blocks_dal()
+            .insert_miniblock(&create_miniblock(number))
+            .await
+            .unwrap();
+    }
+    storage
+        .pruning_dal()
+        .soft_prune_batches_range(L1BatchNumber(0), MiniblockNumber(1))
+        .await
+        .unwrap();
+    let start_info = BlockStartInfo::new(&mut storage, BlockStartInfoConfig::default())
+        .await
+        .unwrap();
+
+    let pruned_ranges = [(0, 3), (1, 2), (0, 0), (1, 10)];
+    for (from, to) in pruned_ranges {
+        let err = start_info
+            .ensure_range_not_pruned(MiniblockNumber(from), MiniblockNumber(to), &mut storage)
+            .await
+            .unwrap_err();
+        assert_matches!(
+            err,
+            BlockArgsError::Pruned { first_miniblock, first_l1_batch }
+                if first_miniblock == MiniblockNumber(2) && first_l1_batch == L1BatchNumber(1)
+        );
+    }
+
+    // Ranges may extend past the last stored miniblock; only pruning is checked.
+    let retained_ranges = [(2, 3), (2, 2), (3, 10)];
+    for (from, to) in retained_ranges {
+        start_info
+            .ensure_range_not_pruned(MiniblockNumber(from), MiniblockNumber(to), &mut storage)
+            .await
+            .unwrap();
+    }
+}
+
+#[test]
+fn block_start_info_expiration_respects_config() {
+    let cached_at = Instant::now();
+    let inner = BlockStartInfoInner {
+        info: PruningInfo::default(),
+        cached_at: Some(cached_at),
+    };
+
+    let default_config = BlockStartInfoConfig::default();
+    assert!(!inner.is_expired(cached_at + Duration::from_secs(1), &default_config));
+    assert!(inner.is_expired(cached_at + Duration::from_secs(21), &default_config));
+
+    let config = BlockStartInfoConfig {
+        max_cache_age: Duration::from_millis(10),
+        max_random_delay: Duration::ZERO,
+        ..BlockStartInfoConfig::default()
+    };
+    assert!(!inner.is_expired(cached_at + Duration::from_millis(5), &config));
+    assert!(inner.is_expired(cached_at + Duration::from_millis(11), &config));
+
+    let uninitialized_inner = BlockStartInfoInner {
+        info: PruningInfo::default(),
+        cached_at: None,
+    };
+    assert!(uninitialized_inner.is_expired(cached_at, &default_config));
+    assert!(uninitialized_inner.is_expired(cached_at, &config));
+}
+
+#[tokio::test]
+async fn reporting_slow_sandbox_queries() {
+    let threshold = Some(Duration::from_millis(50));
+    let slow_queries = &SANDBOX_METRICS.sandbox_slow_queries[&SandboxQuery::L1BatchTimestamp];
+    let slow_query_count = slow_queries.get();
+
+    let output = observe_query(SandboxQuery::L1BatchTimestamp, threshold, async { 42 }).await;
+    assert_eq!(output, 42);
+    assert_eq!(slow_queries.get(), slow_query_count);
+
+    let slow_query = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        23
+    };
+    let output = observe_query(SandboxQuery::L1BatchTimestamp, threshold, slow_query).await;
+    assert_eq!(output, 23);
+    assert_eq!(slow_queries.get(), slow_query_count + 1);
+
+    // Reporting can be disabled.
+    let slow_query = tokio::time::sleep(Duration::from_millis(100));
+    observe_query(SandboxQuery::L1BatchTimestamp, None, slow_query).await;
+    assert_eq!(slow_queries.get(), slow_query_count + 1);
+}
+
+#[test]
+fn random_delays_for_cache_expiration() {
+    const MAX_DELAY: Duration = Duration::from_millis(100);
+    const SAMPLE_COUNT: u32 = 10_000;
+
+    assert_eq!(random_delay(Duration::ZERO), Duration::ZERO);
+
+    let delays: Vec<_> = (0..SAMPLE_COUNT).map(|_| random_delay(MAX_DELAY)).collect();
+    assert!(delays.iter().all(|&delay| delay <= MAX_DELAY));
+    // Delays should be spread roughly uniformly over the range, like with `thread_rng().gen_range()`.
+    let mean = delays.iter().sum::<Duration>() / SAMPLE_COUNT;
+    assert!(
+        mean > MAX_DELAY * 2 / 5 && mean < MAX_DELAY * 3 / 5,
+        "{mean:?}"
+    );
+    for quartile in 0..4 {
+        let (start, end) = (MAX_DELAY * quartile / 4, MAX_DELAY * (quartile + 1) / 4);
+        let count = delays
+            .iter()
+            .filter(|&&delay| delay >= start && delay < end)
+            .count();
+        assert!(count > 2_000 && count < 3_000, "{quartile}: {count}");
+    }
+
+    // Delays generated on different threads must be decorrelated.
+    let other_delays = std::thread::spawn(|| {
+        (0..SAMPLE_COUNT)
+            .map(|_| random_delay(MAX_DELAY))
+            .collect::<Vec<_>>()
+    })
+    .join()
+    .unwrap();
+    assert_ne!(delays, other_delays);
+}
+
+#[tokio::test]
+async fn block_start_info_on_uninitialized_node() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    let pruning_info = storage.pruning_dal().get_pruning_info().await.unwrap();
+    assert_eq!(pruning_info, PruningInfo::default());
+
+    let start_info = BlockStartInfo::new(&mut storage, BlockStartInfoConfig::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        start_info.first_miniblock(&mut storage).await.unwrap(),
+        MiniblockNumber(0)
+    );
+    assert_eq!(
+        start_info.first_l1_batch(&mut storage).await.unwrap(),
+        L1BatchNumber(0)
+    );
+}
+
+#[tokio::test]
+async fn block_start_info_without_pruning_log_table() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    let start_info = {
+        // Emulate a node on which DB migrations aren't applied yet. The table is restored once the transaction
+        // is rolled back (i.e., dropped).
+        let mut transaction = storage.start_transaction().await.unwrap();
+        sqlx::query("DROP TABLE pruning_log")
+            .execute(transaction.conn())
+            .await
+            .unwrap();
+        let err = transaction
+            .pruning_dal()
+            .get_pruning_info()
+            .await
+            .unwrap_err();
+        assert!(is_undefined_table_error(&err), "{err}");
+
+        let start_info = BlockStartInfo::new(&mut transaction, BlockStartInfoConfig::default())
+            .await
+            .unwrap();
+        let inner = start_info.copy_inner();
+        assert_eq!(inner.info, PruningInfo::default());
+        assert_eq!(inner.cached_at, None);
+        start_info
+    };
+
+    // The uninitialized info is reloaded on the next access.
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    storage
+        .pruning_dal()
+        .soft_prune_batches_range(L1BatchNumber(0), MiniblockNumber(0))
+        .await
+        .unwrap();
+    assert_eq!(
+        start_info.first_miniblock(&mut storage).await.unwrap(),
+        MiniblockNumber(1)
+    );
+    assert!(start_info.copy_inner().cached_at.is_some());
+}
+
+#[tokio::test]
+async fn block_start_info_cache_refresh_outcomes() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    let start_info = BlockStartInfo::new(&mut storage, BlockStartInfoConfig::default())
+        .await
+        .unwrap();
+
+    let stale_now = Instant::now();
+    let fresh_now = stale_now + Duration::from_secs(1);
+    let (_, refresh) = start_info
+        .update_cache(&mut storage, fresh_now)
+        .await
+        .unwrap();
+    assert_eq!(refresh, CacheRefresh::Updated);
+    // Emulate a concurrent refresh that has started before the previous one.
+    let (_, refresh) = start_info
+        .update_cache(&mut storage, stale_now)
+        .await
+        .unwrap();
+    assert_eq!(refresh, CacheRefresh::NewerCachePresent);
+    assert_eq!(start_info.copy_inner().cached_at, Some(fresh_now));
+}
+
+#[tokio::test]
+async fn block_start_info_recovers_from_poisoned_lock() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+
+    let start_info = BlockStartInfo::new(&mut storage, BlockStartInfoConfig::default())
+        .await
+        .unwrap();
+    let poisoning_start_info = start_info.clone();
+    std::thread::spawn(move || {
+        let _guard = poisoning_start_info.cached_pruning_info.write().unwrap();
+        panic!("poisoning the lock");
+    })
+    .join()
+    .unwrap_err();
+    assert!(start_info.cached_pruning_info.is_poisoned());
+
+    assert_eq!(
+        start_info.first_miniblock(&mut storage).await.unwrap(),
+        MiniblockNumber(0)
+    );
+    assert_eq!(
+        start_info.first_l1_batch(&mut storage).await.unwrap(),
+        L1BatchNumber(0)
+    );
+    // Cache updates should work as well.
+    start_info
+        .update_cache(&mut storage, Instant::now())
+        .await
+        .unwrap();
+    assert_eq!(
+        start_info.first_miniblock(&mut storage).await.unwrap(),
+        MiniblockNumber(0)
+    );
+}
+
 #[tokio::test]
 async fn instantiating_vm() {
     let pool = ConnectionPool::<Core>::test_pool().await;
@@ -173,7 +702,9 @@ async fn instantiating_vm() {
 
     let block_args = BlockArgs::pending(&mut storage).await.unwrap();
     test_instantiating_vm(pool.clone(), block_args).await;
-    let start_info = BlockStartInfo::new(&mut storage).await.unwrap();
+    let start_info = BlockStartInfo::new(&mut storage, BlockStartInfoConfig::default())
+        .await
+        .unwrap();
     let block_args = BlockArgs::new(&mut storage, api::BlockId::Number(0.into()), &start_info)
         .await
         .unwrap();
@@ -181,8 +712,11 @@ async fn instantiating_vm() {
 }
 
 async fn test_instantiating_vm(pool: ConnectionPool<Core>, block_args: BlockArgs) {
-    let (vm_concurrency_limiter, _) = VmConcurrencyLimiter::new(1);
-    let vm_permit = vm_concurrency_limiter.acquire().await.unwrap();
+    let (vm_concurrency_limiter, _) = VmConcurrencyLimiter::new(1, None);
+    let vm_permit = vm_concurrency_limiter
+        .acquire(VmPriority::Background)
+        .await
+        .unwrap();
     let transaction = create_l2_transaction(10, 100).into();
 
     tokio::task::spawn_blocking(move || {
@@ -203,3 +737,927 @@ async fn test_instantiating_vm(pool: ConnectionPool<Core>, block_args: BlockArgs
     .expect("VM instantiation panicked")
     .expect("VM instantiation errored");
 }
+
+#[tokio::test]
+async fn estimating_vm_permit_wait_time() {
+    const HOLD_TIME: Duration = Duration::from_millis(100);
+
+    let (limiter, _barrier) = VmConcurrencyLimiter::new(2, None);
+    let limiter = Arc::new(limiter);
+    assert_eq!(limiter.estimated_wait(), Duration::ZERO);
+
+    for _ in 0..3 {
+        limiter.stats.record_hold_time(HOLD_TIME);
+    }
+    assert_eq!(limiter.stats.avg_hold_time(), HOLD_TIME);
+    // All permits are available, so there should be no wait.
+    assert_eq!(limiter.estimated_wait(), Duration::ZERO);
+
+    let _permits = [
+        limiter.acquire(VmPriority::Background).await.unwrap(),
+        limiter.acquire(VmPriority::Background).await.unwrap(),
+    ];
+    // Only the caller itself is queued.
+    assert_eq!(limiter.estimated_wait(), HOLD_TIME);
+
+    let waiters: Vec<_> = (0..5)
+        .map(|_| {
+            let limiter = Arc::clone(&limiter);
+            tokio::spawn(async move { limiter.acquire(VmPriority::Background).await.map(drop) })
+        })
+        .collect();
+    while limiter.stats.waiting.load(Ordering::Relaxed) < waiters.len() {
+        tokio::task::yield_now().await;
+    }
+
+    // A new caller is 6th in the queue, with 2 permits released per average hold time.
+    assert_eq!(limiter.estimated_wait(), HOLD_TIME * 3);
+
+    for waiter in waiters {
+        waiter.abort();
+    }
+}
+
+#[tokio::test]
+async fn reporting_slow_vm_permits() {
+    const THRESHOLD: Duration = Duration::from_millis(50);
+
+    let (limiter, _barrier) = VmConcurrencyLimiter::new(2, None);
+    let limiter = limiter.with_slow_permit_threshold(Some(THRESHOLD));
+    let fast_permit = limiter.acquire(VmPriority::Interactive).await.unwrap();
+    drop(fast_permit);
+    assert_eq!(limiter.stats.slow_permits.load(Ordering::Relaxed), 0);
+
+    let slow_permit = limiter.acquire(VmPriority::Background).await.unwrap();
+    // The permit is only reported once all its clones are dropped.
+    let slow_permit_clone = slow_permit.clone();
+    tokio::time::sleep(THRESHOLD * 2).await;
+    drop(slow_permit);
+    assert_eq!(limiter.stats.slow_permits.load(Ordering::Relaxed), 0);
+    drop(slow_permit_clone);
+    assert_eq!(limiter.stats.slow_permits.load(Ordering::Relaxed), 1);
+
+    let (limiter, _barrier) = VmConcurrencyLimiter::new(1, None);
+    let limiter = limiter.with_slow_permit_threshold(None);
+    let permit = limiter.acquire(VmPriority::Background).await.unwrap();
+    tokio::time::sleep(THRESHOLD * 2).await;
+    drop(permit);
+    assert_eq!(limiter.stats.slow_permits.load(Ordering::Relaxed), 0);
+}
+
+#[tokio::test]
+async fn reporting_starved_vm_permit_acquisitions() {
+    const THRESHOLD: Duration = Duration::from_millis(50);
+
+    let (limiter, _barrier) = VmConcurrencyLimiter::new(1, None);
+    let limiter = Arc::new(limiter.with_starvation_threshold(Some(THRESHOLD)));
+    let permit = limiter.acquire(VmPriority::Background).await.unwrap();
+    drop(permit);
+    assert_eq!(
+        limiter.stats.starved_acquisitions.load(Ordering::Relaxed),
+        0
+    );
+
+    let permit = limiter.acquire(VmPriority::Background).await.unwrap();
+    let waiter = tokio::spawn({
+        let limiter = Arc::clone(&limiter);
+        async move { limiter.acquire(VmPriority::Interactive).await.map(drop) }
+    });
+    tokio::time::sleep(THRESHOLD * 2).await;
+    drop(permit);
+    waiter.await.unwrap().unwrap();
+    assert_eq!(
+        limiter.stats.starved_acquisitions.load(Ordering::Relaxed),
+        1
+    );
+
+    let (limiter, _barrier) = VmConcurrencyLimiter::new(1, None);
+    let limiter = Arc::new(limiter.with_starvation_threshold(None));
+    let permit = limiter.acquire(VmPriority::Background).await.unwrap();
+    let waiter = tokio::spawn({
+        let limiter = Arc::clone(&limiter);
+        async move { limiter.acquire(VmPriority::Background).await.map(drop) }
+    });
+    tokio::time::sleep(THRESHOLD * 2).await;
+    drop(permit);
+    waiter.await.unwrap().unwrap();
+    assert_eq!(
+        limiter.stats.starved_acquisitions.load(Ordering::Relaxed),
+        0
+    );
+}
+
+#[tokio::test]
+async fn reporting_slow_vm_permit_acquisitions() {
+    const THRESHOLD: Duration = Duration::from_millis(50);
+
+    let (limiter, _barrier) = VmConcurrencyLimiter::new(1, Some(THRESHOLD));
+    let limiter = Arc::new(limiter);
+    let permit = limiter.acquire(VmPriority::Background).await.unwrap();
+    drop(permit);
+    assert_eq!(limiter.stats.slow_acquisitions.load(Ordering::Relaxed), 0);
+
+    let permit = limiter.acquire(VmPriority::Background).await.unwrap();
+    let waiter = tokio::spawn({
+        let limiter = Arc::clone(&limiter);
+        async move { limiter.acquire(VmPriority::Background).await.map(drop) }
+    });
+    tokio::time::sleep(THRESHOLD * 2).await;
+    drop(permit);
+    waiter.await.unwrap().unwrap();
+    assert_eq!(limiter.stats.slow_acquisitions.load(Ordering::Relaxed), 1);
+
+    let (limiter, _barrier) = VmConcurrencyLimiter::new(1, None);
+    let limiter = Arc::new(limiter);
+    let permit = limiter.acquire(VmPriority::Background).await.unwrap();
+    let waiter = tokio::spawn({
+        let limiter = Arc::clone(&limiter);
+        async move { limiter.acquire(VmPriority::Background).await.map(drop) }
+    });
+    tokio::time::sleep(THRESHOLD * 2).await;
+    drop(permit);
+    waiter.await.unwrap().unwrap();
+    assert_eq!(limiter.stats.slow_acquisitions.load(Ordering::Relaxed), 0);
+}
+
+#[tokio::test]
+async fn interactive_vm_permits_are_not_blocked_by_background_ones() {
+    let (limiter, barrier) = VmConcurrencyLimiter::with_reserved_permits(2, 1, None);
+    let limiter = Arc::new(limiter);
+
+    let background_permit = limiter.acquire(VmPriority::Background).await.unwrap();
+    assert_eq!(background_permit.priority(), VmPriority::Background);
+    // Shared permits are exhausted, so background callers are queued.
+    let background_waiters: Vec<_> = (0..3)
+        .map(|_| {
+            let limiter = Arc::clone(&limiter);
+            tokio::spawn(async move { limiter.acquire(VmPriority::Background).await.map(drop) })
+        })
+        .collect();
+    while limiter.stats.waiting.load(Ordering::Relaxed) < background_waiters.len() {
+        tokio::task::yield_now().await;
+    }
+
+    let interactive_permit = tokio::time::timeout(
+        Duration::from_secs(5),
+        limiter.acquire(VmPriority::Interactive),
+    )
+    .await
+    .expect("interactive caller is blocked by background ones")
+    .unwrap();
+    assert_eq!(interactive_permit.priority(), VmPriority::Interactive);
+    assert_eq!(limiter.available_permits(), 0);
+
+    for waiter in background_waiters {
+        waiter.abort();
+    }
+    barrier.close();
+    assert_matches!(
+        limiter.acquire(VmPriority::Interactive).await,
+        Err(AcquireError::ShuttingDown)
+    );
+    drop((background_permit, interactive_permit));
+    tokio::time::timeout(Duration::from_secs(5), barrier.wait_until_stopped())
+        .await
+        .expect("permits from both tiers should be returned");
+}
+
+#[tokio::test]
+async fn waiting_until_vm_permits_are_dropped_with_deadline() {
+    let (limiter, barrier) = VmConcurrencyLimiter::new(3, None);
+    let permit = limiter.acquire(VmPriority::Background).await.unwrap();
+    let stuck_permit = limiter.acquire(VmPriority::Interactive).await.unwrap();
+    barrier.close();
+
+    drop(permit);
+    // Emulate a stuck VM execution that never releases its permit.
+    std::mem::forget(stuck_permit);
+    let remaining_permits = barrier
+        .wait_until_stopped_with_deadline(Duration::from_millis(200))
+        .await
+        .unwrap_err();
+    assert_eq!(remaining_permits, 1);
+}
+
+#[tokio::test]
+async fn waiting_until_vm_permits_are_dropped_before_deadline() {
+    let (limiter, barrier) = VmConcurrencyLimiter::new(1, None);
+    let permit = limiter.acquire(VmPriority::Background).await.unwrap();
+    barrier.close();
+
+    let drop_task = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(permit);
+    });
+    barrier
+        .wait_until_stopped_with_deadline(Duration::from_secs(5))
+        .await
+        .unwrap();
+    drop_task.await.unwrap();
+}
+
+#[tokio::test]
+async fn closing_vm_barrier_while_acquiring_permit() {
+    let (limiter, barrier) = VmConcurrencyLimiter::with_reserved_permits(2, 1, None);
+    let limiter = Arc::new(limiter);
+    let background_permit = limiter.acquire(VmPriority::Background).await.unwrap();
+    let interactive_permit = limiter.acquire(VmPriority::Interactive).await.unwrap();
+
+    let waiters: Vec<_> = [VmPriority::Background, VmPriority::Interactive]
+        .into_iter()
+        .map(|priority| {
+            let limiter = Arc::clone(&limiter);
+            tokio::spawn(async move { limiter.acquire(priority).await.map(drop) })
+        })
+        .collect();
+    while limiter.stats.waiting.load(Ordering::Relaxed) < waiters.len() {
+        tokio::task::yield_now().await;
+    }
+
+    barrier.close();
+    for waiter in waiters {
+        let result = tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .expect("waiter is not woken up after closing the barrier")
+            .unwrap();
+        assert_matches!(result, Err(AcquireError::ShuttingDown));
+    }
+    assert_matches!(
+        limiter
+            .acquire_timeout(VmPriority::Background, Duration::from_secs(5))
+            .await,
+        Err(AcquireError::ShuttingDown)
+    );
+
+    drop((background_permit, interactive_permit));
+    tokio::time::timeout(Duration::from_secs(5), barrier.wait_until_stopped())
+        .await
+        .expect("permits should be returned");
+}
+
+#[tokio::test]
+async fn acquiring_weighted_vm_permits() {
+    let (limiter, barrier) = VmConcurrencyLimiter::new(3, None);
+    let permit = limiter
+        .acquire_weighted(VmPriority::Background, 2)
+        .await
+        .unwrap();
+    let cloned_permit = permit.clone();
+    let other_permit = limiter.acquire(VmPriority::Background).await.unwrap();
+    assert_matches!(
+        limiter
+            .acquire_timeout(VmPriority::Background, Duration::from_millis(50))
+            .await,
+        Err(AcquireError::Timeout)
+    );
+    barrier.close();
+
+    drop(other_permit);
+    drop(permit);
+    let remaining_permits = barrier
+        .clone()
+        .wait_until_stopped_with_deadline(Duration::from_millis(50))
+        .await
+        .unwrap_err();
+    assert_eq!(remaining_permits, 2);
+
+    drop(cloned_permit);
+    barrier
+        .wait_until_stopped_with_deadline(Duration::from_secs(5))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn weighted_vm_permit_is_clamped_to_limiter_capacity() {
+    let (limiter, _barrier) = VmConcurrencyLimiter::with_reserved_permits(3, 1, None);
+    let permit = limiter
+        .acquire_weighted(VmPriority::Background, 100)
+        .await
+        .unwrap();
+    assert_eq!(limiter.available_permits(), 1);
+    drop(permit);
+    assert_eq!(limiter.available_permits(), 3);
+}
+
+#[tokio::test]
+async fn weighted_interactive_vm_permit_does_not_block_background_ones() {
+    // Reserved permits outnumber shared ones, so the interactive weight below only fits reserved permits.
+    let (limiter, _barrier) = VmConcurrencyLimiter::with_reserved_permits(4, 3, None);
+    let limiter = Arc::new(limiter);
+    let interactive_permit = limiter.acquire(VmPriority::Interactive).await.unwrap();
+    assert_eq!(limiter.reserved_limiter.available_permits(), 2);
+
+    let weighted_waiter = tokio::spawn({
+        let limiter = Arc::clone(&limiter);
+        async move { limiter.acquire_weighted(VmPriority::Interactive, 3).await }
+    });
+    while limiter.stats.waiting.load(Ordering::Relaxed) == 0 {
+        tokio::task::yield_now().await;
+    }
+
+    // The weighted caller must not be queued on (and hold) the single shared permit.
+    let background_permit = limiter
+        .acquire_timeout(VmPriority::Background, Duration::from_secs(5))
+        .await
+        .expect("background caller is blocked by a weighted interactive one");
+    assert_eq!(background_permit.priority(), VmPriority::Background);
+
+    drop(interactive_permit);
+    let weighted_permit = tokio::time::timeout(Duration::from_secs(5), weighted_waiter)
+        .await
+        .expect("weighted interactive caller was not served")
+        .unwrap()
+        .unwrap();
+    assert_eq!(weighted_permit.priority(), VmPriority::Interactive);
+    assert_eq!(limiter.available_permits(), 0);
+    drop((background_permit, weighted_permit));
+    assert_eq!(limiter.available_permits(), 4);
+}
+
+#[tokio::test]
+async fn shedding_vm_permit_acquisitions_on_overload() {
+    let (limiter, _barrier) = VmConcurrencyLimiter::new(1, None);
+    let limiter = Arc::new(limiter.with_max_queue_depth(Some(1)));
+    let permit = limiter.acquire(VmPriority::Background).await.unwrap();
+
+    let waiter = tokio::spawn({
+        let limiter = Arc::clone(&limiter);
+        async move { limiter.acquire(VmPriority::Interactive).await.map(drop) }
+    });
+    while limiter.stats.waiting.load(Ordering::Relaxed) == 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // The queue is full, so excess acquisitions are rejected immediately rather than enqueued.
+    for priority in [VmPriority::Interactive, VmPriority::Background] {
+        let result = tokio::time::timeout(Duration::from_secs(1), limiter.acquire(priority))
+            .await
+            .expect("acquisition was not rejected immediately");
+        assert_matches!(result, Err(AcquireError::Overloaded));
+    }
+    assert_eq!(limiter.stats.waiting.load(Ordering::Relaxed), 1);
+
+    drop(permit);
+    waiter.await.unwrap().unwrap();
+    // Once the queue is drained, permits can be acquired again.
+    limiter.acquire(VmPriority::Background).await.unwrap();
+}
+
+#[tokio::test]
+async fn shedding_background_vm_permit_acquisitions_with_reserved_permits_available() {
+    let (limiter, _barrier) = VmConcurrencyLimiter::with_reserved_permits(2, 1, None);
+    let limiter = Arc::new(limiter.with_max_queue_depth(Some(1)));
+    let permit = limiter.acquire(VmPriority::Background).await.unwrap();
+
+    let waiter = tokio::spawn({
+        let limiter = Arc::clone(&limiter);
+        async move { limiter.acquire(VmPriority::Background).await.map(drop) }
+    });
+    while limiter.stats.waiting.load(Ordering::Relaxed) == 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // The reserved permit is available, but background callers cannot use it, so they are rejected.
+    let result = tokio::time::timeout(
+        Duration::from_secs(1),
+        limiter.acquire(VmPriority::Background),
+    )
+    .await
+    .expect("acquisition was not rejected immediately");
+    assert_matches!(result, Err(AcquireError::Overloaded));
+    // Interactive callers can use the reserved permit, so they are not rejected.
+    let interactive_permit = tokio::time::timeout(
+        Duration::from_secs(1),
+        limiter.acquire(VmPriority::Interactive),
+    )
+    .await
+    .expect("interactive acquisition was not served immediately")
+    .unwrap();
+    assert_eq!(limiter.stats.waiting.load(Ordering::Relaxed), 1);
+
+    drop(interactive_permit);
+    drop(permit);
+    waiter.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn acquiring_vm_permit_with_timeout() {
+    let (limiter, _barrier) = VmConcurrencyLimiter::new(1, None);
+    let permit = limiter
+        .acquire_timeout(VmPriority::Background, Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    let started_at = Instant::now();
+    let timed_out_permit = limiter
+        .acquire_timeout(VmPriority::Background, Duration::from_millis(50))
+        .await;
+    assert_matches!(timed_out_permit, Err(AcquireError::Timeout));
+    assert!(started_at.elapsed() >= Duration::from_millis(50));
+    assert_eq!(limiter.stats.waiting.load(Ordering::Relaxed), 0);
+
+    drop(permit);
+    limiter
+        .acquire_timeout(VmPriority::Background, Duration::from_secs(5))
+        .await
+        .unwrap();
+}
+
+fn approval_based_paymaster_input(token: Address) -> Vec<u8> {
+    let param_types = [ParamType::Address, ParamType::Uint(256), ParamType::Bytes];
+    let selector = ethabi::short_signature("approvalBased", &param_types);
+    let params = ethabi::encode(&[
+        Token::Address(token),
+        Token::Uint(U256::zero()),
+        Token::Bytes(vec![]),
+    ]);
+    selector.into_iter().chain(params).collect()
+}
+
+#[tokio::test]
+async fn validation_params_with_aa_token_policy() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+
+    let mut tx = create_l2_transaction(10, 100);
+    let listed_token = Address::repeat_byte(1);
+    let non_listed_token = Address::repeat_byte(2);
+    let is_trusted = |params: &ValidationTracerParams, token: Address| {
+        TRUSTED_TOKEN_SLOTS
+            .iter()
+            .all(|&slot| params.trusted_slots.contains(&(token, slot)))
+    };
+
+    let policy = AaTokenPolicy::whitelist(vec![listed_token]);
+    let params = validate::get_validation_params(&mut storage, &tx, u32::MAX, &policy)
+        .await
+        .unwrap();
+    assert!(is_trusted(&params, listed_token));
+    assert!(!is_trusted(&params, non_listed_token));
+
+    let params =
+        validate::get_validation_params(&mut storage, &tx, u32::MAX, &AaTokenPolicy::AllowAll)
+            .await
+            .unwrap();
+    assert!(!is_trusted(&params, listed_token));
+    assert!(!is_trusted(&params, non_listed_token));
+
+    // Only the token used by the approval-based paymaster should be trusted.
+    tx.common_data.paymaster_params = PaymasterParams {
+        paymaster: Address::repeat_byte(3),
+        paymaster_input: approval_based_paymaster_input(non_listed_token),
+    };
+    let params =
+        validate::get_validation_params(&mut storage, &tx, u32::MAX, &AaTokenPolicy::AllowAll)
+            .await
+            .unwrap();
+    assert!(!is_trusted(&params, listed_token));
+    assert!(is_trusted(&params, non_listed_token));
+    // The paymaster token shouldn't be trusted with the whitelist policy.
+    let params = validate::get_validation_params(&mut storage, &tx, u32::MAX, &policy)
+        .await
+        .unwrap();
+    assert!(!is_trusted(&params, non_listed_token));
+}
+
+/// Deploys `SlotReadingAccount`s (which can also serve as paymasters) and `StorageReader`s (emulating tokens)
+/// at the specified addresses in miniblock #1.
+async fn deploy_slot_reading_contracts(
+    storage: &mut Connection<'_, Core>,
+    accounts: &[Address],
+    readers: &[Address],
+) {
+    const CONTRACTS_PATH: &str =
+        "etc/contracts-test-data/artifacts-zk/contracts/custom-account/slot-reading-account.sol";
+
+    let account_bytecode = read_bytecode(format!("{CONTRACTS_PATH}/SlotReadingAccount.json"));
+    let account_hash = hash_bytecode(&account_bytecode);
+    let reader_bytecode = read_bytecode(format!("{CONTRACTS_PATH}/StorageReader.json"));
+    let reader_hash = hash_bytecode(&reader_bytecode);
+
+    storage
+        .blocks_dal()
+        .insert_miniblock(&create_miniblock(1))
+        .await
+        .unwrap();
+    let factory_deps = HashMap::from([
+        (account_hash, account_bytecode),
+        (reader_hash, reader_bytecode),
+    ]);
+    storage
+        .factory_deps_dal()
+        .insert_factory_deps(MiniblockNumber(1), &factory_deps)
+        .await
+        .unwrap();
+
+    let account_logs = accounts.iter().flat_map(|account| {
+        [
+            StorageLog::new_write_log(get_code_key(account), account_hash),
+            StorageLog::new_write_log(get_is_account_key(account), u256_to_h256(U256::one())),
+            StorageLog::new_write_log(
+                storage_key_for_eth_balance(account),
+                u256_to_h256(U256::one() << 64),
+            ),
+        ]
+    });
+    let reader_logs = readers
+        .iter()
+        .map(|reader| StorageLog::new_write_log(get_code_key(reader), reader_hash));
+    let logs = account_logs.chain(reader_logs).collect();
+    storage
+        .storage_logs_dal()
+        .insert_storage_logs(MiniblockNumber(1), &[(H256::zero(), logs)])
+        .await
+        .unwrap();
+}
+
+/// Creates a transaction from a `SlotReadingAccount` reading the specified slot of a `StorageReader`
+/// during validation.
+fn create_slot_reading_tx(account: Address, reader: Address, slot: U256) -> L2Tx {
+    let mut tx = create_l2_transaction(250_000_000, 800);
+    tx.common_data.initiator_address = account;
+    tx.common_data.fee.gas_limit = 10_000_000.into();
+    tx.execute.calldata = ethabi::encode(&[
+        Token::Address(reader),
+        Token::FixedBytes(u256_to_h256(slot).0.to_vec()),
+    ]);
+    tx
+}
+
+async fn validate_tx(
+    pool: &ConnectionPool<Core>,
+    tx: L2Tx,
+    aa_token_policy: AaTokenPolicy,
+) -> Result<(), ValidationError> {
+    let (vm_concurrency_limiter, _) = VmConcurrencyLimiter::new(1, None);
+    let vm_permit = vm_concurrency_limiter
+        .acquire(VmPriority::Background)
+        .await
+        .unwrap();
+    let mut shared_args = TxSharedArgs::mock(ApiContracts::load_from_disk().eth_call);
+    shared_args.aa_token_policy = aa_token_policy;
+    let block_args = BlockArgs::pending(&mut pool.connection().await.unwrap())
+        .await
+        .unwrap();
+
+    TransactionExecutor::Real
+        .validate_tx_in_sandbox(
+            pool.clone(),
+            vm_permit,
+            tx,
+            shared_args,
+            block_args,
+            u32::MAX,
+        )
+        .await
+}
+
+#[tokio::test]
+async fn validating_token_slot_reads_with_aa_token_policy() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    let account = Address::repeat_byte(0x10);
+    let paymaster = Address::repeat_byte(0x11);
+    let token = Address::repeat_byte(0x20);
+    let other_token = Address::repeat_byte(0x21);
+    deploy_slot_reading_contracts(&mut storage, &[account, paymaster], &[token, other_token]).await;
+    drop(storage);
+
+    let slot = TRUSTED_TOKEN_SLOTS[0];
+    let tx = create_slot_reading_tx(account, token, slot);
+    validate_tx(&pool, tx.clone(), AaTokenPolicy::whitelist(vec![token]))
+        .await
+        .unwrap();
+    let err = validate_tx(&pool, tx.clone(), AaTokenPolicy::whitelist(vec![]))
+        .await
+        .unwrap_err();
+    assert_matches!(
+        err,
+        ValidationError::TouchedUnallowedStorageSlot { address, key }
+            if address == token && key == slot
+    );
+    // Without a paymaster, allowing all tokens shouldn't trust any token.
+    let err = validate_tx(&pool, tx.clone(), AaTokenPolicy::AllowAll)
+        .await
+        .unwrap_err();
+    assert_matches!(
+        err,
+        ValidationError::TouchedUnallowedStorageSlot { address, .. } if address == token
+    );
+
+    let paymaster_params = PaymasterParams {
+        paymaster,
+        paymaster_input: approval_based_paymaster_input(token),
+    };
+    let mut tx = tx;
+    tx.common_data.paymaster_params = paymaster_params.clone();
+    validate_tx(&pool, tx, AaTokenPolicy::AllowAll)
+        .await
+        .unwrap();
+
+    // The same slot of another contract must not be trusted.
+    let mut tx = create_slot_reading_tx(account, other_token, slot);
+    tx.common_data.paymaster_params = paymaster_params;
+    let err = validate_tx(&pool, tx, AaTokenPolicy::AllowAll)
+        .await
+        .unwrap_err();
+    assert_matches!(
+        err,
+        ValidationError::TouchedUnallowedStorageSlot { address, key }
+            if address == other_token && key == slot
+    );
+}
+
+#[tokio::test]
+async fn checking_aa_token_whitelist_membership() {
+    let tokens: Vec<_> = (0..1_000).map(|_| Address::random()).collect();
+    let non_listed_tokens: Vec<_> = (0..100).map(|_| Address::random()).collect();
+    let whitelist = AaTokenWhitelist::from(tokens.clone());
+    assert_eq!(whitelist.tokens(), tokens);
+    for token in tokens.iter().chain(&non_listed_tokens) {
+        // Should be equivalent to a linear scan of the list.
+        assert_eq!(whitelist.is_whitelisted(token), tokens.contains(token));
+    }
+
+    let tx = create_l2_transaction(10, 100);
+    let bridged_token = non_listed_tokens[0];
+    let policy = AaTokenPolicy::whitelist(tokens.clone());
+    let trusted_tokens = policy.trusted_tokens(&tx, vec![bridged_token]).await;
+    assert_eq!(trusted_tokens.len(), tokens.len() + 1);
+    assert!(trusted_tokens.contains(&tokens[42]));
+    assert!(trusted_tokens.contains(&bridged_token));
+    assert!(!trusted_tokens.contains(&non_listed_tokens[1]));
+}
+
+#[tokio::test]
+async fn composite_tracer_collects_outputs_in_single_pass() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    let block_args = BlockArgs::pending(&mut storage).await.unwrap();
+    drop(storage);
+
+    let (vm_concurrency_limiter, _) = VmConcurrencyLimiter::new(1, None);
+    let vm_permit = vm_concurrency_limiter
+        .acquire(VmPriority::Background)
+        .await
+        .unwrap();
+    let transaction: Transaction = create_l2_transaction(10, 100).into();
+    let execution_args = TxExecutionArgs::for_gas_estimate(None, &transaction, 123);
+    let mut tracers = CompositeApiTracer::default();
+    tracers.push(ApiTracer::CallTracer(Default::default()));
+    tracers.push(ApiTracer::CallTracer(Default::default()));
+
+    TransactionExecutor::Real
+        .execute_tx_in_sandbox(
+            vm_permit,
+            TxSharedArgs::mock(ApiContracts::load_from_disk().estimate_gas),
+            true,
+            execution_args,
+            pool,
+            transaction,
+            block_args,
+            tracers.tracers(),
+        )
+        .await
+        .unwrap();
+
+    let outputs = tracers.into_outputs();
+    assert_eq!(outputs.len(), 2);
+    let call_counts: Vec<_> = outputs
+        .into_iter()
+        .map(|output| match output {
+            ApiTracerOutput::CallTracer(calls) => calls.expect("no calls recorded").len(),
+        })
+        .collect();
+    assert!(call_counts[0] > 0);
+    assert_eq!(call_counts[0], call_counts[1]);
+}
+
+#[tokio::test]
+async fn tracing_call() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    let block_args = BlockArgs::pending(&mut storage).await.unwrap();
+    drop(storage);
+
+    let (vm_concurrency_limiter, _) = VmConcurrencyLimiter::new(1, None);
+    let tx = create_l2_transaction(10, 100);
+    for only_top_call in [false, true] {
+        let vm_permit = vm_concurrency_limiter
+            .acquire(VmPriority::Background)
+            .await
+            .unwrap();
+        let call = TransactionExecutor::Real
+            .trace_call(
+                vm_permit,
+                TxSharedArgs::mock(ApiContracts::load_from_disk().eth_call),
+                pool.clone(),
+                tx.clone(),
+                block_args,
+                None,
+                None,
+                api::CallTracerConfig { only_top_call },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(call.gas, tx.common_data.fee.gas_limit.as_u64());
+        assert!(call.gas_used > 0);
+        assert_eq!(call.calls.is_empty(), only_top_call, "{call:?}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cancelling_execution_on_runtime_shutdown() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    let block_args = BlockArgs::pending(&mut storage).await.unwrap();
+    drop(storage);
+
+    // Use a separate runtime for the VM permit, so that it can be shut down during execution.
+    let vm_runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .unwrap();
+    let (vm_concurrency_limiter, _) = {
+        let _guard = vm_runtime.enter();
+        VmConcurrencyLimiter::new(1, None)
+    };
+    let vm_permit = vm_concurrency_limiter
+        .acquire(VmPriority::Background)
+        .await
+        .unwrap();
+    assert!(vm_permit.is_runtime_alive());
+    let transaction: Transaction = create_l2_transaction(10, 100).into();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let permit = vm_permit.clone();
+        apply_vm_in_sandbox(
+            vm_permit,
+            TxSharedArgs::mock(ApiContracts::load_from_disk().estimate_gas),
+            true,
+            &TxExecutionArgs::for_gas_estimate(None, &transaction, 123),
+            &pool,
+            transaction.clone(),
+            block_args,
+            |vm, tx| {
+                vm_runtime.shutdown_background();
+                while permit.is_runtime_alive() {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                let tracer = CancellationTracer::new(permit.runtime_shutdown_flag());
+                vm.inspect_transaction_with_bytecode_compression(
+                    vec![tracer.into_tracer_pointer()].into(),
+                    tx,
+                    true,
+                )
+            },
+        )
+    })
+    .await
+    .expect("VM execution panicked");
+
+    let err = result.unwrap_err();
+    assert_matches!(
+        err.downcast_ref::<SandboxExecutionError>(),
+        Some(SandboxExecutionError::Cancelled)
+    );
+}
+
+#[tokio::test]
+async fn cancelling_execution_with_token() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    let block_args = BlockArgs::pending(&mut storage).await.unwrap();
+    drop(storage);
+
+    let (vm_concurrency_limiter, _) = VmConcurrencyLimiter::new(1, None);
+    let vm_permit = vm_concurrency_limiter
+        .acquire(VmPriority::Background)
+        .await
+        .unwrap();
+    let transaction: Transaction = create_l2_transaction(10, 100).into();
+    let execution_args = TxExecutionArgs::for_gas_estimate(None, &transaction, 123);
+    let token = CancellationToken::new();
+
+    let result = tokio::task::spawn_blocking(move || {
+        apply_vm_in_sandbox(
+            vm_permit,
+            TxSharedArgs::mock(ApiContracts::load_from_disk().estimate_gas),
+            true,
+            &execution_args,
+            &pool,
+            transaction,
+            block_args,
+            |vm, tx| {
+                // Emulate the client disconnecting after the VM is initialized.
+                drop(token.clone().drop_guard());
+                let cancellation_flag = execute::CancellationFlag::new(token);
+                vm.inspect_transaction_with_bytecode_compression(
+                    vec![cancellation_flag.tracer().into_tracer_pointer()].into(),
+                    tx,
+                    true,
+                )
+            },
+        )
+    })
+    .await
+    .expect("VM execution panicked");
+
+    // The VM must stop right away rather than executing the transaction to completion.
+    let (_, result) = result.unwrap();
+    assert_matches!(result.result, ExecutionResult::Halt { .. });
+    assert!(
+        result.statistics.cycles_used < 100,
+        "{:?}",
+        result.statistics
+    );
+    // The permit must be released once the execution is cancelled.
+    assert_eq!(vm_concurrency_limiter.available_permits(), 1);
+}
+
+#[tokio::test]
+async fn building_shared_args() {
+    let token = Address::repeat_byte(0x42);
+    let fee_input = BatchFeeInput::sensible_l1_pegged_default();
+    let shared_args = TxSharedArgs::builder(fee_input)
+        .with_chain_id(L2ChainId::from(270))
+        .with_aa_token_policy(AaTokenPolicy::whitelist(vec![token]))
+        .build(ApiContracts::load_from_disk().eth_call)
+        .unwrap();
+    assert_eq!(shared_args.chain_id, L2ChainId::from(270));
+    let tx = create_l2_transaction(10, 100);
+    assert_eq!(
+        shared_args
+            .aa_token_policy
+            .trusted_tokens(&tx, vec![])
+            .await,
+        HashSet::from([token])
+    );
+    assert_eq!(shared_args.fee_input, fee_input);
+    assert_eq!(
+        shared_args.validation_computational_gas_limit,
+        BATCH_COMPUTATIONAL_GAS_LIMIT
+    );
+
+    let mock_args = TxSharedArgs::mock(ApiContracts::load_from_disk().eth_call);
+    assert_eq!(mock_args.fee_input, BatchFeeInput::l1_pegged(55, 555));
+    assert_eq!(mock_args.validation_computational_gas_limit, u32::MAX);
+    let trusted_tokens = mock_args.aa_token_policy.trusted_tokens(&tx, vec![]).await;
+    assert!(trusted_tokens.is_empty(), "{trusted_tokens:?}");
+}
+
+#[test]
+fn validating_shared_args() {
+    let contracts = ApiContracts::load_from_disk().eth_call;
+
+    let err = TxSharedArgs::builder(BatchFeeInput::l1_pegged(1_000, 0))
+        .build(contracts.clone())
+        .unwrap_err();
+    assert_eq!(err, TxSharedArgsError::ZeroL2GasPrice);
+    let err = TxSharedArgs::builder(BatchFeeInput::pubdata_independent(1_000, 0, 1_000))
+        .build(contracts.clone())
+        .unwrap_err();
+    assert_eq!(err, TxSharedArgsError::ZeroL2GasPrice);
+    // The default fee input is invalid, so it must not be used implicitly.
+    let err = TxSharedArgs::builder(BatchFeeInput::default())
+        .build(contracts.clone())
+        .unwrap_err();
+    assert_eq!(err, TxSharedArgsError::ZeroL2GasPrice);
+
+    let err = TxSharedArgs::builder(BatchFeeInput::l1_pegged(u64::MAX, 1_000))
+        .build(contracts.clone())
+        .unwrap_err();
+    assert_eq!(
+        err,
+        TxSharedArgsError::PubdataPriceOverflow {
+            l1_gas_price: u64::MAX
+        }
+    );
+
+    let err = TxSharedArgs::builder(BatchFeeInput::sensible_l1_pegged_default())
+        .with_validation_computational_gas_limit(0)
+        .build(contracts)
+        .unwrap_err();
+    assert_eq!(err, TxSharedArgsError::ZeroValidationGasLimit);
+}
@@ -6,7 +6,7 @@ use zksync_state::WriteStorage;
 use zksync_types::vm_trace::Call;
 
 /// Custom tracers supported by our API
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum ApiTracer {
     CallTracer(Arc<OnceCell<Vec<Call>>>),
 }
@@ -22,4 +22,51 @@ impl ApiTracer {
             ApiTracer::CallTracer(tracer) => CallTracer::new(tracer.clone()).into_tracer_pointer(),
         }
     }
+
+    fn into_output(self) -> ApiTracerOutput {
+        match self {
+            ApiTracer::CallTracer(calls) => {
+                let calls = Arc::try_unwrap(calls)
+                    .map(OnceCell::into_inner)
+                    .unwrap_or_else(|calls| calls.get().cloned());
+                ApiTracerOutput::CallTracer(calls)
+            }
+        }
+    }
+}
+
+/// Output of an [`ApiTracer`] collected after the execution.
+#[derive(Debug)]
+pub(crate) enum ApiTracerOutput {
+    /// Calls recorded by the call tracer. `None` if the execution hasn't finished.
+    CallTracer(Option<Vec<Call>>),
+}
+
+/// Composition of [`ApiTracer`]s collecting outputs in a single VM pass.
+///
+/// Each VM callback is dispatched to all tracers. If any tracer requests to stop execution,
+/// execution is stopped regardless of other tracers (the strictest request wins).
+#[derive(Debug, Default)]
+pub(crate) struct CompositeApiTracer {
+    tracers: Vec<ApiTracer>,
+}
+
+impl CompositeApiTracer {
+    pub fn push(&mut self, tracer: ApiTracer) {
+        self.tracers.push(tracer);
+    }
+
+    /// Returns tracers to be installed for the execution. The returned tracers share outputs with this composite.
+    pub fn tracers(&self) -> Vec<ApiTracer> {
+        self.tracers.clone()
+    }
+
+    /// Returns outputs of all tracers in the order they were added. Should be called after
+    /// the execution has finished.
+    pub fn into_outputs(self) -> Vec<ApiTracerOutput> {
+        self.tracers
+            .into_iter()
+            .map(ApiTracer::into_output)
+            .collect()
+    }
 }
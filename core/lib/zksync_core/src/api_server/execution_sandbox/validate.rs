@@ -1,8 +1,8 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, sync::Arc};
 
 use anyhow::Context as _;
 use multivm::{
-    interface::{ExecutionResult, VmExecutionMode, VmInterface},
+    interface::{ExecutionResult, Halt, VmExecutionMode, VmInterface},
     tracers::{
         validator::{self, ValidationTracer, ValidationTracerParams},
         StorageInvocations,
@@ -10,8 +10,16 @@ use multivm::{
     vm_latest::HistoryDisabled,
     MultiVMTracer,
 };
+use once_cell::sync::Lazy;
+use tokio::sync::RwLock;
 use zksync_dal::{Connection, ConnectionPool, Core, CoreDal};
-use zksync_types::{l2::L2Tx, Address, Transaction, TRUSTED_ADDRESS_SLOTS, TRUSTED_TOKEN_SLOTS};
+use zksync_types::{
+    ethabi::{self, ParamType},
+    l2::L2Tx,
+    vm_trace::ViolatedValidationRule,
+    Address, Transaction, TRUSTED_ADDRESS_SLOTS, TRUSTED_TOKEN_SLOTS, U256,
+};
+use zksync_utils::u256_to_h256;
 
 use super::{
     apply,
@@ -20,16 +28,133 @@ use super::{
     BlockArgs, TxExecutionArgs, TxSharedArgs, VmPermit,
 };
 
+/// Policy determining which tokens account abstraction may use during validation, e.g. to pay fees.
+#[derive(Debug, Clone)]
+pub(crate) enum AaTokenPolicy {
+    /// Any token used by an approval-based paymaster is treated as whitelisted for the paid transaction.
+    /// Intended for permissive environments, such as testnets.
+    AllowAll,
+    /// Only bridged tokens and the specified tokens are allowed. The whitelist is shared with its source
+    /// (e.g., [`TxSender`](crate::api_server::tx_sender::TxSender)), so it can be updated without a restart;
+    /// cloning the policy doesn't copy the whitelist.
+    Whitelist(Arc<RwLock<AaTokenWhitelist>>),
+}
+
+impl Default for AaTokenPolicy {
+    fn default() -> Self {
+        Self::whitelist(Vec::new())
+    }
+}
+
+impl AaTokenPolicy {
+    /// Creates a policy with the specified whitelist not shared with any other source.
+    pub fn whitelist(tokens: Vec<Address>) -> Self {
+        Self::Whitelist(Arc::new(RwLock::new(tokens.into())))
+    }
+
+    /// Returns tokens that may be used by account abstraction when validating `tx`, given the bridged tokens.
+    /// Should be called once per validation, so that updates to the whitelist don't affect it midway.
+    pub async fn trusted_tokens(
+        &self,
+        tx: &L2Tx,
+        bridged_tokens: Vec<Address>,
+    ) -> HashSet<Address> {
+        let mut tokens: HashSet<_> = bridged_tokens.into_iter().collect();
+        match self {
+            Self::AllowAll => tokens.extend(approval_based_paymaster_token(tx)),
+            Self::Whitelist(whitelist) => tokens.extend(&whitelist.read().await.lookup),
+        }
+        tokens
+    }
+}
+
+/// Extracts the token from the input of an approval-based paymaster flow, if `tx` uses this flow.
+fn approval_based_paymaster_token(tx: &L2Tx) -> Option<Address> {
+    const PARAM_TYPES: [ParamType; 3] =
+        [ParamType::Address, ParamType::Uint(256), ParamType::Bytes];
+    static SELECTOR: Lazy<[u8; 4]> =
+        Lazy::new(|| ethabi::short_signature("approvalBased", &PARAM_TYPES));
+
+    let input = &tx.common_data.paymaster_params.paymaster_input;
+    let params = input.strip_prefix(SELECTOR.as_slice())?;
+    let tokens = ethabi::decode(&PARAM_TYPES, params).ok()?;
+    tokens.into_iter().next()?.into_address()
+}
+
+/// Whitelist of tokens for account abstraction. Retains the order of tokens as they were provided (e.g., to return them
+/// via the API), and maintains a set of tokens for O(1) membership checks.
+#[derive(Debug, Clone, Default)]
+pub struct AaTokenWhitelist {
+    tokens: Vec<Address>,
+    lookup: HashSet<Address>,
+}
+
+impl From<Vec<Address>> for AaTokenWhitelist {
+    fn from(tokens: Vec<Address>) -> Self {
+        let lookup = tokens.iter().copied().collect();
+        Self { tokens, lookup }
+    }
+}
+
+impl AaTokenWhitelist {
+    /// Returns whitelisted tokens in the order they were provided.
+    pub fn tokens(&self) -> &[Address] {
+        &self.tokens
+    }
+
+    pub fn is_whitelisted(&self, token: &Address) -> bool {
+        self.lookup.contains(token)
+    }
+}
+
 /// Validation error used by the sandbox. Besides validation errors returned by VM, it also includes an internal error
 /// variant (e.g., for DB-related errors).
+///
+/// VM errors are split into structured variants, so that they can be reported to API clients in a machine-readable form.
+/// Messages are the same as for the corresponding [VM errors](validator::ValidationError).
 #[derive(Debug, thiserror::Error)]
-pub(crate) enum ValidationError {
-    #[error("VM validation error: {0}")]
-    Vm(validator::ValidationError),
+pub enum ValidationError {
+    /// Transaction has halted during validation (e.g., the account contract has rejected it).
+    #[error("Validation revert: {0}")]
+    FailedTx(Halt),
+    #[error(
+        "Violated validation rules: Touched unallowed storage slots: address {}, key: {}",
+        hex::encode(.address),
+        hex::encode(u256_to_h256(*.key))
+    )]
+    TouchedUnallowedStorageSlot { address: Address, key: U256 },
+    #[error("Violated validation rules: Called contract with no code: {}", hex::encode(.address))]
+    CalledContractWithNoCode { address: Address },
+    /// Validation has accessed the context unavailable to it, e.g. by using a disallowed opcode.
+    #[error("Violated validation rules: Touched unallowed context")]
+    TouchedUnallowedContext,
+    /// Validation has spent more computational gas than allowed. The VM doesn't report the amount of gas spent.
+    #[error("Violated validation rules: Took too many computational gas, allowed limit: {limit}")]
+    ValidationGasLimitExceeded { limit: u32 },
     #[error("Internal error")]
     Internal(#[from] anyhow::Error),
 }
 
+impl From<validator::ValidationError> for ValidationError {
+    fn from(err: validator::ValidationError) -> Self {
+        match err {
+            validator::ValidationError::FailedTx(reason) => Self::FailedTx(reason),
+            validator::ValidationError::ViolatedRule(rule) => match rule {
+                ViolatedValidationRule::TouchedUnallowedStorageSlots(address, key) => {
+                    Self::TouchedUnallowedStorageSlot { address, key }
+                }
+                ViolatedValidationRule::CalledContractWithNoCode(address) => {
+                    Self::CalledContractWithNoCode { address }
+                }
+                ViolatedValidationRule::TouchedUnallowedContext => Self::TouchedUnallowedContext,
+                ViolatedValidationRule::TookTooManyComputationalGas(limit) => {
+                    Self::ValidationGasLimitExceeded { limit }
+                }
+            },
+        }
+    }
+}
+
 impl TransactionExecutor {
     pub(crate) async fn validate_tx_in_sandbox(
         &self,
@@ -54,7 +179,7 @@ impl TransactionExecutor {
             &mut connection,
             &tx,
             computational_gas_limit,
-            &shared_args.whitelisted_tokens_for_aa,
+            &shared_args.aa_token_policy,
         )
         .await
         .context("failed getting validation params")?;
@@ -113,18 +238,18 @@ impl TransactionExecutor {
         .context("transaction validation panicked")??;
 
         stage_latency.observe();
-        validation_result.map_err(ValidationError::Vm)
+        validation_result.map_err(ValidationError::from)
     }
 }
 
 /// Some slots can be marked as "trusted". That is needed for slots which can not be
 /// trusted to change between validation and execution in general case, but
 /// sometimes we can safely rely on them to not change often.
-async fn get_validation_params(
+pub(super) async fn get_validation_params(
     connection: &mut Connection<'_, Core>,
     tx: &L2Tx,
     computational_gas_limit: u32,
-    whitelisted_tokens_for_aa: &[Address],
+    aa_token_policy: &AaTokenPolicy,
 ) -> anyhow::Result<ValidationTracerParams> {
     let method_latency = EXECUTION_METRICS.get_validation_params.start();
     let user_address = tx.common_data.initiator_address;
@@ -133,16 +258,13 @@ async fn get_validation_params(
     // This method assumes that the number of tokens is relatively low. When it grows
     // we may need to introduce some kind of caching.
     let all_bridged_tokens = connection.tokens_dal().get_all_l2_token_addresses().await?;
-    let all_tokens: Vec<_> = all_bridged_tokens
-        .iter()
-        .chain(whitelisted_tokens_for_aa)
-        .collect();
+    let all_tokens = aa_token_policy.trusted_tokens(tx, all_bridged_tokens).await;
     EXECUTION_METRICS.tokens_amount.set(all_tokens.len());
 
     let span = tracing::debug_span!("compute_trusted_slots_for_validation").entered();
     let trusted_slots: HashSet<_> = all_tokens
         .iter()
-        .flat_map(|&token| TRUSTED_TOKEN_SLOTS.iter().map(move |&slot| (*token, slot)))
+        .flat_map(|&token| TRUSTED_TOKEN_SLOTS.iter().map(move |&slot| (token, slot)))
         .collect();
 
     // We currently don't support any specific trusted addresses.
@@ -152,11 +274,7 @@ async fn get_validation_params(
     // Required for working with transparent proxies.
     let trusted_address_slots: HashSet<_> = all_tokens
         .into_iter()
-        .flat_map(|token| {
-            TRUSTED_ADDRESS_SLOTS
-                .iter()
-                .map(move |&slot| (*token, slot))
-        })
+        .flat_map(|token| TRUSTED_ADDRESS_SLOTS.iter().map(move |&slot| (token, slot)))
         .collect();
     EXECUTION_METRICS
         .trusted_address_slots_amount
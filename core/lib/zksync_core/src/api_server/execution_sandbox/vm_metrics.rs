@@ -1,7 +1,7 @@
 use std::time::Duration;
 
 use multivm::interface::{VmExecutionResultAndLogs, VmMemoryMetrics};
-use vise::{Buckets, EncodeLabelSet, EncodeLabelValue, Family, Gauge, Histogram, Metrics};
+use vise::{Buckets, Counter, EncodeLabelSet, EncodeLabelValue, Family, Gauge, Histogram, Metrics};
 use zksync_shared_metrics::InteractionType;
 use zksync_state::StorageViewMetrics;
 use zksync_types::{
@@ -11,6 +11,8 @@ use zksync_types::{
 };
 use zksync_utils::bytecode::bytecode_len_in_bytes;
 
+use super::VmPriority;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
 #[metrics(label = "type", rename_all = "snake_case")]
 enum SizeType {
@@ -74,12 +76,40 @@ static STORAGE_METRICS: vise::Global<RuntimeContextStorageMetrics> = vise::Globa
 #[metrics(label = "stage", rename_all = "snake_case")]
 pub(super) enum SandboxStage {
     VmConcurrencyLimiterAcquire,
+    /// Loading pruning info from Postgres in `BlockStartInfo`.
+    PruningInfoFetch,
+    /// Resolving `BlockArgs` in full, including the stages below.
+    BlockArgsResolution,
+    /// Resolving the L1 batch of a miniblock for `BlockArgs`.
+    L1BatchResolution,
+    /// Loading the L1 batch timestamp for `BlockArgs`.
+    L1BatchTimestampFetch,
     Initialization,
     ValidateInSandbox,
     Validation,
     Execution,
 }
 
+/// Outcome of refreshing the pruning info cache in `BlockStartInfo` after a cache miss.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+#[metrics(label = "refresh", rename_all = "snake_case")]
+pub(super) enum CacheRefresh {
+    /// Cache was updated with the info loaded from Postgres.
+    Updated,
+    /// Cache was updated concurrently with a newer info, so the loaded info was discarded.
+    NewerCachePresent,
+}
+
+/// Kind of a DB query issued by the sandbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+#[metrics(label = "query", rename_all = "snake_case")]
+pub(super) enum SandboxQuery {
+    PruningInfo,
+    ResolveBlockId,
+    ResolveL1BatchNumber,
+    L1BatchTimestamp,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
 #[metrics(label = "stage", rename_all = "snake_case")]
 pub(in crate::api_server) enum SubmitTxStage {
@@ -103,6 +133,26 @@ pub(in crate::api_server) struct SandboxMetrics {
     #[metrics(buckets = Buckets::linear(0.0..=2_000.0, 200.0))]
     pub(super) sandbox_execution_permits: Histogram<usize>,
     #[metrics(buckets = Buckets::LATENCIES)]
+    pub(super) sandbox_permit_hold_time: Family<VmPriority, Histogram<Duration>>,
+    /// Number of VM permits held longer than the configured slow permit threshold.
+    pub(super) sandbox_slow_permits: Family<VmPriority, Counter>,
+    /// Time spent waiting for a VM permit. Unlike the `vm_concurrency_limiter_acquire` sandbox stage, buckets
+    /// are fine-grained enough to reason about high percentiles of the wait time.
+    #[metrics(buckets = Buckets::exponential(0.001..=60.0, 2.0))]
+    pub(super) sandbox_permit_acquire_latency: Family<VmPriority, Histogram<Duration>>,
+    /// Number of VM permit acquisitions that waited longer than the configured starvation threshold.
+    pub(super) sandbox_starved_permit_acquisitions: Family<VmPriority, Counter>,
+    /// Number of VM permit acquisitions that have timed out.
+    pub(super) sandbox_permit_acquire_timeouts: Counter,
+    /// Number of VM permit acquisitions rejected because the queue of waiting callers was full.
+    pub(super) sandbox_permit_acquire_rejections: Family<VmPriority, Counter>,
+    /// Number of pruning info requests in `BlockStartInfo` served from the cache.
+    pub(super) block_start_info_cache_hits: Counter,
+    /// Number of pruning info requests in `BlockStartInfo` that had to query Postgres.
+    pub(super) block_start_info_cache_misses: Family<CacheRefresh, Counter>,
+    /// Number of sandbox DB queries that took longer than the configured slow query threshold.
+    pub(super) sandbox_slow_queries: Family<SandboxQuery, Counter>,
+    #[metrics(buckets = Buckets::LATENCIES)]
     pub submit_tx: Family<SubmitTxStage, Histogram<Duration>>,
     #[metrics(buckets = Buckets::linear(0.0..=30.0, 3.0))]
     pub estimate_gas_binary_search_iterations: Histogram<usize>,
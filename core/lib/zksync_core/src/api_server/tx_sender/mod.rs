@@ -35,8 +35,10 @@ use self::tx_sink::TxSink;
 use crate::{
     api_server::{
         execution_sandbox::{
-            BlockArgs, SubmitTxStage, TransactionExecutor, TxExecutionArgs, TxSharedArgs,
-            VmConcurrencyLimiter, VmPermit, SANDBOX_METRICS,
+            check_eth_call_cost, AaTokenPolicy, AaTokenWhitelist, BlockArgs,
+            OversizedResponsePolicy, ResponseSizeLimit, SandboxExecutionError, SubmitTxStage,
+            TransactionExecutor, TxExecutionArgs, TxSharedArgs, VmConcurrencyLimiter, VmPermit,
+            VmPriority, SANDBOX_METRICS,
         },
         tx_sender::result::ApiCallResult,
     },
@@ -160,7 +162,7 @@ pub struct TxSenderBuilder {
     /// Batch sealer used to check whether transaction can be executed by the sequencer.
     sealer: Option<Arc<dyn ConditionalSealer>>,
     /// Cache for tokens that are white-listed for AA.
-    whitelisted_tokens_for_aa_cache: Option<Arc<RwLock<Vec<Address>>>>,
+    whitelisted_tokens_for_aa_cache: Option<Arc<RwLock<AaTokenWhitelist>>>,
 }
 
 impl TxSenderBuilder {
@@ -183,7 +185,7 @@ impl TxSenderBuilder {
         self
     }
 
-    pub fn with_whitelisted_tokens_for_aa(mut self, cache: Arc<RwLock<Vec<Address>>>) -> Self {
+    pub fn with_whitelisted_tokens_for_aa(mut self, cache: Arc<RwLock<AaTokenWhitelist>>) -> Self {
         self.whitelisted_tokens_for_aa_cache = Some(cache);
         self
     }
@@ -199,7 +201,9 @@ impl TxSenderBuilder {
         let sealer = self.sealer.unwrap_or_else(|| Arc::new(NoopSealer));
         let whitelisted_tokens_for_aa_cache =
             self.whitelisted_tokens_for_aa_cache.unwrap_or_else(|| {
-                Arc::new(RwLock::new(self.config.whitelisted_tokens_for_aa.clone()))
+                Arc::new(RwLock::new(
+                    self.config.whitelisted_tokens_for_aa.clone().into(),
+                ))
             });
 
         TxSender(Arc::new(TxSenderInner {
@@ -232,6 +236,13 @@ pub struct TxSenderConfig {
     pub chain_id: L2ChainId,
     pub max_pubdata_per_batch: u64,
     pub whitelisted_tokens_for_aa: Vec<Address>,
+    /// Whether any token used by an approval-based paymaster may be used by AA when validating the paid transaction.
+    pub all_tokens_allowed_for_aa: bool,
+    /// Limit on the size of `eth_call` return data.
+    pub response_size_limit: Option<ResponseSizeLimit>,
+    /// Ceiling on the estimated cost of a single `eth_call` in gas. Calls exceeding it are rejected
+    /// before they acquire a VM permit.
+    pub max_call_cost: Option<u64>,
 }
 
 impl TxSenderConfig {
@@ -252,6 +263,18 @@ impl TxSenderConfig {
             chain_id,
             max_pubdata_per_batch: state_keeper_config.max_pubdata_per_batch,
             whitelisted_tokens_for_aa: web3_json_config.whitelisted_tokens_for_aa.clone(),
+            all_tokens_allowed_for_aa: web3_json_config.all_tokens_allowed_for_aa,
+            response_size_limit: web3_json_config.eth_call_max_response_size.map(|max_size| {
+                ResponseSizeLimit {
+                    max_size,
+                    policy: if web3_json_config.eth_call_truncate_oversized_responses {
+                        OversizedResponsePolicy::Truncate
+                    } else {
+                        OversizedResponsePolicy::Error
+                    },
+                }
+            }),
+            max_call_cost: web3_json_config.eth_call_max_cost,
         }
     }
 }
@@ -269,7 +292,7 @@ pub struct TxSenderInner {
     // Caches used in VM execution.
     storage_caches: PostgresStorageCaches,
     // Cache for white-listed tokens.
-    pub(super) whitelisted_tokens_for_aa_cache: Arc<RwLock<Vec<Address>>>,
+    pub(super) whitelisted_tokens_for_aa_cache: Arc<RwLock<AaTokenWhitelist>>,
     /// Batch sealer used to check whether transaction can be executed by the sequencer.
     sealer: Arc<dyn ConditionalSealer>,
     pub(super) executor: TransactionExecutor,
@@ -294,7 +317,21 @@ impl TxSender {
     }
 
     pub(crate) async fn read_whitelisted_tokens_for_aa_cache(&self) -> Vec<Address> {
-        self.0.whitelisted_tokens_for_aa_cache.read().await.clone()
+        self.0
+            .whitelisted_tokens_for_aa_cache
+            .read()
+            .await
+            .tokens()
+            .to_vec()
+    }
+
+    /// Returns the AA token policy sharing the whitelist with this sender.
+    pub(crate) fn aa_token_policy(&self) -> AaTokenPolicy {
+        if self.0.sender_config.all_tokens_allowed_for_aa {
+            AaTokenPolicy::AllowAll
+        } else {
+            AaTokenPolicy::Whitelist(self.0.whitelisted_tokens_for_aa_cache.clone())
+        }
     }
 
     async fn acquire_replica_connection(&self) -> anyhow::Result<Connection<'_, Core>> {
@@ -314,9 +351,13 @@ impl TxSender {
         stage_latency.observe();
 
         let stage_latency = SANDBOX_METRICS.submit_tx[&SubmitTxStage::DryRun].start();
-        let shared_args = self.shared_args().await;
-        let vm_permit = self.0.vm_concurrency_limiter.acquire().await;
-        let vm_permit = vm_permit.ok_or(SubmitTxError::ServerShuttingDown)?;
+        let shared_args = self.shared_args().await?;
+        let vm_permit = self
+            .0
+            .vm_concurrency_limiter
+            .acquire(VmPriority::Interactive)
+            .await;
+        let vm_permit = vm_permit?;
         let block_args = BlockArgs::pending(&mut connection).await?;
         drop(connection);
 
@@ -406,19 +447,17 @@ impl TxSender {
         }
     }
 
-    async fn shared_args(&self) -> TxSharedArgs {
-        TxSharedArgs {
-            operator_account: AccountTreeId::new(self.0.sender_config.fee_account_addr),
-            fee_input: self.0.batch_fee_input_provider.get_batch_fee_input().await,
-            base_system_contracts: self.0.api_contracts.eth_call.clone(),
-            caches: self.storage_caches(),
-            validation_computational_gas_limit: self
-                .0
-                .sender_config
-                .validation_computational_gas_limit,
-            chain_id: self.0.sender_config.chain_id,
-            whitelisted_tokens_for_aa: self.read_whitelisted_tokens_for_aa_cache().await,
-        }
+    async fn shared_args(&self) -> anyhow::Result<TxSharedArgs> {
+        let config = &self.0.sender_config;
+        let fee_input = self.0.batch_fee_input_provider.get_batch_fee_input().await;
+        TxSharedArgs::builder(fee_input)
+            .with_operator_account(AccountTreeId::new(config.fee_account_addr))
+            .with_caches(self.storage_caches())
+            .with_validation_computational_gas_limit(config.validation_computational_gas_limit)
+            .with_chain_id(config.chain_id)
+            .with_aa_token_policy(self.aa_token_policy())
+            .build(self.0.api_contracts.eth_call.clone())
+            .context("invalid shared args")
     }
 
     async fn validate_tx(
@@ -624,7 +663,7 @@ impl TxSender {
             }
         }
 
-        let shared_args = self.shared_args_for_gas_estimate(fee_model_params).await;
+        let shared_args = self.shared_args_for_gas_estimate(fee_model_params).await?;
         let vm_execution_cache_misses_limit = self.0.sender_config.vm_execution_cache_misses_limit;
         let execution_args =
             TxExecutionArgs::for_gas_estimate(vm_execution_cache_misses_limit, &tx, base_fee);
@@ -645,19 +684,21 @@ impl TxSender {
         Ok((execution_output.vm, execution_output.metrics))
     }
 
-    async fn shared_args_for_gas_estimate(&self, fee_input: BatchFeeInput) -> TxSharedArgs {
+    async fn shared_args_for_gas_estimate(
+        &self,
+        fee_input: BatchFeeInput,
+    ) -> anyhow::Result<TxSharedArgs> {
         let config = &self.0.sender_config;
 
-        TxSharedArgs {
-            operator_account: AccountTreeId::new(config.fee_account_addr),
-            fee_input,
+        TxSharedArgs::builder(fee_input)
+            .with_operator_account(AccountTreeId::new(config.fee_account_addr))
             // We want to bypass the computation gas limit check for gas estimation
-            validation_computational_gas_limit: BATCH_COMPUTATIONAL_GAS_LIMIT,
-            base_system_contracts: self.0.api_contracts.estimate_gas.clone(),
-            caches: self.storage_caches(),
-            chain_id: config.chain_id,
-            whitelisted_tokens_for_aa: self.read_whitelisted_tokens_for_aa_cache().await,
-        }
+            .with_validation_computational_gas_limit(BATCH_COMPUTATIONAL_GAS_LIMIT)
+            .with_caches(self.storage_caches())
+            .with_chain_id(config.chain_id)
+            .with_aa_token_policy(self.aa_token_policy())
+            .build(self.0.api_contracts.estimate_gas.clone())
+            .context("invalid shared args")
     }
 
     pub async fn get_txs_fee_in_wei(
@@ -669,7 +710,8 @@ impl TxSender {
         let estimation_started_at = Instant::now();
 
         let mut connection = self.acquire_replica_connection().await?;
-        let block_args = BlockArgs::pending(&mut connection).await?;
+        // Fix the pending L1 batch timestamp, so that all estimation steps are executed in the same VM environment.
+        let block_args = BlockArgs::pending_with_timestamp(&mut connection).await?;
         let protocol_version = pending_protocol_version(&mut connection)
             .await
             .context("failed getting pending protocol version")?;
@@ -748,8 +790,12 @@ impl TxSender {
         }
 
         // Acquire the vm token for the whole duration of the binary search.
-        let vm_permit = self.0.vm_concurrency_limiter.acquire().await;
-        let vm_permit = vm_permit.ok_or(SubmitTxError::ServerShuttingDown)?;
+        let vm_permit = self
+            .0
+            .vm_concurrency_limiter
+            .acquire(VmPriority::Interactive)
+            .await;
+        let vm_permit = vm_permit?;
 
         // When the pubdata cost grows very high, the total gas limit required may become very high as well. If
         // we do binary search over any possible gas limit naively, we may end up with a very high number of iterations,
@@ -915,22 +961,47 @@ impl TxSender {
         block_args: BlockArgs,
         tx: L2Tx,
     ) -> Result<Vec<u8>, SubmitTxError> {
-        let vm_permit = self.0.vm_concurrency_limiter.acquire().await;
-        let vm_permit = vm_permit.ok_or(SubmitTxError::ServerShuttingDown)?;
+        let mut gas_limit = None;
+        if let Some(max_cost) = self.0.sender_config.max_call_cost {
+            let target = tx.execute.contract_address;
+            let target_code_hash = self
+                .acquire_replica_connection()
+                .await?
+                .storage_web3_dal()
+                .get_historical_value_unchecked(
+                    &get_code_key(&target),
+                    block_args.resolved_block_number(),
+                )
+                .await
+                .with_context(|| format!("failed getting code hash for call target {target:?}"))?;
+            gas_limit = check_eth_call_cost(&tx, target_code_hash != H256::zero(), max_cost)?;
+        }
+        let vm_permit = self
+            .0
+            .vm_concurrency_limiter
+            .acquire(VmPriority::Background)
+            .await;
+        let vm_permit = vm_permit?;
 
         let vm_execution_cache_misses_limit = self.0.sender_config.vm_execution_cache_misses_limit;
         self.0
             .executor
             .execute_tx_eth_call(
                 vm_permit,
-                self.shared_args().await,
+                self.shared_args().await?,
                 self.0.replica_connection_pool.clone(),
                 tx,
                 block_args,
                 vm_execution_cache_misses_limit,
+                gas_limit,
+                self.0.sender_config.response_size_limit,
                 vec![],
             )
-            .await?
+            .await
+            .map_err(|err| match err.downcast::<SandboxExecutionError>() {
+                Ok(err) => err.into(),
+                Err(err) => SubmitTxError::Internal(err),
+            })?
             .into_api_call_result()
     }
 
@@ -3,7 +3,7 @@ use thiserror::Error;
 use zksync_types::{l2::error::TxCheckError, U256};
 use zksync_web3_decl::error::EnrichedClientError;
 
-use crate::api_server::execution_sandbox::{SandboxExecutionError, ValidationError};
+use crate::api_server::execution_sandbox::{AcquireError, SandboxExecutionError, ValidationError};
 
 /// Errors that con occur submitting a transaction or estimating gas for its execution.
 #[derive(Debug, Error)]
@@ -32,6 +32,9 @@ pub enum SubmitTxError {
     BootloaderFailure(String),
     #[error("failed to validate the transaction. reason: {0}")]
     ValidationFailed(String),
+    /// Same as `ValidationFailed`, but with the structured cause. Never contains [`ValidationError::Internal`].
+    #[error("failed to validate the transaction. reason: {0}")]
+    ValidationRuleViolated(ValidationError),
     #[error("not enough balance to cover the fee. error message: {0}")]
     FailedToChargeFee(String),
     #[error("failed paymaster validation. error message: {0}")]
@@ -72,6 +75,10 @@ pub enum SubmitTxError {
     ProxyError(#[from] EnrichedClientError),
     #[error("not enough gas to publish compressed bytecodes")]
     FailedToPublishCompressedBytecodes,
+    #[error("call is too expensive: estimated cost {0} gas exceeds the limit of {1} gas")]
+    CallTooExpensive(u64, u64),
+    #[error("call return data of {0} bytes exceeds the limit of {1} bytes")]
+    ResponseTooLarge(usize, usize),
     /// Catch-all internal error (e.g., database error) that should not be exposed to the caller.
     #[error("internal error")]
     Internal(#[from] anyhow::Error),
@@ -91,7 +98,7 @@ impl SubmitTxError {
             Self::RateLimitExceeded => "rate-limit-exceeded",
             Self::ServerShuttingDown => "shutting-down",
             Self::BootloaderFailure(_) => "bootloader-failure",
-            Self::ValidationFailed(_) => "validation-failed",
+            Self::ValidationFailed(_) | Self::ValidationRuleViolated(_) => "validation-failed",
             Self::FailedToChargeFee(_) => "failed-too-charge-fee",
             Self::PaymasterValidationFailed(_) => "failed-paymaster-validation",
             Self::PrePaymasterPreparationFailed(_) => "failed-prepaymaster-preparation",
@@ -107,6 +114,8 @@ impl SubmitTxError {
             Self::IntrinsicGas => "intrinsic-gas",
             Self::ProxyError(_) => "proxy-error",
             Self::FailedToPublishCompressedBytecodes => "failed-to-publish-compressed-bytecodes",
+            Self::CallTooExpensive(_, _) => "call-too-expensive",
+            Self::ResponseTooLarge(_, _) => "response-too-large",
             Self::Internal(_) => "internal",
         }
     }
@@ -145,6 +154,23 @@ impl From<SandboxExecutionError> for SubmitTxError {
             SandboxExecutionError::FailedToPayForTransaction(reason) => {
                 Self::FailedToChargeFee(reason)
             }
+            SandboxExecutionError::TooExpensive { estimate, max_cost } => {
+                Self::CallTooExpensive(estimate, max_cost)
+            }
+            SandboxExecutionError::ResponseTooLarge { size, max_size } => {
+                Self::ResponseTooLarge(size, max_size)
+            }
+            SandboxExecutionError::Cancelled => Self::ServerShuttingDown,
+            SandboxExecutionError::Internal(err) => Self::Internal(err),
+        }
+    }
+}
+
+impl From<AcquireError> for SubmitTxError {
+    fn from(err: AcquireError) -> Self {
+        match err {
+            AcquireError::ShuttingDown => Self::ServerShuttingDown,
+            AcquireError::Timeout | AcquireError::Overloaded => Self::RateLimitExceeded,
         }
     }
 }
@@ -153,7 +179,7 @@ impl From<ValidationError> for SubmitTxError {
     fn from(err: ValidationError) -> Self {
         match err {
             ValidationError::Internal(err) => Self::Internal(err),
-            ValidationError::Vm(err) => Self::ValidationFailed(err.to_string()),
+            err => Self::ValidationRuleViolated(err),
         }
     }
 }
@@ -1,13 +1,24 @@
 //! Tests for the transaction sender.
 
+use std::collections::HashSet;
+
+use assert_matches::assert_matches;
+use multivm::{interface::ExecutionResult, vm_latest::constants::ETH_CALL_GAS_LIMIT};
 use zksync_config::configs::wallets::Wallets;
-use zksync_types::{get_nonce_key, L1BatchNumber, MiniblockNumber, StorageLog};
+use zksync_types::{
+    get_nonce_key, L1BatchNumber, MiniblockNumber, StorageLog, L2_ETH_TOKEN_ADDRESS,
+};
 
 use super::*;
 use crate::{
-    api_server::execution_sandbox::{testonly::MockTransactionExecutor, VmConcurrencyBarrier},
+    api_server::execution_sandbox::{
+        testonly::MockTransactionExecutor, OversizedResponsePolicy, VmConcurrencyBarrier,
+    },
     genesis::{insert_genesis_batch, GenesisParams},
-    utils::testonly::{create_miniblock, prepare_recovery_snapshot, MockBatchFeeParamsProvider},
+    utils::testonly::{
+        create_l2_transaction, create_miniblock, prepare_recovery_snapshot,
+        MockBatchFeeParamsProvider,
+    },
 };
 
 pub(crate) async fn create_test_tx_sender(
@@ -139,3 +150,199 @@ async fn getting_nonce_for_account_after_snapshot_recovery() {
     let nonce = tx_sender.get_expected_nonce(missing_address).await.unwrap();
     assert_eq!(nonce, Nonce(0));
 }
+
+#[tokio::test]
+async fn oversized_call_response_is_truncated_or_rejected() {
+    const RESPONSE_SIZE: usize = 1_000;
+    const MAX_SIZE: usize = 100;
+
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    let block_args = BlockArgs::pending(&mut storage).await.unwrap();
+    drop(storage);
+
+    let mut tx_executor = MockTransactionExecutor::default();
+    tx_executor.set_call_responses(|_, _| ExecutionResult::Success {
+        output: (0..RESPONSE_SIZE).map(|i| i as u8).collect(),
+    });
+    let (mut tx_sender, _vm_barrier) =
+        create_test_tx_sender(pool, L2ChainId::default(), tx_executor.into()).await;
+
+    let tx = create_l2_transaction(1, 1);
+    let output = tx_sender.eth_call(block_args, tx.clone()).await.unwrap();
+    assert_eq!(output.len(), RESPONSE_SIZE);
+
+    Arc::get_mut(&mut tx_sender.0)
+        .unwrap()
+        .sender_config
+        .response_size_limit = Some(ResponseSizeLimit {
+        max_size: MAX_SIZE,
+        policy: OversizedResponsePolicy::Truncate,
+    });
+    let output = tx_sender.eth_call(block_args, tx.clone()).await.unwrap();
+    let expected_output: Vec<_> = (0..MAX_SIZE).map(|i| i as u8).collect();
+    assert_eq!(output, expected_output);
+
+    Arc::get_mut(&mut tx_sender.0)
+        .unwrap()
+        .sender_config
+        .response_size_limit = Some(ResponseSizeLimit {
+        max_size: MAX_SIZE,
+        policy: OversizedResponsePolicy::Error,
+    });
+    let err = tx_sender
+        .eth_call(block_args, tx.clone())
+        .await
+        .unwrap_err();
+    assert_matches!(
+        err,
+        SubmitTxError::ResponseTooLarge(RESPONSE_SIZE, MAX_SIZE)
+    );
+
+    // Responses within the limit are not affected.
+    Arc::get_mut(&mut tx_sender.0)
+        .unwrap()
+        .sender_config
+        .response_size_limit = Some(ResponseSizeLimit {
+        max_size: RESPONSE_SIZE,
+        policy: OversizedResponsePolicy::Error,
+    });
+    let output = tx_sender.eth_call(block_args, tx).await.unwrap();
+    assert_eq!(output.len(), RESPONSE_SIZE);
+}
+
+#[tokio::test]
+async fn too_expensive_call_is_rejected_before_acquiring_permit() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    let block_args = BlockArgs::pending(&mut storage).await.unwrap();
+    drop(storage);
+
+    let tx_executor = MockTransactionExecutor::default().into();
+    let (mut tx_sender, vm_barrier) =
+        create_test_tx_sender(pool, L2ChainId::default(), tx_executor).await;
+    Arc::get_mut(&mut tx_sender.0)
+        .unwrap()
+        .sender_config
+        .max_call_cost = Some(10_000);
+    // Close the limiter, so that any attempt to acquire a permit fails.
+    vm_barrier.close();
+
+    let mut tx = create_l2_transaction(1, 1);
+    tx.execute.contract_address = L2_ETH_TOKEN_ADDRESS;
+    tx.common_data.fee.gas_limit = U256::from(1_000_000);
+    let err = tx_sender.eth_call(block_args, tx).await.unwrap_err();
+    assert_matches!(err, SubmitTxError::CallTooExpensive(1_000_000, 10_000));
+
+    // Calls without a declared gas limit are executed with the maximum limit, so they are estimated accordingly.
+    let mut tx = create_l2_transaction(1, 1);
+    tx.execute.contract_address = L2_ETH_TOKEN_ADDRESS;
+    tx.common_data.fee.gas_limit = U256::zero();
+    let err = tx_sender.eth_call(block_args, tx).await.unwrap_err();
+    let expected_estimate = u64::from(ETH_CALL_GAS_LIMIT);
+    assert_matches!(
+        err,
+        SubmitTxError::CallTooExpensive(estimate, 10_000) if estimate == expected_estimate
+    );
+
+    let mut tx = create_l2_transaction(1, 1);
+    tx.execute.contract_address = L2_ETH_TOKEN_ADDRESS;
+    let err = tx_sender.eth_call(block_args, tx).await.unwrap_err();
+    assert_matches!(err, SubmitTxError::ServerShuttingDown);
+
+    // Calls to addresses without code are admitted regardless of the declared gas limit.
+    let mut tx = create_l2_transaction(1, 1);
+    tx.common_data.fee.gas_limit = U256::zero();
+    let err = tx_sender.eth_call(block_args, tx).await.unwrap_err();
+    assert_matches!(err, SubmitTxError::ServerShuttingDown);
+}
+
+#[tokio::test]
+async fn admitted_call_is_executed_with_estimated_gas_limit() {
+    const DECLARED_GAS_LIMIT: u64 = 5_000;
+
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let mut storage = pool.connection().await.unwrap();
+    insert_genesis_batch(&mut storage, &GenesisParams::mock())
+        .await
+        .unwrap();
+    let block_args = BlockArgs::pending(&mut storage).await.unwrap();
+    drop(storage);
+
+    let mut tx_executor = MockTransactionExecutor::default();
+    tx_executor.set_call_responses(|tx, _| ExecutionResult::Success {
+        output: tx.gas_limit().as_u64().to_be_bytes().to_vec(),
+    });
+    let (mut tx_sender, _vm_barrier) =
+        create_test_tx_sender(pool, L2ChainId::default(), tx_executor.into()).await;
+
+    let mut tx = create_l2_transaction(1, 1);
+    tx.execute.contract_address = L2_ETH_TOKEN_ADDRESS;
+    tx.common_data.fee.gas_limit = DECLARED_GAS_LIMIT.into();
+    // Without a cost ceiling, the declared gas limit is ignored.
+    let output = tx_sender.eth_call(block_args, tx.clone()).await.unwrap();
+    assert_eq!(output, u64::from(ETH_CALL_GAS_LIMIT).to_be_bytes());
+
+    Arc::get_mut(&mut tx_sender.0)
+        .unwrap()
+        .sender_config
+        .max_call_cost = Some(10_000);
+    let output = tx_sender.eth_call(block_args, tx).await.unwrap();
+    assert_eq!(output, DECLARED_GAS_LIMIT.to_be_bytes());
+
+    // Calls to addresses without code are not limited.
+    let mut tx = create_l2_transaction(1, 1);
+    tx.common_data.fee.gas_limit = DECLARED_GAS_LIMIT.into();
+    let output = tx_sender.eth_call(block_args, tx).await.unwrap();
+    assert_eq!(output, u64::from(ETH_CALL_GAS_LIMIT).to_be_bytes());
+}
+
+#[tokio::test]
+async fn whitelisted_tokens_for_aa_are_hot_reloaded() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let tx_executor = MockTransactionExecutor::default().into();
+    let (tx_sender, _) = create_test_tx_sender(pool, L2ChainId::default(), tx_executor).await;
+
+    let shared_args = tx_sender.shared_args().await.unwrap();
+    let cloned_args = shared_args.clone();
+    let tx = create_l2_transaction(10, 100);
+    let trusted_tokens = shared_args
+        .aa_token_policy
+        .trusted_tokens(&tx, vec![])
+        .await;
+    assert!(trusted_tokens.is_empty(), "{trusted_tokens:?}");
+
+    let token = Address::repeat_byte(0x42);
+    // Emulate the whitelist being updated via the shared cache, e.g. by the external node fetching it from the main node.
+    *tx_sender.0.whitelisted_tokens_for_aa_cache.write().await = vec![token].into();
+    for args in [&shared_args, &cloned_args] {
+        assert_eq!(
+            args.aa_token_policy.trusted_tokens(&tx, vec![]).await,
+            HashSet::from([token])
+        );
+    }
+    assert_eq!(
+        tx_sender.read_whitelisted_tokens_for_aa_cache().await,
+        [token]
+    );
+}
+
+#[tokio::test]
+async fn aa_token_policy_is_configured() {
+    let pool = ConnectionPool::<Core>::test_pool().await;
+    let tx_executor = MockTransactionExecutor::default().into();
+    let (mut tx_sender, _) = create_test_tx_sender(pool, L2ChainId::default(), tx_executor).await;
+    assert_matches!(tx_sender.aa_token_policy(), AaTokenPolicy::Whitelist(_));
+
+    Arc::get_mut(&mut tx_sender.0)
+        .unwrap()
+        .sender_config
+        .all_tokens_allowed_for_aa = true;
+    assert_matches!(tx_sender.aa_token_policy(), AaTokenPolicy::AllowAll);
+}
@@ -2,6 +2,7 @@
 //! Consists mostly of boilerplate code implementing the `jsonrpsee` server traits for the corresponding
 //! namespace structures defined in `zksync_core`.
 
+use serde_json::json;
 use zksync_web3_decl::{
     error::Web3Error,
     jsonrpsee::types::{error::ErrorCode, ErrorObjectOwned},
@@ -11,7 +12,7 @@ pub(crate) use self::{
     metadata::{MethodMetadata, MethodTracer},
     middleware::{LimitMiddleware, MetadataMiddleware, ShutdownMiddleware, TrafficTracker},
 };
-use crate::api_server::tx_sender::SubmitTxError;
+use crate::api_server::{execution_sandbox::ValidationError, tx_sender::SubmitTxError};
 
 mod metadata;
 mod middleware;
@@ -24,8 +25,11 @@ impl MethodTracer {
         self.observe_error(&err);
 
         let data = match &err {
-            Web3Error::SubmitTransactionError(_, data) => Some(format!("0x{}", hex::encode(data))),
-            Web3Error::ProxyError(_) => Some("0x".to_owned()),
+            Web3Error::SubmitTransactionError(_, data) => {
+                Some(json!(format!("0x{}", hex::encode(data))))
+            }
+            Web3Error::TransactionValidationError { data, .. } => Some(data.clone()),
+            Web3Error::ProxyError(_) => Some(json!("0x")),
             _ => None,
         };
         let code = match err {
@@ -41,13 +45,15 @@ impl MethodTracer {
             Web3Error::SubmitTransactionError(_, _)
             | Web3Error::SerializationError(_)
             | Web3Error::ProxyError(_) => 3,
+            Web3Error::TransactionValidationError { code, .. } => code,
             Web3Error::TreeApiUnavailable => 6,
         };
         let message = match err {
             // Do not expose internal error details to the client.
             Web3Error::InternalError(_) => "Internal error".to_owned(),
             Web3Error::ProxyError(err) => err.as_ref().to_string(),
-            Web3Error::SubmitTransactionError(message, _) => message,
+            Web3Error::SubmitTransactionError(message, _)
+            | Web3Error::TransactionValidationError { message, .. } => message,
             _ => err.to_string(),
         };
 
@@ -60,7 +66,93 @@ impl From<SubmitTxError> for Web3Error {
         match err {
             SubmitTxError::Internal(err) => Self::InternalError(err),
             SubmitTxError::ProxyError(err) => Self::ProxyError(err),
+            SubmitTxError::ValidationRuleViolated(ref validation_err) => {
+                let (code, data) = validation_error_code_and_data(validation_err);
+                Self::TransactionValidationError {
+                    message: err.to_string(),
+                    code,
+                    data,
+                }
+            }
             _ => Self::SubmitTransactionError(err.to_string(), err.data()),
         }
     }
 }
+
+/// Maps a validation error to the JSON-RPC error code and machine-readable data describing its cause.
+/// Violated validation rules use codes from [EIP-1474](https://eips.ethereum.org/EIPS/eip-1474).
+fn validation_error_code_and_data(err: &ValidationError) -> (i32, serde_json::Value) {
+    /// EIP-1474 code for rejected transactions.
+    const TRANSACTION_REJECTED: i32 = -32003;
+    /// EIP-1474 code for exceeded limits.
+    const LIMIT_EXCEEDED: i32 = -32005;
+
+    match err {
+        // Uses the same code as other transaction execution errors.
+        ValidationError::FailedTx(reason) => (
+            3,
+            json!({ "kind": "failedTx", "reason": reason.to_string() }),
+        ),
+        ValidationError::TouchedUnallowedStorageSlot { address, key } => (
+            TRANSACTION_REJECTED,
+            json!({ "kind": "touchedUnallowedStorageSlot", "address": address, "key": key }),
+        ),
+        ValidationError::CalledContractWithNoCode { address } => (
+            TRANSACTION_REJECTED,
+            json!({ "kind": "calledContractWithNoCode", "address": address }),
+        ),
+        ValidationError::TouchedUnallowedContext => (
+            TRANSACTION_REJECTED,
+            json!({ "kind": "touchedUnallowedContext" }),
+        ),
+        ValidationError::ValidationGasLimitExceeded { limit } => (
+            LIMIT_EXCEEDED,
+            json!({ "kind": "validationGasLimitExceeded", "limit": limit }),
+        ),
+        ValidationError::Internal(_) => (ErrorCode::InternalError.code(), serde_json::Value::Null),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use multivm::interface::Halt;
+    use zksync_types::{Address, U256};
+
+    use super::*;
+
+    fn map_validation_error(err: ValidationError) -> ErrorObjectOwned {
+        let err = Web3Error::from(SubmitTxError::from(err));
+        MethodTracer::default().map_err(err)
+    }
+
+    #[test]
+    fn mapping_validation_errors() {
+        let err = map_validation_error(ValidationError::ValidationGasLimitExceeded { limit: 500 });
+        assert_eq!(err.code(), -32005);
+        assert_eq!(
+            err.message(),
+            "failed to validate the transaction. reason: Violated validation rules: \
+             Took too many computational gas, allowed limit: 500"
+        );
+        let data: serde_json::Value = serde_json::from_str(err.data().unwrap().get()).unwrap();
+        assert_eq!(
+            data,
+            json!({ "kind": "validationGasLimitExceeded", "limit": 500 })
+        );
+
+        let err = map_validation_error(ValidationError::TouchedUnallowedStorageSlot {
+            address: Address::repeat_byte(1),
+            key: U256::from(2),
+        });
+        assert_eq!(err.code(), -32003);
+        let data: serde_json::Value = serde_json::from_str(err.data().unwrap().get()).unwrap();
+        assert_eq!(data["kind"], "touchedUnallowedStorageSlot");
+        assert_eq!(data["address"], json!(Address::repeat_byte(1)));
+        assert_eq!(data["key"], "0x2");
+
+        let err = map_validation_error(ValidationError::FailedTx(Halt::ValidationOutOfGas));
+        assert_eq!(err.code(), 3);
+        let data: serde_json::Value = serde_json::from_str(err.data().unwrap().get()).unwrap();
+        assert_eq!(data["kind"], "failedTx");
+    }
+}
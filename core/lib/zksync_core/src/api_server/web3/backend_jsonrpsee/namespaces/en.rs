@@ -19,6 +19,17 @@ impl EnNamespaceServer for EnNamespace {
             .map_err(|err| self.current_method().map_err(err))
     }
 
+    async fn sync_l2_block_range(
+        &self,
+        from: MiniblockNumber,
+        to: MiniblockNumber,
+        include_transactions: bool,
+    ) -> RpcResult<Vec<en::SyncBlock>> {
+        self.sync_l2_block_range_impl(from, to, include_transactions)
+            .await
+            .map_err(|err| self.current_method().map_err(err))
+    }
+
     async fn consensus_genesis(&self) -> RpcResult<Option<en::ConsensusGenesis>> {
         self.consensus_genesis_impl()
             .await
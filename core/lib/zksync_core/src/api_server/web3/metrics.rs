@@ -112,7 +112,8 @@ impl Web3ErrorKind {
         match err {
             Web3Error::NoBlock => Self::NoBlock,
             Web3Error::PrunedBlock(_) | Web3Error::PrunedL1Batch(_) => Self::Pruned,
-            Web3Error::SubmitTransactionError(..) => Self::SubmitTransaction,
+            Web3Error::SubmitTransactionError(..)
+            | Web3Error::TransactionValidationError { .. } => Self::SubmitTransaction,
             Web3Error::ProxyError(_) => Self::Proxy,
             Web3Error::SerializationError(_) => Self::TransactionSerialization,
             Web3Error::TooManyTopics => Self::TooManyTopics,
@@ -11,7 +11,7 @@ use tokio::{
 use tower_http::{cors::CorsLayer, metrics::InFlightRequestsLayer};
 use zksync_dal::{ConnectionPool, Core};
 use zksync_health_check::{HealthStatus, HealthUpdater, ReactiveHealthCheck};
-use zksync_types::MiniblockNumber;
+use zksync_types::{MiniblockNumber, StorageKey};
 use zksync_web3_decl::{
     jsonrpsee::{
         server::{BatchRequestConfig, RpcServiceBuilder, ServerBuilder},
@@ -39,7 +39,7 @@ use self::{
 };
 use crate::{
     api_server::{
-        execution_sandbox::{BlockStartInfo, VmConcurrencyBarrier},
+        execution_sandbox::{BlockStartInfo, BlockStartInfoConfig, VmConcurrencyBarrier},
         tree::TreeApiClient,
         tx_sender::TxSender,
     },
@@ -130,6 +130,8 @@ struct OptionalApiParams {
     tree_api: Option<Arc<dyn TreeApiClient>>,
     mempool_cache: Option<MempoolCache>,
     pub_sub_events_sender: Option<mpsc::UnboundedSender<PubSubEvent>>,
+    block_start_info_config: BlockStartInfoConfig,
+    cache_prime_list: Vec<StorageKey>,
 }
 
 /// Structure capable of spawning a configured Web3 API server along with all the required
@@ -260,6 +262,18 @@ impl ApiBuilder {
         self
     }
 
+    /// Configures caching of pruning info. By default, pruning info is cached for about 20 seconds.
+    pub fn with_block_start_info_config(mut self, config: BlockStartInfoConfig) -> Self {
+        self.optional.block_start_info_config = config;
+        self
+    }
+
+    /// Sets storage keys (e.g., slots of hot contracts) to preload into the VM storage values cache on server start.
+    pub fn with_cache_prime_list(mut self, keys: Vec<StorageKey>) -> Self {
+        self.optional.cache_prime_list = keys;
+        self
+    }
+
     pub fn with_mempool_cache(mut self, cache: MempoolCache) -> Self {
         self.optional.mempool_cache = Some(cache);
         self
@@ -317,7 +331,15 @@ impl ApiServer {
         last_sealed_miniblock: SealedMiniblockNumber,
     ) -> anyhow::Result<RpcState> {
         let mut storage = self.updaters_pool.connection_tagged("api").await?;
-        let start_info = BlockStartInfo::new(&mut storage).await?;
+        let start_info =
+            BlockStartInfo::new(&mut storage, self.optional.block_start_info_config).await?;
+        if !self.optional.cache_prime_list.is_empty() {
+            self.tx_sender
+                .storage_caches()
+                .prime(&self.optional.cache_prime_list, &mut storage)
+                .await
+                .context("failed priming VM storage values cache")?;
+        }
         drop(storage);
 
         // Disable filter API for HTTP endpoints, WS endpoints are unaffected by the `filters_disabled` flag
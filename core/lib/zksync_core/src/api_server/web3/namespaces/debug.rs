@@ -1,8 +1,5 @@
-use std::sync::Arc;
-
 use anyhow::Context as _;
-use multivm::{interface::ExecutionResult, vm_latest::constants::BATCH_COMPUTATIONAL_GAS_LIMIT};
-use once_cell::sync::OnceCell;
+use multivm::vm_latest::constants::BATCH_COMPUTATIONAL_GAS_LIMIT;
 use zksync_dal::{CoreDal, DalError};
 use zksync_system_constants::MAX_ENCODED_TX_SIZE;
 use zksync_types::{
@@ -11,14 +8,13 @@ use zksync_types::{
     fee_model::BatchFeeInput,
     l2::L2Tx,
     transaction_request::CallRequest,
-    vm_trace::Call,
-    AccountTreeId, H256,
+    H256,
 };
 use zksync_web3_decl::error::Web3Error;
 
 use crate::api_server::{
-    execution_sandbox::{ApiTracer, TxSharedArgs},
-    tx_sender::{ApiContracts, TxSenderConfig},
+    execution_sandbox::{TxSharedArgs, VmPriority},
+    tx_sender::{ApiContracts, SubmitTxError, TxSenderConfig},
     web3::{backend_jsonrpsee::MethodTracer, state::RpcState},
 };
 
@@ -135,9 +131,9 @@ impl DebugNamespace {
         let block_id = block_id.unwrap_or(BlockId::Number(BlockNumber::Pending));
         self.current_method().set_block_id(block_id);
 
-        let only_top_call = options
-            .map(|options| options.tracer_config.only_top_call)
-            .unwrap_or(false);
+        let tracer_config = options
+            .map(|options| options.tracer_config)
+            .unwrap_or_default();
 
         let mut connection = self.state.acquire_connection().await?;
         let block_args = self
@@ -153,78 +149,41 @@ impl DebugNamespace {
         );
         let tx = L2Tx::from_request(request.into(), MAX_ENCODED_TX_SIZE)?;
 
-        let shared_args = self.shared_args().await;
+        let shared_args = self.shared_args().await?;
         let vm_permit = self
             .state
             .tx_sender
             .vm_concurrency_limiter()
-            .acquire()
+            .acquire(VmPriority::Background)
             .await;
-        let vm_permit = vm_permit.context("cannot acquire VM permit")?;
-
-        // We don't need properly trace if we only need top call
-        let call_tracer_result = Arc::new(OnceCell::default());
-        let custom_tracers = if only_top_call {
-            vec![]
-        } else {
-            vec![ApiTracer::CallTracer(call_tracer_result.clone())]
-        };
+        // Map the error to `SubmitTxError`, so that shutdown is reported the same way as for `eth_call`.
+        let vm_permit = vm_permit.map_err(SubmitTxError::from)?;
 
         let executor = &self.state.tx_sender.0.executor;
-        let result = executor
-            .execute_tx_eth_call(
+        let call = executor
+            .trace_call(
                 vm_permit,
                 shared_args,
                 self.state.connection_pool.clone(),
-                tx.clone(),
+                tx,
                 block_args,
                 self.sender_config().vm_execution_cache_misses_limit,
-                custom_tracers,
+                self.sender_config().response_size_limit,
+                tracer_config,
             )
-            .await?;
-
-        let (output, revert_reason) = match result.result {
-            ExecutionResult::Success { output, .. } => (output, None),
-            ExecutionResult::Revert { output } => (vec![], Some(output.to_string())),
-            ExecutionResult::Halt { reason } => {
-                return Err(Web3Error::SubmitTransactionError(
-                    reason.to_string(),
-                    vec![],
-                ))
-            }
-        };
-
-        // We had only one copy of Arc this arc is already dropped it's safe to unwrap
-        let trace = Arc::try_unwrap(call_tracer_result)
-            .unwrap()
-            .take()
-            .unwrap_or_default();
-        let call = Call::new_high_level(
-            tx.common_data.fee.gas_limit.as_u64(),
-            result.statistics.gas_used,
-            tx.execute.value,
-            tx.execute.calldata,
-            output,
-            revert_reason,
-            trace,
-        );
+            .await
+            .map_err(SubmitTxError::from)?;
         Ok(call.into())
     }
 
-    async fn shared_args(&self) -> TxSharedArgs {
+    async fn shared_args(&self) -> anyhow::Result<TxSharedArgs> {
         let sender_config = self.sender_config();
-        TxSharedArgs {
-            operator_account: AccountTreeId::default(),
-            fee_input: self.batch_fee_input,
-            base_system_contracts: self.api_contracts.eth_call.clone(),
-            caches: self.state.tx_sender.storage_caches().clone(),
-            validation_computational_gas_limit: BATCH_COMPUTATIONAL_GAS_LIMIT,
-            chain_id: sender_config.chain_id,
-            whitelisted_tokens_for_aa: self
-                .state
-                .tx_sender
-                .read_whitelisted_tokens_for_aa_cache()
-                .await,
-        }
+        TxSharedArgs::builder(self.batch_fee_input)
+            .with_caches(self.state.tx_sender.storage_caches().clone())
+            .with_validation_computational_gas_limit(BATCH_COMPUTATIONAL_GAS_LIMIT)
+            .with_chain_id(sender_config.chain_id)
+            .with_aa_token_policy(self.state.tx_sender.aa_token_policy())
+            .build(self.api_contracts.eth_call.clone())
+            .context("invalid shared args")
     }
 }
@@ -6,6 +6,9 @@ use zksync_web3_decl::error::Web3Error;
 
 use crate::api_server::web3::{backend_jsonrpsee::MethodTracer, state::RpcState};
 
+/// Maximum number of blocks returned by a single `en_syncL2BlockRange` call.
+const MAX_SYNC_BLOCK_RANGE: u32 = 100;
+
 /// Namespace for External Node unique methods.
 /// Main use case for it is the EN synchronization.
 #[derive(Debug)]
@@ -51,6 +54,30 @@ impl EnNamespace {
             .map_err(DalError::generalize)?)
     }
 
+    #[tracing::instrument(skip(self))]
+    pub async fn sync_l2_block_range_impl(
+        &self,
+        from: MiniblockNumber,
+        to: MiniblockNumber,
+        include_transactions: bool,
+    ) -> Result<Vec<en::SyncBlock>, Web3Error> {
+        let to = to.0.min(from.0.saturating_add(MAX_SYNC_BLOCK_RANGE - 1));
+        let mut storage = self.state.acquire_connection().await?;
+        let mut blocks = vec![];
+        for number in from.0..=to {
+            let block = storage
+                .sync_dal()
+                .sync_block(MiniblockNumber(number), include_transactions)
+                .await
+                .map_err(DalError::generalize)?;
+            let Some(block) = block else {
+                break;
+            };
+            blocks.push(block);
+        }
+        Ok(blocks)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn sync_tokens_impl(
         &self,
@@ -172,6 +172,14 @@ impl EthNamespace {
     pub async fn get_logs_impl(&self, mut filter: Filter) -> Result<Vec<Log>, Web3Error> {
         self.state.resolve_filter_block_hash(&mut filter).await?;
         let (from_block, to_block) = self.state.resolve_filter_block_range(&filter).await?;
+        // Block tags (e.g., `latest`) are never considered pruned, so only explicit block numbers are checked.
+        if let Some(BlockNumber::Number(_)) = filter.from_block {
+            let mut storage = self.state.acquire_connection().await?;
+            self.state
+                .start_info
+                .ensure_range_not_pruned(from_block, to_block, &mut storage)
+                .await?;
+        }
 
         filter.to_block = Some(BlockNumber::Number(to_block.0.into()));
         let changes = self
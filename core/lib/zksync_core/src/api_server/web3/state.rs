@@ -65,7 +65,9 @@ impl From<L1BatchNumber> for PruneQuery {
 impl From<BlockArgsError> for Web3Error {
     fn from(value: BlockArgsError) -> Self {
         match value {
-            BlockArgsError::Pruned(miniblock) => Web3Error::PrunedBlock(miniblock),
+            BlockArgsError::Pruned {
+                first_miniblock, ..
+            } => Web3Error::PrunedBlock(first_miniblock),
             BlockArgsError::Missing => Web3Error::NoBlock,
             BlockArgsError::Database(error) => Web3Error::InternalError(error),
         }
@@ -332,11 +334,7 @@ impl RpcState {
     ) -> Result<BlockArgs, Web3Error> {
         BlockArgs::new(connection, block, &self.start_info)
             .await
-            .map_err(|err| match err {
-                BlockArgsError::Pruned(number) => Web3Error::PrunedBlock(number),
-                BlockArgsError::Missing => Web3Error::NoBlock,
-                BlockArgsError::Database(err) => Web3Error::InternalError(err),
-            })
+            .map_err(Web3Error::from)
     }
 
     pub async fn resolve_filter_block_number(
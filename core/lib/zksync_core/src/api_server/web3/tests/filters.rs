@@ -267,6 +267,50 @@ async fn log_filter_changes_with_block_boundaries() {
     test_http_server(LogFilterChangesWithBlockBoundariesTest).await;
 }
 
+#[derive(Debug)]
+struct GetLogsAfterSnapshotRecoveryTest;
+
+#[async_trait]
+impl HttpTest for GetLogsAfterSnapshotRecoveryTest {
+    fn storage_initialization(&self) -> StorageInitialization {
+        StorageInitialization::empty_recovery()
+    }
+
+    async fn test(&self, client: &HttpClient, pool: &ConnectionPool<Core>) -> anyhow::Result<()> {
+        let first_local_miniblock = StorageInitialization::SNAPSHOT_RECOVERY_BLOCK + 1;
+        let mut storage = pool.connection().await?;
+        let (_, events) = store_events(&mut storage, first_local_miniblock.0 + 1, 0).await?;
+        drop(storage);
+        let events: Vec<_> = events.iter().collect();
+
+        let pruned_block_numbers = [0, StorageInitialization::SNAPSHOT_RECOVERY_BLOCK.0];
+        for number in pruned_block_numbers {
+            let filter = Filter {
+                from_block: Some(api::BlockNumber::Number(number.into())),
+                ..Filter::default()
+            };
+            let error = client.get_logs(filter).await.unwrap_err();
+            assert_pruned_block_error(&error, first_local_miniblock);
+        }
+
+        let filter = Filter {
+            from_block: Some(api::BlockNumber::Number(first_local_miniblock.0.into())),
+            ..Filter::default()
+        };
+        let logs = client.get_logs(filter).await?;
+        assert_logs_match(&logs, &events);
+        // Block tags are never considered pruned.
+        let logs = client.get_logs(Filter::default()).await?;
+        assert_logs_match(&logs, &events);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn getting_logs_after_snapshot_recovery() {
+    test_http_server(GetLogsAfterSnapshotRecoveryTest).await;
+}
+
 fn assert_not_implemented<T: Debug>(result: Result<T, Error>) {
     assert_matches!(result, Err(Error::Call(e)) => {
         assert_eq!(e.code(), ErrorCode::MethodNotFound.code());
@@ -9,7 +9,11 @@ use zksync_config::configs::consensus::{ConsensusConfig, ConsensusSecrets};
 use zksync_dal::{ConnectionPool, Core};
 use zksync_web3_decl::client::BoxedL2Client;
 
-use super::{config, fetcher::Fetcher, storage::Store};
+use super::{
+    config,
+    fetcher::{Fetcher, FetcherConfig, FetcherHealth, P2PStats},
+    storage::Store,
+};
 use crate::sync_layer::{sync_action::ActionQueueSender, SyncState};
 
 /// Runs the consensus task in the main node mode.
@@ -30,19 +34,26 @@ pub async fn run_main_node(
 }
 
 /// Runs the consensus in the fetcher mode (e.g. for the external node needs).
-/// The fetcher implementation may either be p2p or centralized.
+/// The fetcher implementation may either be p2p or centralized. Fetcher progress is reported to `health`,
+/// which should be registered as a health check by the caller.
+#[allow(clippy::too_many_arguments)]
 pub async fn run_fetcher(
     ctx: &ctx::Ctx,
     cfg: Option<(ConsensusConfig, ConsensusSecrets)>,
     pool: ConnectionPool<Core>,
     sync_state: SyncState,
+    health: FetcherHealth,
+    fetcher_config: FetcherConfig,
     main_node_client: BoxedL2Client,
     actions: ActionQueueSender,
 ) -> anyhow::Result<()> {
     let fetcher = Fetcher {
         store: Store(pool),
         sync_state: sync_state.clone(),
+        health,
+        p2p_stats: P2PStats::default(),
         client: main_node_client,
+        config: fetcher_config,
     };
     let res = match cfg {
         Some((cfg, secrets)) => {
@@ -1,10 +1,29 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
 use anyhow::Context as _;
-use zksync_concurrency::{ctx, error::Wrap as _, scope, time};
+use async_trait::async_trait;
+use rand::Rng;
+use serde::Serialize;
+use zksync_concurrency::{ctx, error::Wrap as _, scope, sync, time};
 use zksync_consensus_executor as executor;
 use zksync_consensus_roles::validator;
-use zksync_types::MiniblockNumber;
-use zksync_web3_decl::client::BoxedL2Client;
+use zksync_health_check::{CheckHealth, Health, HealthStatus};
+use zksync_types::{MiniblockNumber, H256};
+use zksync_utils::time::seconds_since_epoch;
+use zksync_web3_decl::{
+    client::BoxedL2Client,
+    error::EnrichedClientError,
+    jsonrpsee::{core::ClientError, types::error::ErrorCode},
+};
 
+use super::metrics::FETCHER_METRICS;
 use crate::{
     consensus::{storage, Store},
     sync_layer::{
@@ -14,11 +33,485 @@ use crate::{
 
 pub type P2PConfig = executor::Config;
 
+/// Interval between genesis checks performed by the genesis monitor.
+const GENESIS_POLL_INTERVAL: time::Duration = time::Duration::seconds(5);
+/// Number of consecutive malformed genesis responses from the main node after which the genesis monitor fails.
+pub(super) const MAX_MALFORMED_GENESIS_RESPONSES: usize = 3;
+
+/// Checks whether the main node has responded that the called RPC method is not supported.
+fn is_method_not_found(err: &EnrichedClientError) -> bool {
+    matches!(
+        err.as_ref(),
+        ClientError::Call(err) if err.code() == ErrorCode::MethodNotFound.code()
+    )
+}
+
+/// Errors that can occur when fetching genesis from the main node.
+#[derive(Debug, thiserror::Error)]
+pub(super) enum GenesisError {
+    #[error("fetch_consensus_genesis(): {0}")]
+    Rpc(#[from] EnrichedClientError),
+    #[error("main node is not running consensus component")]
+    Missing,
+    #[error("main node returned malformed genesis: {0:#}")]
+    Malformed(anyhow::Error),
+}
+
+/// Raw genesis last returned by the main node, used by the genesis monitor to skip deserialization
+/// when the main node keeps returning the same genesis (i.e., almost always).
+#[derive(Debug, Default)]
+pub(super) struct GenesisCache {
+    /// Raw genesis known to be equal to the monitored one.
+    raw: Option<serde_json::Value>,
+}
+
+impl GenesisCache {
+    /// Checks the `raw` genesis returned by the main node against the `old` one. Returns the new genesis
+    /// if it differs from `old`. The raw genesis is only deserialized if it differs from the cached one.
+    pub(super) fn check(
+        &mut self,
+        raw: serde_json::Value,
+        old: &validator::Genesis,
+    ) -> Result<Option<validator::Genesis>, GenesisError> {
+        if self.raw.as_ref() == Some(&raw) {
+            return Ok(None);
+        }
+
+        let new = deserialize_genesis(&raw)?;
+        if new == *old {
+            self.raw = Some(raw);
+            Ok(None)
+        } else {
+            Ok(Some(new))
+        }
+    }
+}
+
+fn deserialize_genesis(raw: &serde_json::Value) -> Result<validator::Genesis, GenesisError> {
+    zksync_protobuf::serde::deserialize(raw)
+        .context("deserialize(genesis)")
+        .map_err(GenesisError::Malformed)
+}
+
+/// Tunable parameters of the [`Fetcher`].
+#[derive(Debug, Clone)]
+pub struct FetcherConfig {
+    /// Maximum number of blocks past the last applied block that the fetcher is allowed to fetch
+    /// when fetching blocks from the main node json RPC. Smaller values keep the node close to the head
+    /// (i.e., waste less work on reorgs), larger ones maximize throughput. `None` means that the fetcher
+    /// is only limited by the number of concurrent requests.
+    pub read_ahead_window: Option<NonZeroUsize>,
+    /// Maximum allowed difference between the timestamp of a block fetched from the main node json RPC
+    /// and the local wall-clock time. Blocks with timestamps further in the future are rejected. `None` (the default)
+    /// means that the difference is not checked; timestamps are still checked to not go backwards. Since a node
+    /// with a lagging local clock would stop syncing, the check should only be enabled if the clock is synchronized.
+    pub max_timestamp_skew: Option<Duration>,
+    /// Maximum number of blocks fetched concurrently from the main node json RPC. Lower values reduce the load
+    /// on (e.g., rate-limited) main node endpoints, higher ones speed up catching up with the main node.
+    pub concurrency_limit: NonZeroUsize,
+    /// Number of polls after which a block that the main node should have (i.e., the main node head
+    /// is not less than the block number), but still doesn't return, is reported as a possible reorg or gap.
+    pub missing_block_warn_polls: NonZeroUsize,
+    /// Number of blocks requested from the main node in a single `en_syncL2BlockRange` call. Range requests
+    /// reduce the number of round-trips when catching up with the main node, but are performed sequentially.
+    /// `None` means that blocks are fetched one by one. If the main node doesn't support range requests,
+    /// the fetcher falls back to fetching blocks one by one.
+    pub range_size: Option<NonZeroUsize>,
+    /// If applying a fetched block (e.g., sending it to the payload queue) takes longer than this threshold,
+    /// blocks are fetched one at a time until applying becomes fast again. This prevents fetched blocks
+    /// from piling up in memory when the sink lags. `None` (the default) means that fetching is never throttled.
+    pub backpressure_threshold: Option<Duration>,
+}
+
+impl Default for FetcherConfig {
+    fn default() -> Self {
+        Self {
+            read_ahead_window: None,
+            max_timestamp_skew: None,
+            concurrency_limit: NonZeroUsize::new(30).unwrap(),
+            missing_block_warn_polls: NonZeroUsize::new(10).unwrap(),
+            range_size: None,
+            backpressure_threshold: None,
+        }
+    }
+}
+
+/// Errors that can occur when verifying a range of blocks using [`Fetcher::verify_backwards()`].
+#[derive(Debug, thiserror::Error)]
+pub enum VerificationError {
+    #[error("invalid range to verify: miniblock #{to} is above miniblock #{from}")]
+    InvalidRange {
+        from: MiniblockNumber,
+        to: MiniblockNumber,
+    },
+    #[error("miniblock #{0} fetched from the main node has no hash")]
+    MissingHash(MiniblockNumber),
+    #[error(
+        "miniblock #{number} is not linked to its parent: hash computed from the parent is {computed:?}, \
+         while the main node returned {returned:?}"
+    )]
+    BrokenLink {
+        number: MiniblockNumber,
+        computed: H256,
+        returned: H256,
+    },
+    #[error(transparent)]
+    Fetch(#[from] ctx::Error),
+}
+
+/// Error returned by [`Fetcher::fetch_blocks_into()`] if a fetched block is not linked to the block applied
+/// before it, i.e., the main node has reorged blocks. Returned wrapped in [`ctx::Error::Internal`];
+/// the caller can downcast it and roll back blocks starting from `number`.
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "reorg detected at miniblock #{number}: hash computed from the previously applied block is {computed:?}, \
+     while the main node returned {returned:?}"
+)]
+pub struct ReorgDetected {
+    pub number: MiniblockNumber,
+    pub computed: H256,
+    pub returned: H256,
+}
+
+/// Error returned by [`Fetcher::fetch_blocks_into()`] if a fetched block doesn't have the expected number, i.e.
+/// blocks were received out of order. This indicates a bug in the fetcher or a main node returning a wrong block;
+/// the block is not applied. Returned wrapped in [`ctx::Error::Internal`].
+#[derive(Debug, thiserror::Error)]
+#[error("received miniblock #{got} out of order; expected miniblock #{expected}")]
+pub struct BlockOutOfOrder {
+    pub expected: MiniblockNumber,
+    pub got: MiniblockNumber,
+}
+
+/// Destination of blocks fetched by [`Fetcher::fetch_blocks_into()`], e.g. the state keeper action queue
+/// or a mirror.
+#[async_trait]
+pub(crate) trait BlockSink: Send {
+    /// Returns the number of the next block expected by this sink.
+    fn next(&self) -> validator::BlockNumber;
+
+    /// Returns the hash of the block preceding [`Self::next()`], if known. The first applied block is checked
+    /// to link to this hash; by default, the hash is unknown and the first block is not checked.
+    fn prev_hash(&self) -> Option<H256> {
+        None
+    }
+
+    /// Checks that the timestamp of the block is plausible. By default, timestamps are not checked.
+    fn validate_timestamp(
+        &self,
+        _block: &FetchedBlock,
+        _now: u64,
+        _max_skew: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Applies the next block. Blocks are supplied in order, starting from [`Self::next()`].
+    async fn apply(&mut self, block: FetchedBlock) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl BlockSink for storage::PayloadQueue {
+    fn next(&self) -> validator::BlockNumber {
+        storage::PayloadQueue::next(self)
+    }
+
+    fn prev_hash(&self) -> Option<H256> {
+        Some(storage::PayloadQueue::prev_hash(self))
+    }
+
+    fn validate_timestamp(
+        &self,
+        block: &FetchedBlock,
+        now: u64,
+        max_skew: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        storage::PayloadQueue::validate_timestamp(self, block, now, max_skew)
+    }
+
+    async fn apply(&mut self, block: FetchedBlock) -> anyhow::Result<()> {
+        self.send(block).await
+    }
+}
+
+#[derive(Debug)]
+struct FetcherProgress {
+    main_node_block: Option<MiniblockNumber>,
+    last_fetched_block: Option<MiniblockNumber>,
+    /// Time when the last block was fetched, or when the fetcher started lagging behind the main node
+    /// after being caught up with it (whichever is later). The fetcher isn't expected to make progress
+    /// while caught up, so the stall is only measured from this point.
+    last_progress_at: Instant,
+}
+
+impl FetcherProgress {
+    fn is_lagging(&self) -> bool {
+        let Some(main_node_block) = self.main_node_block else {
+            return false;
+        };
+        self.last_fetched_block
+            .map_or(true, |fetched| fetched < main_node_block)
+    }
+}
+
+/// Shared handle tracking progress of a [`Fetcher`]. Can be used as a health check: the fetcher is considered
+/// unhealthy if the main node has blocks that the fetcher didn't fetch within the configured window.
+#[derive(Debug, Clone)]
+pub struct FetcherHealth {
+    progress: Arc<Mutex<FetcherProgress>>,
+    max_stall: Duration,
+}
+
+impl Default for FetcherHealth {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60))
+    }
+}
+
+impl FetcherHealth {
+    /// Creates a handle that considers the fetcher stalled if it doesn't fetch a new block for `max_stall`
+    /// while lagging behind the main node.
+    pub fn new(max_stall: Duration) -> Self {
+        Self {
+            progress: Arc::new(Mutex::new(FetcherProgress {
+                main_node_block: None,
+                last_fetched_block: None,
+                last_progress_at: Instant::now(),
+            })),
+            max_stall,
+        }
+    }
+
+    // The progress is plain data, so it's safe to recover from lock poisoning.
+    fn lock(&self) -> std::sync::MutexGuard<'_, FetcherProgress> {
+        self.progress.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn set_main_node_block(&self, number: MiniblockNumber, now: Instant) {
+        let mut progress = self.lock();
+        let was_lagging = progress.is_lagging();
+        progress.main_node_block = Some(number);
+        if !was_lagging && progress.is_lagging() {
+            progress.last_progress_at = now;
+        }
+    }
+
+    pub(crate) fn set_fetched_block(&self, number: MiniblockNumber, now: Instant) {
+        let mut progress = self.lock();
+        progress.last_fetched_block = Some(number);
+        progress.last_progress_at = now;
+    }
+
+    pub(crate) fn health_at(&self, now: Instant) -> Health {
+        #[derive(Debug, Serialize)]
+        struct FetcherHealthDetails {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            main_node_block: Option<MiniblockNumber>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            last_fetched_block: Option<MiniblockNumber>,
+            secs_since_last_progress: u64,
+            stalled: bool,
+        }
+
+        let progress = self.lock();
+        let Some(main_node_block) = progress.main_node_block else {
+            return HealthStatus::NotReady.into(); // the main node head isn't known yet
+        };
+        let since_last_progress = now.saturating_duration_since(progress.last_progress_at);
+        let stalled = progress.is_lagging() && since_last_progress > self.max_stall;
+        let status = if stalled {
+            HealthStatus::NotReady
+        } else {
+            HealthStatus::Ready
+        };
+        Health::from(status).with_details(FetcherHealthDetails {
+            main_node_block: Some(main_node_block),
+            last_fetched_block: progress.last_fetched_block,
+            secs_since_last_progress: since_last_progress.as_secs(),
+            stalled,
+        })
+    }
+}
+
+#[async_trait]
+impl CheckHealth for FetcherHealth {
+    fn name(&self) -> &'static str {
+        "consensus_fetcher"
+    }
+
+    async fn check_health(&self) -> Health {
+        self.health_at(Instant::now())
+    }
+}
+
+#[derive(Debug, Default)]
+struct P2PStatsInner {
+    gossip_blocks: u64,
+    last_gossip_block: Option<MiniblockNumber>,
+}
+
+/// Shared handle with statistics of blocks received by [`Fetcher::run_p2p()`] via the gossip network
+/// (as opposed to blocks before the consensus genesis, which are fetched from the main node json RPC).
+/// The stats are aggregated over all peers since the block store doesn't know which peer a block came from.
+#[derive(Debug, Clone, Default)]
+pub struct P2PStats(Arc<Mutex<P2PStatsInner>>);
+
+impl P2PStats {
+    // The stats are plain data, so it's safe to recover from lock poisoning.
+    fn lock(&self) -> std::sync::MutexGuard<'_, P2PStatsInner> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn observe_gossip_block(&self, number: MiniblockNumber) {
+        let mut stats = self.lock();
+        stats.gossip_blocks += 1;
+        stats.last_gossip_block = Some(number);
+        FETCHER_METRICS.gossip_blocks.inc();
+    }
+
+    /// Returns the number of blocks received via the gossip network.
+    pub fn gossip_blocks(&self) -> u64 {
+        self.lock().gossip_blocks
+    }
+
+    /// Returns the number of the last block received via the gossip network.
+    pub fn last_gossip_block(&self) -> Option<MiniblockNumber> {
+        self.lock().last_gossip_block
+    }
+}
+
 /// Miniblock fetcher.
 pub struct Fetcher {
     pub store: Store,
     pub sync_state: SyncState,
+    pub health: FetcherHealth,
+    pub p2p_stats: P2PStats,
     pub client: BoxedL2Client,
+    pub config: FetcherConfig,
+}
+
+/// Bounds how far past the last applied block the fetcher may fetch blocks.
+#[derive(Debug)]
+pub(super) struct ReadAheadWindow {
+    /// Number of the next block to be applied.
+    applied: sync::watch::Sender<validator::BlockNumber>,
+    size: Option<NonZeroUsize>,
+}
+
+impl ReadAheadWindow {
+    pub(super) fn new(next_to_apply: validator::BlockNumber, size: Option<NonZeroUsize>) -> Self {
+        Self {
+            applied: sync::watch::channel(next_to_apply).0,
+            size,
+        }
+    }
+
+    /// Records that all blocks before `next_to_apply` were applied.
+    pub(super) fn set_applied(&self, next_to_apply: validator::BlockNumber) {
+        self.applied.send_replace(next_to_apply);
+    }
+
+    /// Waits until block `n` falls into the window, i.e. it can be fetched.
+    pub(super) async fn wait_for_slot(
+        &self,
+        ctx: &ctx::Ctx,
+        n: validator::BlockNumber,
+    ) -> ctx::OrCanceled<()> {
+        let Some(size) = self.size else {
+            return Ok(());
+        };
+        let size = size.get() as u64;
+        sync::wait_for(ctx, &mut self.applied.subscribe(), |applied| {
+            n.0 < applied.0.saturating_add(size)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Waits until all blocks before `n` are applied.
+    pub(super) async fn wait_for_applied(
+        &self,
+        ctx: &ctx::Ctx,
+        n: validator::BlockNumber,
+    ) -> ctx::OrCanceled<()> {
+        sync::wait_for(ctx, &mut self.applied.subscribe(), |applied| n <= *applied).await?;
+        Ok(())
+    }
+}
+
+/// Backpressure from a [`BlockSink`]. If applying a block takes longer than the threshold, fetching is throttled
+/// until a block is applied faster than the threshold.
+#[derive(Debug)]
+pub(super) struct Backpressure {
+    threshold: Option<Duration>,
+    throttled: AtomicBool,
+}
+
+impl Backpressure {
+    pub(super) fn new(threshold: Option<Duration>) -> Self {
+        Self {
+            threshold,
+            throttled: AtomicBool::new(false),
+        }
+    }
+
+    /// Records the time it took to apply a block.
+    pub(super) fn observe(&self, latency: Duration) {
+        let throttled = self
+            .threshold
+            .map_or(false, |threshold| latency > threshold);
+        let was_throttled = self.throttled.swap(throttled, Ordering::Relaxed);
+        if throttled && !was_throttled {
+            tracing::info!(
+                "Applying a block took {latency:?} (threshold: {:?}); throttling fetching",
+                self.threshold
+            );
+            FETCHER_METRICS.backpressure_throttles.inc();
+        } else if !throttled && was_throttled {
+            tracing::info!("Applying blocks caught up; resuming concurrent fetching");
+        }
+        FETCHER_METRICS.throttled.set(throttled.into());
+    }
+
+    /// Checks whether fetching is throttled, i.e. blocks should be fetched one at a time.
+    pub(super) fn is_throttled(&self) -> bool {
+        self.throttled.load(Ordering::Relaxed)
+    }
+}
+
+/// Capped exponential backoff with jitter used when retrying requests to the main node.
+#[derive(Debug)]
+pub(super) struct Backoff {
+    initial: time::Duration,
+    max: time::Duration,
+    current: time::Duration,
+}
+
+impl Backoff {
+    pub(super) const INITIAL_DELAY: time::Duration = time::Duration::milliseconds(200);
+    pub(super) const MAX_DELAY: time::Duration = time::Duration::seconds(5);
+
+    pub(super) fn new(initial: time::Duration, max: time::Duration) -> Self {
+        Self {
+            initial,
+            max,
+            current: initial,
+        }
+    }
+
+    /// Returns the delay before the next retry and doubles the base delay (up to the cap).
+    /// The returned delay is uniformly distributed in `[base / 2, base]` so that concurrent retries
+    /// don't hit the main node at the same time.
+    pub(super) fn next_delay(&mut self, rng: &mut impl Rng) -> time::Duration {
+        let half = self.current / 2;
+        self.current = (self.current * 2).min(self.max);
+        half + half.mul_f64(rng.gen())
+    }
+
+    /// Resets the base delay to its initial value.
+    pub(super) fn reset(&mut self) {
+        self.current = self.initial;
+    }
 }
 
 impl Fetcher {
@@ -47,30 +540,22 @@ impl Fetcher {
             drop(conn);
 
             // Fetch blocks before the genesis.
-            self.fetch_blocks(ctx, &mut payload_queue, Some(genesis.fork.first_block))
-                .await?;
+            self.fetch_blocks(
+                ctx,
+                &mut payload_queue,
+                Some(genesis.fork.first_block),
+                None,
+            )
+            .await?;
             // Monitor the genesis of the main node.
             // If it changes, it means that a hard fork occurred and we need to reset the consensus state.
-            s.spawn_bg::<()>(async {
-                let old = genesis;
-                loop {
-                    if let Ok(new) = self.fetch_genesis(ctx).await {
-                        if new != old {
-                            return Err(anyhow::format_err!(
-                                "genesis changed: old {old:?}, new {new:?}"
-                            )
-                            .into());
-                        }
-                    }
-                    ctx.sleep(time::Duration::seconds(5)).await?;
-                }
-            });
+            s.spawn_bg(self.monitor_genesis(ctx, genesis));
 
             // Run consensus component.
             let (block_store, runner) = self
                 .store
                 .clone()
-                .into_block_store(ctx, Some(payload_queue))
+                .into_block_store(ctx, Some(payload_queue), self.p2p_stats.clone())
                 .await
                 .wrap("into_block_store()")?;
             s.spawn_bg(async { Ok(runner.run(ctx).await?) });
@@ -94,6 +579,27 @@ impl Fetcher {
         self,
         ctx: &ctx::Ctx,
         actions: ActionQueueSender,
+    ) -> anyhow::Result<()> {
+        self.run_centralized_inner(ctx, actions, None).await
+    }
+
+    /// Same as [`Self::run_centralized()`], but returns once `max_blocks` blocks are fetched and stored persistently.
+    /// Can be used for bounded sync jobs, e.g. to sync incrementally with progress checkpoints.
+    pub async fn run_centralized_bounded(
+        self,
+        ctx: &ctx::Ctx,
+        actions: ActionQueueSender,
+        max_blocks: u64,
+    ) -> anyhow::Result<()> {
+        self.run_centralized_inner(ctx, actions, Some(max_blocks))
+            .await
+    }
+
+    async fn run_centralized_inner(
+        self,
+        ctx: &ctx::Ctx,
+        actions: ActionQueueSender,
+        max_blocks: Option<u64>,
     ) -> anyhow::Result<()> {
         let res: ctx::Result<()> = scope::run!(ctx, |ctx, s| async {
             // Update sync state in the background.
@@ -106,7 +612,8 @@ impl Fetcher {
                 .new_payload_queue(ctx, actions)
                 .await
                 .wrap("new_fetcher_cursor()")?;
-            self.fetch_blocks(ctx, &mut payload_queue, None).await
+            self.fetch_blocks(ctx, &mut payload_queue, None, max_blocks)
+                .await
         })
         .await;
         match res {
@@ -120,14 +627,28 @@ impl Fetcher {
     async fn fetch_state_loop(&self, ctx: &ctx::Ctx) -> ctx::Result<()> {
         const DELAY_INTERVAL: time::Duration = time::Duration::milliseconds(500);
         const RETRY_INTERVAL: time::Duration = time::Duration::seconds(5);
+        let mut consecutive_failures = 0;
         loop {
             match ctx.wait(self.client.fetch_l2_block_number()).await? {
                 Ok(head) => {
                     self.sync_state.set_main_node_block(head);
+                    self.health.set_main_node_block(head, Instant::now());
+                    let local_head = self.sync_state.get_local_block();
+                    let lag = head.0.saturating_sub(local_head.0);
+                    FETCHER_METRICS.sync_lag.set(lag.into());
+                    FETCHER_METRICS
+                        .sync_progress
+                        .set(self.sync_state.sync_progress());
+                    consecutive_failures = 0;
+                    FETCHER_METRICS.consecutive_head_fetch_failures.set(0);
                     ctx.sleep(DELAY_INTERVAL).await?;
                 }
                 Err(err) => {
                     tracing::warn!("main_node_client.fetch_l2_block_number(): {err}");
+                    consecutive_failures += 1;
+                    FETCHER_METRICS
+                        .consecutive_head_fetch_failures
+                        .set(consecutive_failures);
                     ctx.sleep(RETRY_INTERVAL).await?;
                 }
             }
@@ -136,67 +657,439 @@ impl Fetcher {
 
     /// Fetches genesis from the main node.
     async fn fetch_genesis(&self, ctx: &ctx::Ctx) -> ctx::Result<validator::Genesis> {
-        let genesis = ctx
-            .wait(self.client.fetch_consensus_genesis())
+        Ok(self
+            .try_fetch_genesis(ctx)
             .await?
-            .context("fetch_consensus_genesis()")?
-            .context("main node is not running consensus component")?;
-        Ok(zksync_protobuf::serde::deserialize(&genesis.0).context("deserialize(genesis)")?)
+            .map_err(anyhow::Error::from)?)
     }
 
-    /// Fetches (with retries) the given block from the main node.
-    async fn fetch_block(&self, ctx: &ctx::Ctx, n: MiniblockNumber) -> ctx::Result<FetchedBlock> {
-        const RETRY_INTERVAL: time::Duration = time::Duration::seconds(5);
+    async fn try_fetch_genesis(
+        &self,
+        ctx: &ctx::Ctx,
+    ) -> ctx::OrCanceled<Result<validator::Genesis, GenesisError>> {
+        let raw = self.try_fetch_raw_genesis(ctx).await?;
+        Ok(raw.and_then(|raw| deserialize_genesis(&raw)))
+    }
 
+    async fn try_fetch_raw_genesis(
+        &self,
+        ctx: &ctx::Ctx,
+    ) -> ctx::OrCanceled<Result<serde_json::Value, GenesisError>> {
+        Ok(
+            match ctx.wait(self.client.fetch_consensus_genesis()).await? {
+                Ok(Some(genesis)) => Ok(genesis.0),
+                Ok(None) => Err(GenesisError::Missing),
+                Err(err) => Err(err.into()),
+            },
+        )
+    }
+
+    /// Periodically fetches genesis from the main node and compares it with `old` genesis. Returns an error
+    /// if the genesis changes, or if the main node returns a malformed genesis [`MAX_MALFORMED_GENESIS_RESPONSES`]
+    /// times in a row. Other errors (e.g., transient RPC errors) are logged and retried indefinitely.
+    ///
+    /// The genesis is only deserialized if the raw genesis returned by the main node changes; see [`GenesisCache`].
+    pub(super) async fn monitor_genesis(
+        &self,
+        ctx: &ctx::Ctx,
+        old: validator::Genesis,
+    ) -> ctx::Result<()> {
+        let mut cache = GenesisCache::default();
+        let mut malformed_responses = 0;
+        loop {
+            let check_result = self
+                .try_fetch_raw_genesis(ctx)
+                .await?
+                .and_then(|raw| cache.check(raw, &old));
+            match check_result {
+                Ok(Some(new)) => {
+                    return Err(
+                        anyhow::format_err!("genesis changed: old {old:?}, new {new:?}").into(),
+                    );
+                }
+                Ok(None) => {
+                    malformed_responses = 0;
+                }
+                Err(GenesisError::Malformed(err)) => {
+                    malformed_responses += 1;
+                    if malformed_responses >= MAX_MALFORMED_GENESIS_RESPONSES {
+                        return Err(err
+                            .context(format!(
+                                "main node returned malformed genesis {malformed_responses} times in a row"
+                            ))
+                            .into());
+                    }
+                    tracing::warn!(
+                        "Main node returned malformed genesis ({malformed_responses} times in a row): {err:#}"
+                    );
+                }
+                Err(err) => {
+                    tracing::warn!("Failed fetching genesis from the main node: {err}");
+                }
+            }
+            ctx.sleep(GENESIS_POLL_INTERVAL).await?;
+        }
+    }
+
+    /// Fetches (with retries) the given block from the main node. The caller is responsible for only requesting
+    /// blocks that the main node should have (e.g., by waiting for the main node head in `sync_state`);
+    /// if the main node doesn't return the block, it is polled with a backoff.
+    pub(super) async fn fetch_block(
+        &self,
+        ctx: &ctx::Ctx,
+        n: MiniblockNumber,
+    ) -> ctx::Result<FetchedBlock> {
+        let mut backoff = Backoff::new(Backoff::INITIAL_DELAY, Backoff::MAX_DELAY);
+        let mut missing_polls = 0;
         loop {
             let res = ctx.wait(self.client.fetch_l2_block(n, true)).await?;
             match res {
                 Ok(Some(block)) => return Ok(block.try_into()?),
-                Ok(None) => {}
+                // The main node is responsive and has reported a head including the block, but doesn't return it.
+                // This may happen briefly (e.g., if the main node is load-balanced across replicas), but if it persists,
+                // the main node has probably reverted blocks, or there is a gap in its storage.
+                // Until the warning threshold is reached, the block is polled with the initial delay since it's
+                // expected to appear shortly; afterwards, the delay grows to not hammer the main node.
+                Ok(None) => {
+                    missing_polls += 1;
+                    let warn_polls = self.config.missing_block_warn_polls.get();
+                    if missing_polls < warn_polls {
+                        backoff.reset();
+                    } else if missing_polls == warn_polls {
+                        tracing::warn!(
+                            "Main node hasn't returned miniblock #{n} after {missing_polls} polls, although \
+                             its head is at least #{n}; this may indicate a reorg or a gap on the main node"
+                        );
+                    }
+                }
                 Err(err) if err.is_transient() => {}
                 Err(err) => {
                     return Err(anyhow::format_err!("client.fetch_l2_block({}): {err}", n).into());
                 }
             }
-            ctx.sleep(RETRY_INTERVAL).await?;
+            let delay = backoff.next_delay(&mut ctx.rng());
+            ctx.sleep(delay).await?;
+        }
+    }
+
+    /// Fetches (with retries) blocks in range `[from..=to]` from the main node with a single request per attempt.
+    /// The main node may return fewer blocks than requested; returned blocks are sorted and checked to form
+    /// a contiguous range starting from `from`. Returns `None` if the main node doesn't support range requests.
+    pub(super) async fn fetch_block_range(
+        &self,
+        ctx: &ctx::Ctx,
+        from: MiniblockNumber,
+        to: MiniblockNumber,
+    ) -> ctx::Result<Option<Vec<FetchedBlock>>> {
+        let mut backoff = Backoff::new(Backoff::INITIAL_DELAY, Backoff::MAX_DELAY);
+        loop {
+            let res = ctx
+                .wait(self.client.fetch_l2_block_range(from, to, true))
+                .await?;
+            match res {
+                Ok(mut blocks) => {
+                    blocks.sort_unstable_by_key(|block| block.number);
+                    for (i, block) in blocks.iter().enumerate() {
+                        let expected = from.0.checked_add(i as u32).filter(|&n| n <= to.0);
+                        if expected != Some(block.number.0) {
+                            return Err(anyhow::format_err!(
+                                "main node returned unexpected miniblock #{} for range {from}..={to}",
+                                block.number
+                            )
+                            .into());
+                        }
+                    }
+                    let blocks = blocks
+                        .into_iter()
+                        .map(FetchedBlock::try_from)
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    return Ok(Some(blocks));
+                }
+                Err(err) if is_method_not_found(&err) => return Ok(None),
+                Err(err) if err.is_transient() => {}
+                Err(err) => {
+                    return Err(anyhow::format_err!(
+                        "client.fetch_l2_block_range({from}, {to}): {err}"
+                    )
+                    .into());
+                }
+            }
+            let delay = backoff.next_delay(&mut ctx.rng());
+            ctx.sleep(delay).await?;
         }
     }
 
-    /// Fetches blocks from the main node in range `[cursor.next()..end)`.
+    /// Verifies that blocks in range `[to..=from]` form a hash chain, going from the (trusted) block `from`
+    /// backwards to the (known-good) block `to`. For each block, its hash is recomputed using the hash
+    /// of its parent and compared to the hash returned by the main node. Blocks are not applied.
+    /// Returns [`VerificationError::InvalidRange`] if `to` is above `from`.
+    pub async fn verify_backwards(
+        &self,
+        ctx: &ctx::Ctx,
+        from: MiniblockNumber,
+        to: MiniblockNumber,
+    ) -> Result<(), VerificationError> {
+        if to > from {
+            return Err(VerificationError::InvalidRange { from, to });
+        }
+
+        let mut child = self.fetch_block(ctx, from).await?;
+        while child.number > to {
+            let parent = self.fetch_block(ctx, child.number - 1).await?;
+            let parent_hash = parent
+                .reference_hash
+                .ok_or(VerificationError::MissingHash(parent.number))?;
+            let returned = child
+                .reference_hash
+                .ok_or(VerificationError::MissingHash(child.number))?;
+
+            let computed = child.compute_hash(parent_hash);
+            if computed != returned {
+                return Err(VerificationError::BrokenLink {
+                    number: child.number,
+                    computed,
+                    returned,
+                });
+            }
+            child = parent;
+        }
+        Ok(())
+    }
+
+    /// Checks that `block` has the `expected` number.
+    fn check_block_order(
+        block: &FetchedBlock,
+        expected: validator::BlockNumber,
+    ) -> Result<(), BlockOutOfOrder> {
+        if u64::from(block.number.0) != expected.0 {
+            return Err(BlockOutOfOrder {
+                expected: MiniblockNumber(expected.0.try_into().unwrap()),
+                got: block.number,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that `block` is linked to the previously applied block with the specified hash.
+    /// Blocks without a hash returned by the main node are not checked.
+    fn check_parent_link(
+        block: &FetchedBlock,
+        prev_hash: Option<H256>,
+    ) -> Result<(), ReorgDetected> {
+        let (Some(parent_hash), Some(returned)) = (prev_hash, block.reference_hash) else {
+            return Ok(());
+        };
+        let computed = block.compute_hash(parent_hash);
+        if computed != returned {
+            return Err(ReorgDetected {
+                number: block.number,
+                computed,
+                returned,
+            });
+        }
+        Ok(())
+    }
+
+    /// Fetches blocks from the main node in range `[queue.next()..end)` and waits until they are stored persistently.
+    /// If `config.range_size` is set, blocks are fetched in ranges (see [`Self::fetch_block_ranges_into()`]).
+    ///
+    /// If `max_blocks` is set, at most this many blocks are fetched; the range end is capped accordingly, so all blocks
+    /// fetched before reaching the cap are still applied and stored before returning.
     pub(super) async fn fetch_blocks(
         &self,
         ctx: &ctx::Ctx,
         queue: &mut storage::PayloadQueue,
         end: Option<validator::BlockNumber>,
+        max_blocks: Option<u64>,
     ) -> ctx::Result<()> {
-        const MAX_CONCURRENT_REQUESTS: usize = 30;
+        let end = match max_blocks {
+            Some(max_blocks) => {
+                let capped_end = validator::BlockNumber(queue.next().0.saturating_add(max_blocks));
+                Some(end.map_or(capped_end, |end| end.min(capped_end)))
+            }
+            None => end,
+        };
+        let Some(range_size) = self.config.range_size else {
+            let start = queue.next();
+            return self.fetch_blocks_from(ctx, queue, start, end).await;
+        };
         let first = queue.next();
-        let mut next = first;
+        self.fetch_block_ranges_into(ctx, queue, end, range_size)
+            .await?;
+        self.wait_for_stored_blocks(ctx, queue, first).await
+    }
+
+    /// Same as [`Self::fetch_blocks()`], but starts fetching from the specified block rather than from `queue.next()`.
+    /// `start` must not exceed `queue.next()` since the queue cannot skip blocks. Starting from an earlier block
+    /// re-fetches already applied blocks; this is only valid if the re-fetched blocks match the applied ones
+    /// (the queue ignores re-fetched blocks, so mismatches are not detected).
+    pub(super) async fn fetch_blocks_from(
+        &self,
+        ctx: &ctx::Ctx,
+        queue: &mut storage::PayloadQueue,
+        start: validator::BlockNumber,
+        end: Option<validator::BlockNumber>,
+    ) -> ctx::Result<()> {
+        let first = queue.next();
+        self.fetch_blocks_into(ctx, queue, start, end).await?;
+        self.wait_for_stored_blocks(ctx, queue, first).await
+    }
+
+    /// Waits until the last block applied to `queue` is stored persistently, provided that any blocks
+    /// were applied since `first`.
+    async fn wait_for_stored_blocks(
+        &self,
+        ctx: &ctx::Ctx,
+        queue: &storage::PayloadQueue,
+        first: validator::BlockNumber,
+    ) -> ctx::Result<()> {
+        if first < queue.next() {
+            self.store
+                .wait_for_payload(ctx, queue.next().prev().unwrap())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Fetches blocks from the main node in range `[start..end)` and applies them to `sink`. `start` must not exceed
+    /// `sink.next()`; if it's less than `sink.next()`, the sink must tolerate re-applying blocks.
+    /// At most `config.read_ahead_window` blocks past the last applied block are fetched at any time,
+    /// with at most `config.concurrency_limit` requests to the main node in flight. If applying blocks to `sink`
+    /// becomes slower than `config.backpressure_threshold`, the next block is only fetched after all previous
+    /// blocks are applied.
+    ///
+    /// Each fetched block (except for the first one) is checked to be linked to the previously fetched block.
+    /// If it isn't, [`ReorgDetected`] is returned, and the block is not applied. Similarly, if a received block
+    /// doesn't have the expected number, [`BlockOutOfOrder`] is returned.
+    pub(crate) async fn fetch_blocks_into(
+        &self,
+        ctx: &ctx::Ctx,
+        sink: &mut impl BlockSink,
+        start: validator::BlockNumber,
+        end: Option<validator::BlockNumber>,
+    ) -> ctx::Result<()> {
+        let next_to_apply = sink.next();
+        if start > next_to_apply {
+            return Err(anyhow::format_err!(
+                "cannot start fetching from block {start:?}: the next block to apply is {next_to_apply:?}"
+            )
+            .into());
+        }
+
+        let concurrency_limit = self.config.concurrency_limit.get();
+        let mut next = start;
+        let window = ReadAheadWindow::new(start, self.config.read_ahead_window);
+        let permits = tokio::sync::Semaphore::new(concurrency_limit);
+        let backpressure = Backpressure::new(self.config.backpressure_threshold);
         scope::run!(ctx, |ctx, s| async {
-            let (send, mut recv) = ctx::channel::bounded(MAX_CONCURRENT_REQUESTS);
+            let (send, mut recv) = ctx::channel::bounded(concurrency_limit);
             s.spawn(async {
                 let send = send;
                 while end.map_or(true, |end| next < end) {
+                    window.wait_for_slot(ctx, next).await?;
+                    if backpressure.is_throttled() {
+                        window.wait_for_applied(ctx, next).await?;
+                    }
                     let n = MiniblockNumber(next.0.try_into().unwrap());
                     self.sync_state.wait_for_main_node_block(ctx, n).await?;
-                    send.send(ctx, s.spawn(self.fetch_block(ctx, n))).await?;
+                    // The semaphore is never closed, so acquiring a permit cannot fail.
+                    let permit = ctx.wait(permits.acquire()).await?.unwrap();
+                    let fetch = s.spawn(async move {
+                        let _permit = permit;
+                        self.fetch_block(ctx, n).await
+                    });
+                    send.send(ctx, fetch).await?;
                     next = next.next();
                 }
                 Ok(())
             });
-            while end.map_or(true, |end| queue.next() < end) {
+            let mut prev_hash = sink.prev_hash();
+            let mut expected = start;
+            while end.map_or(true, |end| sink.next() < end) {
                 let block = recv.recv(ctx).await?.join(ctx).await?;
-                queue.send(block).await?;
+                Self::check_block_order(&block, expected).map_err(anyhow::Error::from)?;
+                expected = expected.next();
+                let latency = self.apply_block(sink, block, &mut prev_hash).await?;
+                backpressure.observe(latency);
+                window.set_applied(sink.next());
             }
             Ok(())
         })
-        .await?;
-        // If fetched anything, wait for the last block to be stored persistently.
-        if first < queue.next() {
-            self.store
-                .wait_for_payload(ctx, queue.next().prev().unwrap())
-                .await?;
+        .await
+    }
+
+    /// Same as [`Self::fetch_blocks_into()`] starting from `sink.next()`, but fetches up to `range_size` blocks
+    /// with a single request (see [`Self::fetch_block_range()`]). Ranges are fetched sequentially. If the main node
+    /// doesn't support range requests, falls back to [`Self::fetch_blocks_into()`].
+    pub(crate) async fn fetch_block_ranges_into(
+        &self,
+        ctx: &ctx::Ctx,
+        sink: &mut impl BlockSink,
+        end: Option<validator::BlockNumber>,
+        range_size: NonZeroUsize,
+    ) -> ctx::Result<()> {
+        let range_size = u32::try_from(range_size.get()).unwrap_or(u32::MAX);
+        let mut prev_hash = sink.prev_hash();
+        while end.map_or(true, |end| sink.next() < end) {
+            let from = MiniblockNumber(sink.next().0.try_into().unwrap());
+            self.sync_state.wait_for_main_node_block(ctx, from).await?;
+            let mut to = from
+                .0
+                .saturating_add(range_size - 1)
+                .min(self.sync_state.get_main_node_block().0);
+            if let Some(end) = end {
+                // `end > from`, so `end - 1` cannot underflow.
+                to = to.min((end.0 - 1).try_into().unwrap_or(u32::MAX));
+            }
+
+            let Some(mut blocks) = self
+                .fetch_block_range(ctx, from, MiniblockNumber(to))
+                .await?
+            else {
+                tracing::info!(
+                    "Main node doesn't support range requests; falling back to fetching blocks one by one"
+                );
+                let start = sink.next();
+                return self.fetch_blocks_into(ctx, sink, start, end).await;
+            };
+            if blocks.is_empty() {
+                // The main node has reported a head including `from`, but doesn't return it.
+                // `fetch_block()` handles this case with retries and diagnostics.
+                blocks.push(self.fetch_block(ctx, from).await?);
+            }
+            for block in blocks {
+                Self::check_block_order(&block, sink.next()).map_err(anyhow::Error::from)?;
+                self.apply_block(sink, block, &mut prev_hash).await?;
+            }
         }
         Ok(())
     }
+
+    /// Validates the next fetched `block` and applies it to `sink`. `prev_hash` is the hash of the previously
+    /// applied block (if known); it's updated to the hash of the applied block. Returns the time spent
+    /// in [`BlockSink::apply()`].
+    async fn apply_block(
+        &self,
+        sink: &mut impl BlockSink,
+        block: FetchedBlock,
+        prev_hash: &mut Option<H256>,
+    ) -> ctx::Result<Duration> {
+        sink.validate_timestamp(
+            &block,
+            seconds_since_epoch(),
+            self.config.max_timestamp_skew,
+        )?;
+        let number = block.number;
+        // Blocks preceding `sink.next()` are already applied and are skipped by the sink, so they aren't linked.
+        if u64::from(number.0) == sink.next().0 {
+            Self::check_parent_link(&block, *prev_hash).map_err(anyhow::Error::from)?;
+            *prev_hash = block.reference_hash;
+        }
+        let started_at = Instant::now();
+        sink.apply(block).await?;
+        let latency = started_at.elapsed();
+        FETCHER_METRICS.sink_apply_latency.observe(latency);
+        self.health.set_fetched_block(number, Instant::now());
+        Ok(latency)
+    }
 }
@@ -0,0 +1,32 @@
+//! Metrics for the consensus component.
+
+use std::time::Duration;
+
+use vise::{Buckets, Counter, Gauge, Histogram, Metrics};
+
+/// Metrics for the consensus fetcher.
+#[derive(Debug, Metrics)]
+#[metrics(prefix = "consensus_fetcher")]
+pub(super) struct FetcherMetrics {
+    /// Number of times the payload queue cursor disagreed with the blocks persisted in storage on startup.
+    pub cursor_mismatches: Counter,
+    /// Number of miniblocks the local head lags behind the main node head. Zero if the node has caught up.
+    pub sync_lag: Gauge<u64>,
+    /// Sync progress as a ratio of the local head to the main node head, in the `[0, 1]` range.
+    /// Zero if the main node head is unknown.
+    pub sync_progress: Gauge<f64>,
+    /// Number of consecutive failed requests for the main node head. Reset to zero after a successful request.
+    pub consecutive_head_fetch_failures: Gauge<u64>,
+    /// Number of blocks received by the p2p fetcher via the gossip network.
+    pub gossip_blocks: Counter,
+    /// Time spent applying a fetched block to the sink, e.g. blocked sending it to the payload queue.
+    #[metrics(buckets = Buckets::LATENCY)]
+    pub sink_apply_latency: Histogram<Duration>,
+    /// Number of times fetching was throttled because applying blocks became slower than the backpressure threshold.
+    pub backpressure_throttles: Counter,
+    /// Whether fetching is currently throttled because of backpressure from the sink (1) or not (0).
+    pub throttled: Gauge<u64>,
+}
+
+#[vise::register]
+pub(super) static FETCHER_METRICS: vise::Global<FetcherMetrics> = vise::Global::new();
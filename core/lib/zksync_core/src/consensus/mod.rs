@@ -12,6 +12,7 @@ pub use self::{fetcher::*, storage::Store};
 pub mod config;
 pub mod era;
 mod fetcher;
+mod metrics;
 mod storage;
 #[cfg(test)]
 pub(crate) mod testonly;
@@ -36,7 +37,7 @@ impl MainNodeConfig {
                 .wrap("block_store.try_init_genesis()")?;
             let (block_store, runner) = store
                 .clone()
-                .into_block_store(ctx, None)
+                .into_block_store(ctx, None, P2PStats::default())
                 .await
                 .wrap("into_block_store()")?;
             s.spawn_bg(runner.run(ctx));
@@ -1,6 +1,6 @@
 //! Storage implementation based on DAL.
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Context as _;
 use zksync_concurrency::{ctx, error::Wrap as _, scope, sync, time};
@@ -8,11 +8,12 @@ use zksync_consensus_bft::PayloadManager;
 use zksync_consensus_roles::validator;
 use zksync_consensus_storage as storage;
 use zksync_dal::{consensus_dal::Payload, ConnectionPool, Core, CoreDal, DalError};
-use zksync_types::MiniblockNumber;
+use zksync_types::{MiniblockNumber, H256};
 
 #[cfg(test)]
 mod testonly;
 
+use super::{fetcher::P2PStats, metrics::FETCHER_METRICS};
 use crate::{
     state_keeper::io::common::IoCursor,
     sync_layer::{
@@ -136,10 +137,39 @@ impl<'a> Connection<'a> {
         ctx: &ctx::Ctx,
         actions: ActionQueueSender,
     ) -> ctx::Result<PayloadQueue> {
-        Ok(PayloadQueue {
+        let mut queue = PayloadQueue {
             inner: ctx.wait(IoCursor::for_fetcher(&mut self.0)).await??,
             actions,
-        })
+        };
+        self.reconcile_payload_queue(ctx, &mut queue)
+            .await
+            .wrap("reconcile_payload_queue()")?;
+        Ok(queue)
+    }
+
+    /// Compares the payload queue cursor with the blocks persisted in storage and, if they disagree
+    /// (e.g., because the state keeper persisted more blocks after the cursor was loaded), reconciles
+    /// the cursor to the higher of the two positions, so that no persisted block is applied twice.
+    pub(super) async fn reconcile_payload_queue(
+        &mut self,
+        ctx: &ctx::Ctx,
+        queue: &mut PayloadQueue,
+    ) -> ctx::Result<()> {
+        let cursor_next = queue.next();
+        let persisted_next = self.block_range(ctx).await.wrap("block_range()")?.end;
+        if cursor_next == persisted_next {
+            return Ok(());
+        }
+
+        tracing::warn!(
+            "Payload queue cursor ({cursor_next}) disagrees with the next block expected by storage \
+             ({persisted_next}); reconciling to the higher value"
+        );
+        FETCHER_METRICS.cursor_mismatches.inc();
+        if cursor_next < persisted_next {
+            queue.inner = ctx.wait(IoCursor::for_fetcher(&mut self.0)).await??;
+        }
+        Ok(())
     }
 
     pub async fn genesis(&mut self, ctx: &ctx::Ctx) -> ctx::Result<Option<validator::Genesis>> {
@@ -171,6 +201,26 @@ impl PayloadQueue {
         validator::BlockNumber(self.inner.next_miniblock.0.into())
     }
 
+    /// Returns the hash of the last miniblock processed by the queue.
+    pub(super) fn prev_hash(&self) -> H256 {
+        self.inner.prev_miniblock_hash
+    }
+
+    /// Checks that the timestamp of the block is plausible (see [`FetchedBlock::validate_timestamp()`]).
+    /// Blocks other than the next expected one are not checked.
+    pub(super) fn validate_timestamp(
+        &self,
+        block: &FetchedBlock,
+        now: u64,
+        max_skew: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        if block.number != self.inner.next_miniblock {
+            return Ok(());
+        }
+        block.validate_timestamp(self.inner.prev_miniblock_timestamp, now, max_skew)?;
+        Ok(())
+    }
+
     /// Converts the block into actions and pushes them to the actions queue.
     /// Does nothing and returns Ok() if the block has been already processed.
     /// Returns an error if a block with an earlier block number was expected.
@@ -338,6 +388,7 @@ impl Store {
         self,
         ctx: &ctx::Ctx,
         payload_queue: Option<PayloadQueue>,
+        p2p_stats: P2PStats,
     ) -> ctx::Result<(Arc<storage::BlockStore>, BlockStoreRunner)> {
         let persisted = self
             .certificates_range(ctx)
@@ -351,6 +402,7 @@ impl Store {
                 inner: self.clone(),
                 certificates: certs_send,
                 payloads: payload_queue.map(sync::Mutex::new),
+                p2p_stats,
                 persisted: persisted.subscribe(),
             }),
         )
@@ -372,6 +424,8 @@ impl Store {
 struct BlockStore {
     inner: Store,
     payloads: Option<sync::Mutex<PayloadQueue>>,
+    /// Statistics of blocks received via the gossip network; only updated if `payloads` are set.
+    p2p_stats: P2PStats,
     certificates: ctx::channel::UnboundedSender<validator::CommitQC>,
     persisted: sync::watch::Receiver<storage::BlockStoreState>,
 }
@@ -472,6 +526,7 @@ impl storage::PersistentBlockStore for BlockStore {
                     .collect(),
             };
             payloads.send(block).await.context("payload_queue.send()")?;
+            self.p2p_stats.observe_gossip_block(number);
         }
         self.certificates.send(block.justification);
         Ok(())
@@ -20,7 +20,7 @@ use zksync_web3_decl::{
 
 use crate::{
     api_server::web3::{state::InternalApiConfig, tests::spawn_http_server},
-    consensus::{fetcher::P2PConfig, Fetcher, Store},
+    consensus::{fetcher::P2PConfig, Fetcher, FetcherConfig, FetcherHealth, P2PStats, Store},
     genesis::{mock_genesis_config, GenesisParams},
     state_keeper::{
         io::{IoCursor, L1BatchParams, MiniblockParams},
@@ -134,6 +134,25 @@ impl MainNodeClient for MockMainNodeClient {
         Ok(Some(block))
     }
 
+    async fn fetch_l2_block_range(
+        &self,
+        from: MiniblockNumber,
+        to: MiniblockNumber,
+        with_transactions: bool,
+    ) -> EnrichedClientResult<Vec<api::en::SyncBlock>> {
+        let mut blocks = vec![];
+        for number in from.0..=to.0 {
+            match self
+                .fetch_l2_block(MiniblockNumber(number), with_transactions)
+                .await?
+            {
+                Some(block) => blocks.push(block),
+                None => break,
+            }
+        }
+        Ok(blocks)
+    }
+
     async fn fetch_consensus_genesis(
         &self,
     ) -> EnrichedClientResult<Option<api::en::ConsensusGenesis>> {
@@ -293,27 +312,66 @@ impl StateKeeper {
         self,
         ctx: &ctx::Ctx,
         client: BoxedL2Client,
+        config: FetcherConfig,
     ) -> anyhow::Result<()> {
         Fetcher {
             store: self.store,
             client,
             sync_state: SyncState::default(),
+            health: FetcherHealth::default(),
+            p2p_stats: P2PStats::default(),
+            config,
         }
         .run_centralized(ctx, self.actions_sender)
         .await
     }
 
+    /// Runs the centralized fetcher until `max_blocks` blocks are fetched and stored.
+    pub async fn run_bounded_centralized_fetcher(
+        self,
+        ctx: &ctx::Ctx,
+        client: BoxedL2Client,
+        config: FetcherConfig,
+        max_blocks: u64,
+    ) -> anyhow::Result<()> {
+        Fetcher {
+            store: self.store,
+            client,
+            sync_state: SyncState::default(),
+            health: FetcherHealth::default(),
+            p2p_stats: P2PStats::default(),
+            config,
+        }
+        .run_centralized_bounded(ctx, self.actions_sender, max_blocks)
+        .await
+    }
+
     /// Runs the p2p fetcher.
     pub async fn run_p2p_fetcher(
         self,
         ctx: &ctx::Ctx,
         client: BoxedL2Client,
         cfg: P2PConfig,
+    ) -> anyhow::Result<()> {
+        self.run_p2p_fetcher_with_stats(ctx, client, cfg, P2PStats::default())
+            .await
+    }
+
+    /// Runs the p2p fetcher reporting statistics of blocks received via the gossip network to `p2p_stats`.
+    pub async fn run_p2p_fetcher_with_stats(
+        self,
+        ctx: &ctx::Ctx,
+        client: BoxedL2Client,
+        cfg: P2PConfig,
+        p2p_stats: P2PStats,
     ) -> anyhow::Result<()> {
         Fetcher {
             store: self.store,
             client,
             sync_state: SyncState::default(),
+            health: FetcherHealth::default(),
+            p2p_stats,
+            config: FetcherConfig::default(),
         }
         .run_p2p(ctx, self.actions_sender, cfg)
         .await
@@ -1,16 +1,41 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
 use anyhow::Context as _;
+use assert_matches::assert_matches;
+use futures::FutureExt as _;
 use rand::Rng;
-use test_casing::test_casing;
+use test_casing::{test_casing, Product};
 use tracing::Instrument as _;
-use zksync_concurrency::{ctx, scope};
+use zksync_concurrency::{ctx, scope, time};
 use zksync_consensus_executor as executor;
 use zksync_consensus_network as network;
 use zksync_consensus_network::testonly::{new_configs, new_fullnode};
 use zksync_consensus_roles::validator::testonly::Setup;
-use zksync_types::{L1BatchNumber, MiniblockNumber};
+use zksync_contracts::BaseSystemContractsHashes;
+use zksync_health_check::HealthStatus;
+use zksync_types::{
+    api, block::MiniblockHasher, Address, L1BatchNumber, MiniblockNumber, ProtocolVersionId, H256,
+};
+use zksync_web3_decl::{
+    client::{BoxedL2Client, MockL2Client},
+    jsonrpsee::{
+        core::ClientError as RpcError,
+        types::{error::ErrorCode, ErrorObject},
+    },
+};
 
 use super::*;
-use crate::utils::testonly::Snapshot;
+use crate::{
+    sync_layer::{fetcher::FetchedBlock, sync_action::ActionQueue, SyncState},
+    utils::testonly::Snapshot,
+};
 
 async fn new_store(from_snapshot: bool) -> Store {
     match from_snapshot {
@@ -63,7 +88,11 @@ async fn test_validator_block_store() {
     // Insert blocks one by one and check the storage state.
     for (i, block) in want.iter().enumerate() {
         scope::run!(ctx, |ctx, s| async {
-            let (block_store, runner) = store.clone().into_block_store(ctx, None).await.unwrap();
+            let (block_store, runner) = store
+                .clone()
+                .into_block_store(ctx, None, P2PStats::default())
+                .await
+                .unwrap();
             s.spawn_bg(runner.run(ctx));
             block_store.queue_block(ctx, block.clone()).await.unwrap();
             block_store
@@ -82,6 +111,41 @@ async fn test_validator_block_store() {
     }
 }
 
+// Test that the payload queue cursor is reconciled with the storage if the latter has advanced
+// after the cursor was loaded (e.g., when the fetcher restarts while the state keeper is still persisting blocks).
+#[test_casing(2, [false, true])]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_payload_queue_reconciliation(from_snapshot: bool) {
+    zksync_concurrency::testonly::abort_on_panic();
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let rng = &mut ctx.rng();
+    let store = new_store(from_snapshot).await;
+
+    scope::run!(ctx, |ctx, s| async {
+        let (mut sk, runner) = testonly::StateKeeper::new(ctx, store.clone()).await?;
+        s.spawn_bg(runner.run(ctx));
+        let mut conn = store.access(ctx).await.wrap("access()")?;
+        let (actions, _) = ActionQueue::new();
+        let mut queue = conn
+            .new_payload_queue(ctx, actions)
+            .await
+            .wrap("new_payload_queue()")?;
+        let stale_next = queue.next();
+        assert_eq!(stale_next, sk.last_block().next());
+
+        sk.push_random_blocks(rng, 5).await;
+        store.wait_for_payload(ctx, sk.last_block()).await?;
+        conn.reconcile_payload_queue(ctx, &mut queue)
+            .await
+            .wrap("reconcile_payload_queue()")?;
+        assert!(queue.next() > stale_next);
+        assert_eq!(queue.next(), sk.last_block().next());
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
 fn executor_config(cfg: &network::Config) -> executor::Config {
     executor::Config {
         server_addr: *cfg.server_addr,
@@ -236,6 +300,65 @@ async fn test_nodes_from_various_snapshots() {
     .unwrap();
 }
 
+// Test that the p2p fetcher reports blocks received via the gossip network.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_p2p_fetcher_stats() {
+    zksync_concurrency::testonly::abort_on_panic();
+    let ctx = &ctx::test_root(&ctx::AffineClock::new(10.));
+    let rng = &mut ctx.rng();
+    let setup = Setup::new(rng, 1);
+    let validator_cfg = new_configs(rng, &setup, 0).pop().unwrap();
+    let p2p_stats = P2PStats::default();
+
+    scope::run!(ctx, |ctx, s| async {
+        let validator_store = new_store(false).await;
+        let (mut validator, runner) =
+            testonly::StateKeeper::new(ctx, validator_store.clone()).await?;
+        s.spawn_bg(runner.run(ctx).instrument(tracing::info_span!("validator")));
+        // Blocks before the consensus genesis are fetched using json RPC, so they are not reported.
+        validator.push_random_blocks(rng, 3).await;
+        validator_store
+            .wait_for_payload(ctx, validator.last_block())
+            .await?;
+        let cfg = MainNodeConfig {
+            executor: executor_config(&validator_cfg),
+            validator_key: setup.keys[0].clone(),
+        };
+        s.spawn_bg(cfg.run(ctx, validator_store.clone()));
+
+        let node_store = new_store(false).await;
+        let (node, runner) = testonly::StateKeeper::new(ctx, node_store.clone()).await?;
+        s.spawn_bg(runner.run(ctx).instrument(tracing::info_span!("node")));
+        let node_cfg = executor_config(&new_fullnode(rng, &validator_cfg));
+        s.spawn_bg(node.run_p2p_fetcher_with_stats(
+            ctx,
+            validator.connect(ctx).await?,
+            node_cfg,
+            p2p_stats.clone(),
+        ));
+
+        validator.push_random_blocks(rng, 5).await;
+        node_store
+            .wait_for_certificate(ctx, validator.last_block())
+            .await?;
+
+        let genesis = validator_store.genesis(ctx).await?;
+        let first_gossip_block = genesis.fork.first_block;
+        let last_block = validator.last_block();
+        assert_eq!(
+            p2p_stats.gossip_blocks(),
+            last_block.0 - first_gossip_block.0 + 1
+        );
+        assert_eq!(
+            p2p_stats.last_gossip_block(),
+            Some(MiniblockNumber(last_block.0.try_into().unwrap()))
+        );
+        Ok(())
+    })
+    .await
+    .unwrap();
+}
+
 // Test running a validator node and a couple of full nodes.
 // Validator is producing signed blocks and fetchers are expected to fetch
 // them directly or indirectly.
@@ -377,7 +500,7 @@ async fn test_p2p_fetcher_backfill_certs(from_snapshot: bool) {
         scope::run!(ctx, |ctx, s| async {
             let (node, runner) = testonly::StateKeeper::new(ctx, node_store.clone()).await?;
             s.spawn_bg(runner.run(ctx));
-            s.spawn_bg(node.run_centralized_fetcher(ctx, client.clone()));
+            s.spawn_bg(node.run_centralized_fetcher(ctx, client.clone(), FetcherConfig::default()));
             validator.push_random_blocks(rng, 3).await;
             node_store
                 .wait_for_payload(ctx, validator.last_block())
@@ -410,9 +533,9 @@ async fn test_p2p_fetcher_backfill_certs(from_snapshot: bool) {
     .unwrap();
 }
 
-#[test_casing(2, [false, true])]
+#[test_casing(4, Product(([false, true], [None, NonZeroUsize::new(2)])))]
 #[tokio::test]
-async fn test_centralized_fetcher(from_snapshot: bool) {
+async fn test_centralized_fetcher(from_snapshot: bool, read_ahead_window: Option<NonZeroUsize>) {
     zksync_concurrency::testonly::abort_on_panic();
     let ctx = &ctx::test_root(&ctx::RealClock);
     let rng = &mut ctx.rng();
@@ -432,7 +555,11 @@ async fn test_centralized_fetcher(from_snapshot: bool) {
         let node_store = new_store(from_snapshot).await;
         let (node, runner) = testonly::StateKeeper::new(ctx, node_store.clone()).await?;
         s.spawn_bg(runner.run(ctx).instrument(tracing::info_span!("fetcher")));
-        s.spawn_bg(node.run_centralized_fetcher(ctx, validator.connect(ctx).await?));
+        let config = FetcherConfig {
+            read_ahead_window,
+            ..FetcherConfig::default()
+        };
+        s.spawn_bg(node.run_centralized_fetcher(ctx, validator.connect(ctx).await?, config));
 
         tracing::info!("Produce some blocks and wait for node to fetch them");
         validator.push_random_blocks(rng, 10).await;
@@ -448,3 +575,932 @@ async fn test_centralized_fetcher(from_snapshot: bool) {
     .await
     .unwrap();
 }
+
+#[tokio::test]
+async fn test_centralized_fetcher_concurrency_limit() {
+    const BLOCK_COUNT: u32 = 5;
+
+    zksync_concurrency::testonly::abort_on_panic();
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let max_in_flight = Arc::new(AtomicUsize::new(0));
+    let blocks = Arc::new(create_sync_block_chain(BLOCK_COUNT + 1));
+    let client = MockL2Client::new_async({
+        let in_flight = in_flight.clone();
+        let max_in_flight = max_in_flight.clone();
+        move |method, params| {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            let blocks = blocks.clone();
+            Box::pin(async move {
+                match method {
+                    "eth_blockNumber" => Ok(serde_json::json!(format!("{BLOCK_COUNT:#x}"))),
+                    "en_syncL2Block" => {
+                        let (number, _): (MiniblockNumber, bool) =
+                            serde_json::from_value(params).unwrap();
+                        let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_in_flight.fetch_max(current, Ordering::SeqCst);
+                        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                        let block = (1..=BLOCK_COUNT)
+                            .contains(&number.0)
+                            .then(|| blocks[number.0 as usize].clone());
+                        Ok(serde_json::to_value(block).unwrap())
+                    }
+                    _ => panic!("unexpected method: {method}"),
+                }
+            })
+        }
+    });
+
+    scope::run!(ctx, |ctx, s| async {
+        let node_store = new_store(false).await;
+        let (node, runner) = testonly::StateKeeper::new(ctx, node_store.clone()).await?;
+        s.spawn_bg(runner.run(ctx));
+        let config = FetcherConfig {
+            concurrency_limit: NonZeroUsize::new(1).unwrap(),
+            ..FetcherConfig::default()
+        };
+        s.spawn_bg(node.run_centralized_fetcher(ctx, BoxedL2Client::new(client), config));
+
+        node_store
+            .wait_for_payload(ctx, validator::BlockNumber(BLOCK_COUNT.into()))
+            .await?;
+        Ok(())
+    })
+    .await
+    .unwrap();
+    assert_eq!(max_in_flight.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_bounded_centralized_fetcher() {
+    const BLOCK_COUNT: u32 = 10;
+    const MAX_BLOCKS: u32 = 4;
+
+    zksync_concurrency::testonly::abort_on_panic();
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let requested_blocks = Arc::new(std::sync::Mutex::new(vec![]));
+    let blocks = create_sync_block_chain(BLOCK_COUNT + 1);
+    let client = MockL2Client::new({
+        let requested_blocks = requested_blocks.clone();
+        move |method, params| match method {
+            "eth_blockNumber" => Ok(serde_json::json!(format!("{BLOCK_COUNT:#x}"))),
+            "en_syncL2Block" => {
+                let (number, _): (MiniblockNumber, bool) = serde_json::from_value(params).unwrap();
+                requested_blocks.lock().unwrap().push(number.0);
+                let block = (1..=BLOCK_COUNT)
+                    .contains(&number.0)
+                    .then(|| blocks[number.0 as usize].clone());
+                Ok(serde_json::to_value(block).unwrap())
+            }
+            _ => panic!("unexpected method: {method}"),
+        }
+    });
+
+    scope::run!(ctx, |ctx, s| async {
+        let node_store = new_store(false).await;
+        let (node, runner) = testonly::StateKeeper::new(ctx, node_store.clone()).await?;
+        s.spawn_bg(runner.run(ctx));
+        node.run_bounded_centralized_fetcher(
+            ctx,
+            BoxedL2Client::new(client),
+            FetcherConfig::default(),
+            MAX_BLOCKS.into(),
+        )
+        .await?;
+
+        // All fetched blocks must be stored by the time the fetcher returns.
+        let mut conn = node_store.access(ctx).await.wrap("access()")?;
+        let last_block = validator::BlockNumber(MAX_BLOCKS.into());
+        let payload = conn.payload(ctx, last_block).await.wrap("payload()")?;
+        assert!(payload.is_some(), "fetched blocks are not stored");
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    let mut requested_blocks = requested_blocks.lock().unwrap().clone();
+    requested_blocks.sort_unstable();
+    requested_blocks.dedup();
+    assert_eq!(requested_blocks, (1..=MAX_BLOCKS).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn test_read_ahead_window() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let window = fetcher::ReadAheadWindow::new(validator::BlockNumber(10), NonZeroUsize::new(3));
+
+    // Blocks inside the window can be fetched immediately.
+    for n in 10..13 {
+        window
+            .wait_for_slot(ctx, validator::BlockNumber(n))
+            .await
+            .unwrap();
+    }
+    // The next block must wait until the applied position advances.
+    let wait_future = window.wait_for_slot(ctx, validator::BlockNumber(13));
+    futures::pin_mut!(wait_future);
+    tokio::task::yield_now().await;
+    assert!((&mut wait_future).now_or_never().is_none());
+
+    window.set_applied(validator::BlockNumber(11));
+    wait_future.await.unwrap();
+    let wait_future = window.wait_for_slot(ctx, validator::BlockNumber(14));
+    futures::pin_mut!(wait_future);
+    tokio::task::yield_now().await;
+    assert!((&mut wait_future).now_or_never().is_none());
+
+    // Unbounded window doesn't block.
+    let window = fetcher::ReadAheadWindow::new(validator::BlockNumber(10), None);
+    window
+        .wait_for_slot(ctx, validator::BlockNumber(1_000))
+        .await
+        .unwrap();
+}
+
+/// Creates an empty miniblock linked to the parent with the specified hash.
+fn create_sync_block(number: MiniblockNumber, prev_hash: H256) -> api::en::SyncBlock {
+    let timestamp = u64::from(number.0) + 1;
+    let protocol_version = ProtocolVersionId::latest();
+    api::en::SyncBlock {
+        number,
+        l1_batch_number: L1BatchNumber(number.0),
+        last_in_batch: true,
+        timestamp,
+        l1_gas_price: 2,
+        l2_fair_gas_price: 3,
+        fair_pubdata_price: Some(24),
+        base_system_contracts_hashes: BaseSystemContractsHashes::default(),
+        operator_address: Address::repeat_byte(2),
+        transactions: Some(vec![]),
+        virtual_blocks: Some(0),
+        hash: Some(MiniblockHasher::new(number, timestamp, prev_hash).finalize(protocol_version)),
+        protocol_version,
+    }
+}
+
+async fn create_fetcher(client: MockL2Client) -> Fetcher {
+    Fetcher {
+        store: new_store(false).await,
+        sync_state: SyncState::default(),
+        health: FetcherHealth::default(),
+        p2p_stats: P2PStats::default(),
+        client: BoxedL2Client::new(client),
+        config: FetcherConfig::default(),
+    }
+}
+
+/// Creates a fetcher serving the specified blocks, which must be sorted by number starting from 0.
+async fn create_fetcher_for_blocks(blocks: Vec<api::en::SyncBlock>) -> Fetcher {
+    let last_number = blocks.last().map(|block| block.number);
+    let client = MockL2Client::new(move |method, params| {
+        assert_eq!(method, "en_syncL2Block");
+        let (number, _): (MiniblockNumber, bool) = serde_json::from_value(params).unwrap();
+        let block = blocks.get(number.0 as usize);
+        Ok(serde_json::to_value(block).unwrap())
+    });
+    let fetcher = create_fetcher(client).await;
+    if let Some(last_number) = last_number {
+        fetcher.sync_state.set_main_node_block(last_number);
+    }
+    fetcher
+}
+
+#[test]
+fn backoff_delays() {
+    let rng = &mut rand::thread_rng();
+    let mut backoff =
+        fetcher::Backoff::new(fetcher::Backoff::INITIAL_DELAY, fetcher::Backoff::MAX_DELAY);
+    let mut base = fetcher::Backoff::INITIAL_DELAY;
+    for _ in 0..10 {
+        let delay = backoff.next_delay(rng);
+        assert!(
+            delay >= base / 2 && delay <= base,
+            "{delay:?}, base {base:?}"
+        );
+        base = (base * 2).min(fetcher::Backoff::MAX_DELAY);
+    }
+    assert_eq!(base, fetcher::Backoff::MAX_DELAY);
+
+    backoff.reset();
+    let delay = backoff.next_delay(rng);
+    assert!(delay <= fetcher::Backoff::INITIAL_DELAY, "{delay:?}");
+}
+
+#[tokio::test]
+async fn fetching_block_with_backoff() {
+    const FAILED_REQUESTS: usize = 3;
+
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let block = create_sync_block(MiniblockNumber(1), H256::repeat_byte(1));
+    let request_count = Arc::new(AtomicUsize::new(0));
+    let client = MockL2Client::new({
+        let block = block.clone();
+        let request_count = request_count.clone();
+        move |method, _params| {
+            assert_eq!(method, "en_syncL2Block");
+            if request_count.fetch_add(1, Ordering::Relaxed) < FAILED_REQUESTS {
+                Err(RpcError::RequestTimeout)
+            } else {
+                Ok(serde_json::to_value(&block).unwrap())
+            }
+        }
+    });
+    let fetcher = create_fetcher(client).await;
+    fetcher.sync_state.set_main_node_block(MiniblockNumber(1));
+
+    let started_at = Instant::now();
+    let fetched_block = fetcher.fetch_block(ctx, MiniblockNumber(1)).await.unwrap();
+    let elapsed = time::Duration::try_from(started_at.elapsed()).unwrap();
+    assert_eq!(fetched_block.number, block.number);
+    assert_eq!(request_count.load(Ordering::Relaxed), FAILED_REQUESTS + 1);
+
+    // Delays are at least `[100ms, 200ms, 400ms]` and at most `[200ms, 400ms, 800ms]`, which is much less
+    // than waiting for the maximum delay after each failure.
+    let min_elapsed = fetcher::Backoff::INITIAL_DELAY * 7 / 2;
+    let max_elapsed = fetcher::Backoff::MAX_DELAY * FAILED_REQUESTS as i32;
+    assert!(elapsed >= min_elapsed, "{elapsed:?}");
+    assert!(elapsed < max_elapsed, "{elapsed:?}");
+}
+
+#[tokio::test]
+async fn fetching_block_missing_on_main_node() {
+    const MISSING_POLLS: usize = 3;
+
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let block = create_sync_block(MiniblockNumber(1), H256::repeat_byte(1));
+    let request_count = Arc::new(AtomicUsize::new(0));
+    let client = MockL2Client::new({
+        let block = block.clone();
+        let request_count = request_count.clone();
+        move |method, _params| {
+            assert_eq!(method, "en_syncL2Block");
+            if request_count.fetch_add(1, Ordering::Relaxed) < MISSING_POLLS {
+                Ok(serde_json::Value::Null)
+            } else {
+                Ok(serde_json::to_value(&block).unwrap())
+            }
+        }
+    });
+    let mut fetcher = create_fetcher(client).await;
+    fetcher.config.missing_block_warn_polls = NonZeroUsize::new(2).unwrap();
+
+    let started_at = Instant::now();
+    let fetched_block = fetcher.fetch_block(ctx, MiniblockNumber(1)).await.unwrap();
+    let elapsed = time::Duration::try_from(started_at.elapsed()).unwrap();
+    assert_eq!(fetched_block.number, block.number);
+    assert_eq!(request_count.load(Ordering::Relaxed), MISSING_POLLS + 1);
+
+    // The first delay is reset to the initial one (i.e., at most `200ms`), but starting from the warning threshold,
+    // delays grow (i.e., are at least `[200ms, 400ms]`). If delays were always reset, the total delay
+    // would be at most `600ms`.
+    let min_elapsed = fetcher::Backoff::INITIAL_DELAY * 7 / 2;
+    assert!(elapsed >= min_elapsed, "{elapsed:?}");
+}
+
+fn create_sync_block_chain(len: u32) -> Vec<api::en::SyncBlock> {
+    let mut prev_hash = H256::zero();
+    (0..len)
+        .map(|number| {
+            let block = create_sync_block(MiniblockNumber(number), prev_hash);
+            prev_hash = block.hash.unwrap();
+            block
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn verifying_consistent_blocks_backwards() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let fetcher = create_fetcher_for_blocks(create_sync_block_chain(10)).await;
+
+    fetcher
+        .verify_backwards(ctx, MiniblockNumber(9), MiniblockNumber(2))
+        .await
+        .unwrap();
+    fetcher
+        .verify_backwards(ctx, MiniblockNumber(5), MiniblockNumber(5))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn verifying_blocks_backwards_without_main_node_head() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let fetcher = create_fetcher_for_blocks(create_sync_block_chain(10)).await;
+    // Verification is a standalone tool, so it shouldn't rely on the main node head being tracked.
+    let fetcher = Fetcher {
+        sync_state: SyncState::default(),
+        ..fetcher
+    };
+
+    fetcher
+        .verify_backwards(ctx, MiniblockNumber(9), MiniblockNumber(2))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn verifying_blocks_backwards_with_invalid_range() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let fetcher = create_fetcher_for_blocks(create_sync_block_chain(10)).await;
+
+    let err = fetcher
+        .verify_backwards(ctx, MiniblockNumber(2), MiniblockNumber(9))
+        .await
+        .unwrap_err();
+    assert_matches!(
+        err,
+        VerificationError::InvalidRange { from, to }
+            if from == MiniblockNumber(2) && to == MiniblockNumber(9)
+    );
+}
+
+#[tokio::test]
+async fn verifying_blocks_backwards_with_broken_link() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let mut blocks = create_sync_block_chain(10);
+    // Replace block #5 with a block that has a different parent. Block #5 itself is internally consistent,
+    // but block #6 is no longer linked to it.
+    blocks[5] = create_sync_block(MiniblockNumber(5), H256::repeat_byte(0xff));
+    let fetcher = create_fetcher_for_blocks(blocks).await;
+
+    let err = fetcher
+        .verify_backwards(ctx, MiniblockNumber(9), MiniblockNumber(2))
+        .await
+        .unwrap_err();
+    assert_matches!(
+        err,
+        VerificationError::BrokenLink { number, .. } if number == MiniblockNumber(6)
+    );
+
+    // The range above the broken link is still consistent.
+    fetcher
+        .verify_backwards(ctx, MiniblockNumber(9), MiniblockNumber(6))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn genesis_monitor_fails_on_malformed_genesis() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let rng = &mut ctx.rng();
+    let setup = Setup::new(rng, 1);
+    let request_count = Arc::new(AtomicUsize::new(0));
+    let client = MockL2Client::new({
+        let request_count = request_count.clone();
+        move |method, _params| {
+            assert_eq!(method, "en_consensusGenesis");
+            request_count.fetch_add(1, Ordering::Relaxed);
+            Ok(serde_json::json!({ "malformed": true }))
+        }
+    });
+    let fetcher = create_fetcher(client).await;
+
+    let err = fetcher
+        .monitor_genesis(ctx, setup.genesis.clone())
+        .await
+        .unwrap_err();
+    let ctx::Error::Internal(err) = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert!(format!("{err:#}").contains("malformed genesis"), "{err:#}");
+    assert_eq!(
+        request_count.load(Ordering::Relaxed),
+        fetcher::MAX_MALFORMED_GENESIS_RESPONSES
+    );
+}
+
+#[test]
+fn genesis_cache_skips_deserializing_identical_genesis() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let rng = &mut ctx.rng();
+    let genesis = Setup::new(rng, 1).genesis;
+    let raw_genesis =
+        zksync_protobuf::serde::serialize(&genesis, serde_json::value::Serializer).unwrap();
+
+    let other_genesis = Setup::new(rng, 1).genesis;
+    let raw_other_genesis =
+        zksync_protobuf::serde::serialize(&other_genesis, serde_json::value::Serializer).unwrap();
+
+    let mut cache = fetcher::GenesisCache::default();
+    for _ in 0..3 {
+        let new_genesis = cache.check(raw_genesis.clone(), &genesis).unwrap();
+        assert_eq!(new_genesis, None);
+    }
+    // The cached raw genesis is neither deserialized nor compared again; otherwise, it would be reported
+    // as differing from `other_genesis`.
+    let new_genesis = cache.check(raw_genesis.clone(), &other_genesis).unwrap();
+    assert_eq!(new_genesis, None);
+
+    // Malformed responses are deserialized (and rejected) every time, and don't evict the cached genesis.
+    for _ in 0..2 {
+        let err = cache
+            .check(serde_json::json!({ "malformed": true }), &genesis)
+            .unwrap_err();
+        assert_matches!(err, fetcher::GenesisError::Malformed(_));
+    }
+    let new_genesis = cache.check(raw_genesis, &other_genesis).unwrap();
+    assert_eq!(new_genesis, None);
+
+    // A changed genesis is reported every time it's checked, since it's not cached.
+    for _ in 0..2 {
+        let new_genesis = cache.check(raw_other_genesis.clone(), &genesis).unwrap();
+        assert_eq!(new_genesis, Some(other_genesis.clone()));
+    }
+}
+
+/// Sink collecting all applied blocks.
+#[derive(Debug)]
+struct CollectingSink {
+    next: validator::BlockNumber,
+    blocks: Vec<FetchedBlock>,
+    prev_hash: Option<H256>,
+}
+
+#[async_trait::async_trait]
+impl BlockSink for CollectingSink {
+    fn next(&self) -> validator::BlockNumber {
+        self.next
+    }
+
+    fn prev_hash(&self) -> Option<H256> {
+        self.prev_hash
+    }
+
+    async fn apply(&mut self, block: FetchedBlock) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            u64::from(block.number.0) == self.next.0,
+            "unexpected block {:?}, expected {:?}",
+            block.number,
+            self.next
+        );
+        self.prev_hash = block.reference_hash;
+        self.blocks.push(block);
+        self.next = self.next.next();
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn fetching_blocks_into_custom_sink() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let fetcher = create_fetcher_for_blocks(create_sync_block_chain(10)).await;
+    fetcher.sync_state.set_main_node_block(MiniblockNumber(9));
+
+    let mut sink = CollectingSink {
+        next: validator::BlockNumber(3),
+        blocks: vec![],
+        prev_hash: None,
+    };
+    fetcher
+        .fetch_blocks_into(
+            ctx,
+            &mut sink,
+            validator::BlockNumber(3),
+            Some(validator::BlockNumber(8)),
+        )
+        .await
+        .unwrap();
+
+    let applied_numbers: Vec<_> = sink.blocks.iter().map(|block| block.number.0).collect();
+    assert_eq!(applied_numbers, [3, 4, 5, 6, 7]);
+    assert_eq!(sink.next, validator::BlockNumber(8));
+}
+
+/// Fetches 20 blocks into a sink that takes 20ms to apply each block. Returns the number of blocks
+/// requested from the main node past each block at the moment it was applied.
+async fn fetch_blocks_into_slow_sink(backpressure_threshold: Option<Duration>) -> Vec<u64> {
+    const BLOCK_COUNT: u32 = 20;
+
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let blocks = create_sync_block_chain(BLOCK_COUNT);
+    let max_requested = Arc::new(AtomicU64::new(0));
+    let client = MockL2Client::new({
+        let max_requested = max_requested.clone();
+        move |method, params| {
+            assert_eq!(method, "en_syncL2Block");
+            let (number, _): (MiniblockNumber, bool) = serde_json::from_value(params).unwrap();
+            max_requested.fetch_max(number.0.into(), Ordering::SeqCst);
+            Ok(serde_json::to_value(blocks.get(number.0 as usize)).unwrap())
+        }
+    });
+    let mut fetcher = create_fetcher(client).await;
+    fetcher.config = FetcherConfig {
+        concurrency_limit: NonZeroUsize::new(3).unwrap(),
+        backpressure_threshold,
+        ..FetcherConfig::default()
+    };
+    fetcher
+        .sync_state
+        .set_main_node_block(MiniblockNumber(BLOCK_COUNT - 1));
+
+    /// Sink that is slow to apply blocks.
+    #[derive(Debug)]
+    struct SlowSink {
+        inner: CollectingSink,
+        max_requested: Arc<AtomicU64>,
+        requested_ahead: Vec<u64>,
+    }
+
+    #[async_trait::async_trait]
+    impl BlockSink for SlowSink {
+        fn next(&self) -> validator::BlockNumber {
+            self.inner.next()
+        }
+
+        async fn apply(&mut self, block: FetchedBlock) -> anyhow::Result<()> {
+            let number = u64::from(block.number.0);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let max_requested = self.max_requested.load(Ordering::SeqCst);
+            self.requested_ahead.push(max_requested - number);
+            self.inner.apply(block).await
+        }
+    }
+
+    let mut sink = SlowSink {
+        inner: CollectingSink {
+            next: validator::BlockNumber(1),
+            blocks: vec![],
+            prev_hash: None,
+        },
+        max_requested,
+        requested_ahead: vec![],
+    };
+    fetcher
+        .fetch_blocks_into(
+            ctx,
+            &mut sink,
+            validator::BlockNumber(1),
+            Some(validator::BlockNumber(BLOCK_COUNT.into())),
+        )
+        .await
+        .unwrap();
+    assert_eq!(sink.inner.blocks.len(), BLOCK_COUNT as usize - 1);
+    sink.requested_ahead
+}
+
+#[tokio::test]
+async fn fetching_blocks_is_throttled_by_slow_sink() {
+    // Without backpressure, the fetcher keeps a full buffer of fetched blocks ahead of the sink.
+    let requested_ahead = fetch_blocks_into_slow_sink(None).await;
+    assert!(
+        requested_ahead[..10].iter().all(|&ahead| ahead > 1),
+        "{requested_ahead:?}"
+    );
+
+    // With backpressure, the fetcher drains the initial buffer and then fetches blocks one by one.
+    let requested_ahead = fetch_blocks_into_slow_sink(Some(Duration::from_millis(5))).await;
+    assert!(
+        requested_ahead[10..].iter().all(|&ahead| ahead <= 1),
+        "{requested_ahead:?}"
+    );
+}
+
+#[tokio::test]
+async fn fetching_blocks_waits_for_main_node_head() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let blocks = create_sync_block_chain(2);
+    let request_count = Arc::new(AtomicUsize::new(0));
+    let client = MockL2Client::new({
+        let request_count = request_count.clone();
+        move |method, params| {
+            assert_eq!(method, "en_syncL2Block");
+            request_count.fetch_add(1, Ordering::Relaxed);
+            let (number, _): (MiniblockNumber, bool) = serde_json::from_value(params).unwrap();
+            Ok(serde_json::to_value(blocks.get(number.0 as usize)).unwrap())
+        }
+    });
+    let fetcher = create_fetcher(client).await;
+    fetcher.sync_state.set_main_node_block(MiniblockNumber(0));
+
+    let mut sink = CollectingSink {
+        next: validator::BlockNumber(1),
+        blocks: vec![],
+        prev_hash: None,
+    };
+    scope::run!(ctx, |ctx, s| async {
+        let fetch = s.spawn(fetcher.fetch_blocks_into(
+            ctx,
+            &mut sink,
+            validator::BlockNumber(1),
+            Some(validator::BlockNumber(2)),
+        ));
+        // The block is not requested until the main node head reaches it.
+        ctx.sleep(time::Duration::milliseconds(100)).await?;
+        assert_eq!(request_count.load(Ordering::Relaxed), 0);
+
+        fetcher.sync_state.set_main_node_block(MiniblockNumber(1));
+        fetch.join(ctx).await
+    })
+    .await
+    .unwrap();
+    assert_eq!(request_count.load(Ordering::Relaxed), 1);
+    assert_eq!(sink.blocks.len(), 1);
+}
+
+#[tokio::test]
+async fn fetching_blocks_detects_reorg() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let mut blocks = create_sync_block_chain(10);
+    // Block #5 has a parent different from block #4 returned by the main node.
+    blocks[5] = create_sync_block(MiniblockNumber(5), H256::repeat_byte(0xff));
+    let fetcher = create_fetcher_for_blocks(blocks).await;
+    fetcher.sync_state.set_main_node_block(MiniblockNumber(9));
+
+    let mut sink = CollectingSink {
+        next: validator::BlockNumber(3),
+        blocks: vec![],
+        prev_hash: None,
+    };
+    let err = fetcher
+        .fetch_blocks_into(
+            ctx,
+            &mut sink,
+            validator::BlockNumber(3),
+            Some(validator::BlockNumber(8)),
+        )
+        .await
+        .unwrap_err();
+    let ctx::Error::Internal(err) = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    let err = err.downcast_ref::<ReorgDetected>().unwrap();
+    assert_eq!(err.number, MiniblockNumber(5));
+
+    // Blocks before the reorg are applied, and the mismatched block is not.
+    let applied_numbers: Vec<_> = sink.blocks.iter().map(|block| block.number.0).collect();
+    assert_eq!(applied_numbers, [3, 4]);
+}
+
+#[tokio::test]
+async fn fetching_blocks_detects_reorg_on_first_block() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let blocks = create_sync_block_chain(10);
+    let client = MockL2Client::new({
+        let blocks = blocks.clone();
+        move |method, params| {
+            if method == "en_syncL2BlockRange" {
+                let (from, to, _): (MiniblockNumber, MiniblockNumber, bool) =
+                    serde_json::from_value(params).unwrap();
+                let range: Vec<_> = blocks
+                    .iter()
+                    .filter(|block| (from..=to).contains(&block.number))
+                    .collect();
+                return Ok(serde_json::to_value(range).unwrap());
+            }
+            assert_eq!(method, "en_syncL2Block");
+            let (number, _): (MiniblockNumber, bool) = serde_json::from_value(params).unwrap();
+            Ok(serde_json::to_value(blocks.get(number.0 as usize)).unwrap())
+        }
+    });
+    let fetcher = create_fetcher(client).await;
+    fetcher.sync_state.set_main_node_block(MiniblockNumber(9));
+
+    // The local block #2 differs from the one returned by the main node, so the first fetched block doesn't link to it.
+    let mut sink = CollectingSink {
+        next: validator::BlockNumber(3),
+        blocks: vec![],
+        prev_hash: Some(H256::repeat_byte(0xff)),
+    };
+    let err = fetcher
+        .fetch_blocks_into(
+            ctx,
+            &mut sink,
+            validator::BlockNumber(3),
+            Some(validator::BlockNumber(8)),
+        )
+        .await
+        .unwrap_err();
+    let ctx::Error::Internal(err) = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    let err = err.downcast_ref::<ReorgDetected>().unwrap();
+    assert_eq!(err.number, MiniblockNumber(3));
+    assert!(sink.blocks.is_empty());
+
+    // The same check is performed when fetching blocks in ranges.
+    let err = fetcher
+        .fetch_block_ranges_into(
+            ctx,
+            &mut sink,
+            Some(validator::BlockNumber(8)),
+            NonZeroUsize::new(4).unwrap(),
+        )
+        .await
+        .unwrap_err();
+    let ctx::Error::Internal(err) = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(
+        err.downcast_ref::<ReorgDetected>().unwrap().number,
+        MiniblockNumber(3)
+    );
+    assert!(sink.blocks.is_empty());
+
+    // If the local block matches the main node, blocks are applied.
+    sink.prev_hash = blocks[2].hash;
+    fetcher
+        .fetch_blocks_into(
+            ctx,
+            &mut sink,
+            validator::BlockNumber(3),
+            Some(validator::BlockNumber(8)),
+        )
+        .await
+        .unwrap();
+    let applied_numbers: Vec<_> = sink.blocks.iter().map(|block| block.number.0).collect();
+    assert_eq!(applied_numbers, [3, 4, 5, 6, 7]);
+}
+
+#[tokio::test]
+async fn fetching_blocks_fails_on_out_of_order_block() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let blocks = create_sync_block_chain(10);
+    let client = MockL2Client::new(move |method, params| {
+        assert_eq!(method, "en_syncL2Block");
+        let (number, _): (MiniblockNumber, bool) = serde_json::from_value(params).unwrap();
+        // Swap blocks #3 and #4 to emulate out-of-order delivery.
+        let number = match number.0 {
+            3 => 4,
+            4 => 3,
+            number => number,
+        };
+        Ok(serde_json::to_value(blocks.get(number as usize)).unwrap())
+    });
+    let fetcher = create_fetcher(client).await;
+    fetcher.sync_state.set_main_node_block(MiniblockNumber(9));
+
+    let mut sink = CollectingSink {
+        next: validator::BlockNumber(1),
+        blocks: vec![],
+        prev_hash: None,
+    };
+    let err = fetcher
+        .fetch_blocks_into(
+            ctx,
+            &mut sink,
+            validator::BlockNumber(1),
+            Some(validator::BlockNumber(8)),
+        )
+        .await
+        .unwrap_err();
+    let ctx::Error::Internal(err) = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    let err = err.downcast::<BlockOutOfOrder>().unwrap();
+    assert_eq!(err.expected, MiniblockNumber(3));
+    assert_eq!(err.got, MiniblockNumber(4));
+
+    // Blocks before the out-of-order block are applied, and the out-of-order block is not.
+    let applied_numbers: Vec<_> = sink.blocks.iter().map(|block| block.number.0).collect();
+    assert_eq!(applied_numbers, [1, 2]);
+}
+
+#[tokio::test]
+async fn fetching_block_ranges() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let blocks = create_sync_block_chain(10);
+    let requested_ranges = Arc::new(std::sync::Mutex::new(vec![]));
+    let client = MockL2Client::new({
+        let requested_ranges = requested_ranges.clone();
+        move |method, params| {
+            assert_eq!(method, "en_syncL2BlockRange");
+            let (from, to, _): (MiniblockNumber, MiniblockNumber, bool) =
+                serde_json::from_value(params).unwrap();
+            requested_ranges.lock().unwrap().push((from.0, to.0));
+            // Return blocks in the reverse order to check that the fetcher sorts them.
+            let range: Vec<_> = blocks
+                .iter()
+                .filter(|block| (from..=to).contains(&block.number))
+                .rev()
+                .collect();
+            Ok(serde_json::to_value(range).unwrap())
+        }
+    });
+    let fetcher = create_fetcher(client).await;
+    fetcher.sync_state.set_main_node_block(MiniblockNumber(9));
+
+    let mut sink = CollectingSink {
+        next: validator::BlockNumber(0),
+        blocks: vec![],
+        prev_hash: None,
+    };
+    fetcher
+        .fetch_block_ranges_into(
+            ctx,
+            &mut sink,
+            Some(validator::BlockNumber(10)),
+            NonZeroUsize::new(4).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let applied_numbers: Vec<_> = sink.blocks.iter().map(|block| block.number.0).collect();
+    assert_eq!(applied_numbers, (0..10).collect::<Vec<_>>());
+    assert_eq!(*requested_ranges.lock().unwrap(), [(0, 3), (4, 7), (8, 9)]);
+}
+
+#[tokio::test]
+async fn fetching_block_ranges_falls_back_to_single_blocks() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let blocks = create_sync_block_chain(10);
+    let client = MockL2Client::new(move |method, params| {
+        if method == "en_syncL2Block" {
+            let (number, _): (MiniblockNumber, bool) = serde_json::from_value(params).unwrap();
+            return Ok(serde_json::to_value(blocks.get(number.0 as usize)).unwrap());
+        }
+        let err = ErrorObject::owned(
+            ErrorCode::MethodNotFound.code(),
+            format!("unknown method: {method}"),
+            None::<()>,
+        );
+        Err(RpcError::Call(err))
+    });
+    let fetcher = create_fetcher(client).await;
+    fetcher.sync_state.set_main_node_block(MiniblockNumber(9));
+
+    let mut sink = CollectingSink {
+        next: validator::BlockNumber(3),
+        blocks: vec![],
+        prev_hash: None,
+    };
+    fetcher
+        .fetch_block_ranges_into(
+            ctx,
+            &mut sink,
+            Some(validator::BlockNumber(8)),
+            NonZeroUsize::new(4).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let applied_numbers: Vec<_> = sink.blocks.iter().map(|block| block.number.0).collect();
+    assert_eq!(applied_numbers, [3, 4, 5, 6, 7]);
+}
+
+#[tokio::test]
+async fn fetching_blocks_from_start_after_next_block_to_apply() {
+    let ctx = &ctx::test_root(&ctx::RealClock);
+    let fetcher = create_fetcher_for_blocks(create_sync_block_chain(10)).await;
+    fetcher.sync_state.set_main_node_block(MiniblockNumber(9));
+
+    let mut sink = CollectingSink {
+        next: validator::BlockNumber(3),
+        blocks: vec![],
+        prev_hash: None,
+    };
+    let err = fetcher
+        .fetch_blocks_into(
+            ctx,
+            &mut sink,
+            validator::BlockNumber(5),
+            Some(validator::BlockNumber(8)),
+        )
+        .await
+        .unwrap_err();
+    let ctx::Error::Internal(err) = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert!(err.to_string().contains("cannot start fetching"), "{err}");
+    assert!(sink.blocks.is_empty());
+}
+
+#[test]
+fn fetcher_health_transitions() {
+    const MAX_STALL: Duration = Duration::from_secs(10);
+
+    let health = FetcherHealth::new(MAX_STALL);
+    let start = Instant::now();
+    assert_matches!(health.health_at(start).status(), HealthStatus::NotReady);
+
+    health.set_main_node_block(MiniblockNumber(5), start);
+    assert_matches!(health.health_at(start).status(), HealthStatus::Ready);
+    let later = start + MAX_STALL * 2;
+    assert_matches!(health.health_at(later).status(), HealthStatus::NotReady);
+
+    health.set_fetched_block(MiniblockNumber(3), later);
+    assert_matches!(health.health_at(later).status(), HealthStatus::Ready);
+    // The fetcher is not stalled if it has caught up with the main node, even if it doesn't fetch new blocks.
+    health.set_fetched_block(MiniblockNumber(5), later);
+    let much_later = later + MAX_STALL * 2;
+    assert_matches!(health.health_at(much_later).status(), HealthStatus::Ready);
+
+    // The stall is measured from the moment the fetcher started lagging, rather than from the last fetched block.
+    health.set_main_node_block(MiniblockNumber(6), much_later);
+    assert_matches!(health.health_at(much_later).status(), HealthStatus::Ready);
+    let lagging_for_a_while = much_later + MAX_STALL / 2;
+    assert_matches!(
+        health.health_at(lagging_for_a_while).status(),
+        HealthStatus::Ready
+    );
+    // Main node head updates don't restart the clock while the fetcher is lagging.
+    health.set_main_node_block(MiniblockNumber(7), lagging_for_a_while);
+    let stalled_at = much_later + MAX_STALL * 2;
+    assert_matches!(
+        health.health_at(stalled_at).status(),
+        HealthStatus::NotReady
+    );
+}
@@ -1236,7 +1236,14 @@ async fn build_tx_sender(
     .with_sealer(Arc::new(sequencer_sealer));
 
     let max_concurrency = web3_json_config.vm_concurrency_limit();
-    let (vm_concurrency_limiter, vm_barrier) = VmConcurrencyLimiter::new(max_concurrency);
+    let reserved_permits = web3_json_config.vm_concurrency_reserved_permits();
+    let (vm_concurrency_limiter, vm_barrier) = VmConcurrencyLimiter::with_reserved_permits(
+        max_concurrency,
+        reserved_permits,
+        web3_json_config.vm_slow_acquire_threshold(),
+    );
+    let vm_concurrency_limiter =
+        vm_concurrency_limiter.with_max_queue_depth(web3_json_config.vm_max_queue_depth);
 
     let batch_fee_input_provider =
         ApiFeeInputProvider::new(batch_fee_model_input_provider, replica_pool);
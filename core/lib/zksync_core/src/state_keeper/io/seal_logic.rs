@@ -4,7 +4,6 @@
 use std::time::{Duration, Instant};
 
 use anyhow::Context as _;
-use itertools::Itertools;
 use multivm::utils::{get_max_batch_gas_limit, get_max_gas_per_pubdata_byte};
 use zksync_dal::{Connection, Core, CoreDal};
 use zksync_shared_metrics::{BlockStage, MiniblockStage, APP_METRICS};
@@ -14,16 +13,14 @@ use zksync_types::{
     helpers::unix_timestamp_ms,
     l1::L1Tx,
     l2::L2Tx,
-    l2_to_l1_log::{SystemL2ToL1Log, UserL2ToL1Log},
     protocol_upgrade::ProtocolUpgradeTx,
-    storage_writes_deduplicator::{ModifiedSlot, StorageWritesDeduplicator},
     tx::{
         tx_execution_info::DeduplicatedWritesMetrics, IncludedTxLocation,
         TransactionExecutionResult,
     },
     zk_evm_types::LogQuery,
     AccountTreeId, Address, ExecuteTransactionCommon, L1BlockNumber, ProtocolVersionId, StorageKey,
-    StorageLog, StorageLogQuery, Transaction, VmEvent, H256,
+    StorageLogQuery, Transaction, H256,
 };
 use zksync_utils::u256_to_h256;
 
@@ -315,6 +312,7 @@ impl MiniblockSealCommand {
         self.ensure_valid_miniblock(is_fictive)
             .context("miniblock is invalid")?;
 
+        let commit_batch = self.miniblock.to_commit_batch();
         let mut transaction = storage.start_transaction().await?;
         if self.pre_insert_txs {
             let progress = MINIBLOCK_METRICS.start(MiniblockSealStage::PreInsertTxs, is_fictive);
@@ -374,14 +372,14 @@ impl MiniblockSealCommand {
             .transactions_dal()
             .mark_txs_as_executed_in_miniblock(
                 miniblock_number,
-                &self.miniblock.executed_transactions,
+                commit_batch.transactions,
                 self.base_fee_per_gas.into(),
             )
             .await?;
         progress.observe(self.miniblock.executed_transactions.len());
 
         let progress = MINIBLOCK_METRICS.start(MiniblockSealStage::InsertStorageLogs, is_fictive);
-        let write_logs = self.extract_deduplicated_write_logs(is_fictive);
+        let write_logs = self.resolve_tx_hashes(commit_batch.storage_writes, is_fictive);
         let write_log_count: usize = write_logs.iter().map(|(_, logs)| logs.len()).sum();
         transaction
             .storage_logs_dal()
@@ -401,7 +399,7 @@ impl MiniblockSealCommand {
         }
 
         let progress = MINIBLOCK_METRICS.start(MiniblockSealStage::InsertFactoryDeps, is_fictive);
-        let new_factory_deps = &self.miniblock.new_factory_deps;
+        let new_factory_deps = commit_batch.factory_deps;
         let new_factory_deps_count = new_factory_deps.len();
         if !new_factory_deps.is_empty() {
             transaction
@@ -422,7 +420,7 @@ impl MiniblockSealCommand {
         progress.observe(added_tokens_len);
 
         let progress = MINIBLOCK_METRICS.start(MiniblockSealStage::ExtractEvents, is_fictive);
-        let miniblock_events = self.extract_events(is_fictive);
+        let miniblock_events = self.resolve_tx_locations(commit_batch.events, is_fictive);
         let miniblock_event_count: usize = miniblock_events
             .iter()
             .map(|(_, events)| events.len())
@@ -437,8 +435,10 @@ impl MiniblockSealCommand {
 
         let progress = MINIBLOCK_METRICS.start(MiniblockSealStage::ExtractL2ToL1Logs, is_fictive);
 
-        let system_l2_to_l1_logs = self.extract_system_l2_to_l1_logs(is_fictive);
-        let user_l2_to_l1_logs = self.extract_user_l2_to_l1_logs(is_fictive);
+        let system_l2_to_l1_logs =
+            self.resolve_tx_locations(commit_batch.system_l2_to_l1_logs, is_fictive);
+        let user_l2_to_l1_logs =
+            self.resolve_tx_locations(commit_batch.user_l2_to_l1_logs, is_fictive);
 
         let system_l2_to_l1_log_count: usize = system_l2_to_l1_logs
             .iter()
@@ -510,44 +510,22 @@ impl MiniblockSealCommand {
         Ok(())
     }
 
-    fn extract_deduplicated_write_logs(&self, is_fictive: bool) -> Vec<(H256, Vec<StorageLog>)> {
-        let mut storage_writes_deduplicator = StorageWritesDeduplicator::new();
-        storage_writes_deduplicator.apply(
-            self.miniblock
-                .storage_logs
-                .iter()
-                .filter(|log| log.log_query.rw_flag),
-        );
-        let deduplicated_logs = storage_writes_deduplicator.into_modified_key_values();
-
-        deduplicated_logs
-            .into_iter()
-            .map(
-                |(
-                    key,
-                    ModifiedSlot {
-                        value, tx_index, ..
-                    },
-                )| (tx_index, (key, value)),
-            )
-            .sorted_by_key(|(tx_index, _)| *tx_index)
-            .group_by(|(tx_index, _)| *tx_index)
+    /// Replaces transaction indices in the L1 batch with transaction hashes in data grouped by transactions.
+    fn resolve_tx_hashes<T>(
+        &self,
+        groups: Vec<(u32, Vec<T>)>,
+        is_fictive: bool,
+    ) -> Vec<(H256, Vec<T>)> {
+        groups
             .into_iter()
-            .map(|(tx_index, logs)| {
+            .map(|(tx_index, entries)| {
                 let tx_hash = if is_fictive {
                     assert_eq!(tx_index as usize, self.first_tx_index);
                     H256::zero()
                 } else {
                     self.transaction(tx_index as usize).hash()
                 };
-                (
-                    tx_hash,
-                    logs.into_iter()
-                        .map(|(_, (key, value))| {
-                            StorageLog::new_write_log(key, u256_to_h256(value))
-                        })
-                        .collect(),
-                )
+                (tx_hash, entries)
             })
             .collect()
     }
@@ -557,18 +535,13 @@ impl MiniblockSealCommand {
         &tx_result.transaction
     }
 
-    fn extract_events(&self, is_fictive: bool) -> Vec<(IncludedTxLocation, Vec<&VmEvent>)> {
-        self.group_by_tx_location(&self.miniblock.events, is_fictive, |event| event.location.1)
-    }
-
-    fn group_by_tx_location<'a, T>(
-        &'a self,
-        entries: &'a [T],
+    /// Replaces transaction indices in the L1 batch with transaction locations in data grouped by transactions.
+    fn resolve_tx_locations<T>(
+        &self,
+        groups: Vec<(u32, Vec<T>)>,
         is_fictive: bool,
-        tx_location: impl Fn(&T) -> u32,
-    ) -> Vec<(IncludedTxLocation, Vec<&'a T>)> {
-        let grouped_entries = entries.iter().group_by(|&entry| tx_location(entry));
-        let grouped_entries = grouped_entries.into_iter().map(|(tx_index, entries)| {
+    ) -> Vec<(IncludedTxLocation, Vec<T>)> {
+        let grouped_entries = groups.into_iter().map(|(tx_index, entries)| {
             let (tx_hash, tx_initiator_address) = if is_fictive {
                 assert_eq!(tx_index as usize, self.first_tx_index);
                 (H256::zero(), Address::zero())
@@ -582,29 +555,11 @@ impl MiniblockSealCommand {
                 tx_index_in_miniblock: tx_index - self.first_tx_index as u32,
                 tx_initiator_address,
             };
-            (location, entries.collect())
+            (location, entries)
         });
         grouped_entries.collect()
     }
 
-    fn extract_system_l2_to_l1_logs(
-        &self,
-        is_fictive: bool,
-    ) -> Vec<(IncludedTxLocation, Vec<&SystemL2ToL1Log>)> {
-        self.group_by_tx_location(&self.miniblock.system_l2_to_l1_logs, is_fictive, |log| {
-            u32::from(log.0.tx_number_in_block)
-        })
-    }
-
-    fn extract_user_l2_to_l1_logs(
-        &self,
-        is_fictive: bool,
-    ) -> Vec<(IncludedTxLocation, Vec<&UserL2ToL1Log>)> {
-        self.group_by_tx_location(&self.miniblock.user_l2_to_l1_logs, is_fictive, |log| {
-            u32::from(log.0.tx_number_in_block)
-        })
-    }
-
     fn report_transaction_metrics(&self) {
         const SLOW_INCLUSION_DELAY: Duration = Duration::from_secs(600);
 
@@ -639,9 +594,7 @@ impl MiniblockSealCommand {
     fn report_miniblock_metrics(&self, started_at: Instant) {
         let miniblock_number = self.miniblock.number;
 
-        MINIBLOCK_METRICS
-            .transactions_in_miniblock
-            .observe(self.miniblock.executed_transactions.len());
+        self.miniblock.record_seal_metrics();
         MINIBLOCK_METRICS.sealed_time.observe(started_at.elapsed());
 
         let miniblock_latency =
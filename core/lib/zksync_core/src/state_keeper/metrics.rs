@@ -190,6 +190,8 @@ pub(super) enum L1BatchSealStage {
 const COUNT_BUCKETS: Buckets = Buckets::values(&[
     10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1_000.0, 2_000.0, 5_000.0, 10_000.0, 20_000.0, 50_000.0,
 ]);
+/// Buckets for encoding sizes of miniblocks (in bytes).
+const ENCODING_SIZE_BUCKETS: Buckets = Buckets::exponential(256.0..=16_777_216.0, 4.0);
 /// Buckets for sealing deltas for L1 batches (in seconds). The expected delta is approximately 1 minute.
 const L1_BATCH_SEAL_DELTA_BUCKETS: Buckets = Buckets::values(&[
     0.1, 0.5, 1.0, 5.0, 10.0, 20.0, 30.0, 40.0, 60.0, 90.0, 120.0, 180.0, 240.0, 300.0,
@@ -288,6 +290,27 @@ pub(super) struct MiniblockMetrics {
     /// Number of transactions in a single miniblock.
     #[metrics(buckets = Buckets::linear(0.0..=50.0, 5.0))]
     pub transactions_in_miniblock: Histogram<usize>,
+    /// Number of L1-originated (priority) transactions in a single miniblock.
+    #[metrics(buckets = Buckets::linear(0.0..=50.0, 5.0))]
+    pub priority_transactions_in_miniblock: Histogram<usize>,
+    /// Number of user (i.e., L2) transactions in a single miniblock.
+    #[metrics(buckets = Buckets::linear(0.0..=50.0, 5.0))]
+    pub user_transactions_in_miniblock: Histogram<usize>,
+    /// Number of events in a single miniblock.
+    #[metrics(buckets = COUNT_BUCKETS)]
+    pub events_in_miniblock: Histogram<usize>,
+    /// Number of storage logs in a single miniblock.
+    #[metrics(buckets = COUNT_BUCKETS)]
+    pub storage_logs_in_miniblock: Histogram<usize>,
+    /// Number of new factory deps in a single miniblock.
+    #[metrics(buckets = Buckets::exponential(1.0..=1_024.0, 4.0))]
+    pub factory_deps_in_miniblock: Histogram<usize>,
+    /// Total bootloader encoding size of transactions in a single miniblock.
+    #[metrics(buckets = ENCODING_SIZE_BUCKETS)]
+    pub txs_encoding_size: Histogram<usize>,
+    /// Total encoding size of the consensus payload of a single miniblock.
+    #[metrics(buckets = ENCODING_SIZE_BUCKETS)]
+    pub payload_encoding_size: Histogram<usize>,
     /// Total latency of sealing a miniblock.
     #[metrics(buckets = Buckets::LATENCIES)]
     pub sealed_time: Histogram<Duration>,
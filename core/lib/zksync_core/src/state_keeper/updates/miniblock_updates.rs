@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use itertools::Itertools;
 use multivm::{
     interface::{ExecutionResult, L2BlockEnv, VmExecutionResultAndLogs},
     vm_latest::TransactionVmExt,
@@ -8,11 +9,64 @@ use zksync_types::{
     block::{BlockGasCount, MiniblockHasher},
     event::extract_bytecodes_marked_as_known,
     l2_to_l1_log::{SystemL2ToL1Log, UserL2ToL1Log},
+    storage_writes_deduplicator::{ModifiedSlot, StorageWritesDeduplicator},
     tx::{tx_execution_info::TxExecutionStatus, ExecutionMetrics, TransactionExecutionResult},
     vm_trace::Call,
-    MiniblockNumber, ProtocolVersionId, StorageLogQuery, Transaction, VmEvent, H256,
+    ExecuteTransactionCommon, MiniblockNumber, ProtocolVersionId, StorageLog, StorageLogQuery,
+    Transaction, VmEvent, H256,
 };
-use zksync_utils::bytecode::{hash_bytecode, CompressedBytecodeInfo};
+use zksync_utils::{
+    bytecode::{hash_bytecode, CompressedBytecodeInfo},
+    u256_to_h256,
+};
+
+use crate::state_keeper::metrics::MINIBLOCK_METRICS;
+
+/// Miniblock data prepared for persisting in a single DB transaction; produced by
+/// [`MiniblockUpdates::to_commit_batch()`]. Per-transaction data is grouped by the index of the transaction
+/// in the L1 batch, in the ascending order of indices.
+#[derive(Debug)]
+pub(crate) struct CommitBatch<'a> {
+    pub transactions: &'a [TransactionExecutionResult],
+    pub events: Vec<(u32, Vec<&'a VmEvent>)>,
+    /// Storage writes deduplicated within the miniblock.
+    pub storage_writes: Vec<(u32, Vec<StorageLog>)>,
+    pub factory_deps: &'a HashMap<H256, Vec<u8>>,
+    pub system_l2_to_l1_logs: Vec<(u32, Vec<&'a SystemL2ToL1Log>)>,
+    pub user_l2_to_l1_logs: Vec<(u32, Vec<&'a UserL2ToL1Log>)>,
+}
+
+/// Parent of a miniblock as seen at the time the miniblock was started.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ParentMiniblock {
+    number: MiniblockNumber,
+    timestamp: u64,
+    hash: H256,
+}
+
+/// Inconsistency between a miniblock and its parent, which would lead to an invalid VM env.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub(crate) enum MiniblockEnvError {
+    #[error("miniblock #{number} doesn't follow parent miniblock #{parent_number}")]
+    NonSequentialNumber {
+        number: MiniblockNumber,
+        parent_number: MiniblockNumber,
+    },
+    #[error(
+        "previous block hash {prev_block_hash:?} of miniblock #{number} differs from the parent hash {parent_hash:?}"
+    )]
+    PrevBlockHashMismatch {
+        number: MiniblockNumber,
+        prev_block_hash: H256,
+        parent_hash: H256,
+    },
+    #[error("timestamp {timestamp} of miniblock #{number} is less than the parent timestamp {parent_timestamp}")]
+    DecreasingTimestamp {
+        number: MiniblockNumber,
+        timestamp: u64,
+        parent_timestamp: u64,
+    },
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct MiniblockUpdates {
@@ -32,6 +86,8 @@ pub struct MiniblockUpdates {
     pub prev_block_hash: H256,
     pub virtual_blocks: u32,
     pub protocol_version: ProtocolVersionId,
+    /// Parent of this miniblock if it was started via [`Self::next_miniblock()`].
+    parent: Option<ParentMiniblock>,
 }
 
 impl MiniblockUpdates {
@@ -58,9 +114,34 @@ impl MiniblockUpdates {
             prev_block_hash,
             virtual_blocks,
             protocol_version,
+            parent: None,
         }
     }
 
+    /// Creates updates for the miniblock following this one. This miniblock is remembered as the parent,
+    /// so that the consistency of the miniblock env can be checked (see [`Self::check_parent()`]).
+    pub(crate) fn next_miniblock(
+        &self,
+        timestamp: u64,
+        virtual_blocks: u32,
+        protocol_version: ProtocolVersionId,
+    ) -> Self {
+        let hash = self.get_miniblock_hash();
+        let mut next = Self::new(
+            timestamp,
+            self.number + 1,
+            hash,
+            virtual_blocks,
+            protocol_version,
+        );
+        next.parent = Some(ParentMiniblock {
+            number: self.number,
+            timestamp: self.timestamp,
+            hash,
+        });
+        next
+    }
+
     pub(crate) fn extend_from_fictive_transaction(
         &mut self,
         result: VmExecutionResultAndLogs,
@@ -116,24 +197,28 @@ impl MiniblockUpdates {
             .map(|bytecode| (hash_bytecode(bytecode), bytecode))
             .collect();
 
-        // Save all bytecodes that were marked as known on the bootloader
-        let known_bytecodes = saved_factory_deps.into_iter().map(|bytecode_hash| {
-            let bytecode = tx_factory_deps.get(&bytecode_hash).unwrap_or_else(|| {
-                panic!(
-                    "Failed to get factory deps on tx: bytecode hash: {:?}, tx hash: {}",
-                    bytecode_hash,
-                    tx.hash()
-                )
-            });
-            (bytecode_hash, bytecode.to_vec())
-        });
+        // Save all bytecodes that were marked as known on the bootloader. A bytecode may be already published
+        // by an earlier transaction in the miniblock, in which case the transaction doesn't need to supply it.
+        let known_bytecodes: Vec<_> = saved_factory_deps
+            .into_iter()
+            .filter(|bytecode_hash| !self.new_factory_deps.contains_key(bytecode_hash))
+            .map(|bytecode_hash| {
+                let bytecode = tx_factory_deps.get(&bytecode_hash).unwrap_or_else(|| {
+                    panic!(
+                        "Failed to get factory deps on tx: bytecode hash: {:?}, tx hash: {}",
+                        bytecode_hash,
+                        tx.hash()
+                    )
+                });
+                (bytecode_hash, bytecode.to_vec())
+            })
+            .collect();
         self.new_factory_deps.extend(known_bytecodes);
 
         self.l1_gas_count += tx_l1_gas_this_tx;
         self.block_execution_metrics += execution_metrics;
         self.txs_encoding_size += tx.bootloader_encoding_size();
-        self.payload_encoding_size +=
-            zksync_protobuf::repr::encode::<zksync_dal::consensus::proto::Transaction>(&tx).len();
+        self.payload_encoding_size += zksync_dal::consensus::encoded_transaction_len(&tx);
         self.storage_logs
             .extend(tx_execution_result.logs.storage_logs);
 
@@ -159,7 +244,139 @@ impl MiniblockUpdates {
         digest.finalize(self.protocol_version)
     }
 
+    /// Returns L1-originated (priority) transactions executed in this miniblock, in execution order.
+    pub(crate) fn priority_transactions(
+        &self,
+    ) -> impl Iterator<Item = &TransactionExecutionResult> + '_ {
+        self.executed_transactions
+            .iter()
+            .filter(|tx_result| tx_result.transaction.is_l1())
+    }
+
+    /// Returns the number of L1-originated (priority) transactions executed in this miniblock.
+    pub(crate) fn priority_tx_count(&self) -> usize {
+        self.priority_transactions().count()
+    }
+
+    /// Returns the number of user transactions executed in this miniblock, i.e. L2 transactions submitted via the API.
+    /// L1-originated (priority) and protocol upgrade transactions are not counted; the fictive transaction
+    /// is not counted either since it's never included into `executed_transactions`.
+    pub(crate) fn user_transaction_count(&self) -> usize {
+        self.executed_transactions
+            .iter()
+            .filter(|tx_result| {
+                matches!(
+                    tx_result.transaction.common_data,
+                    ExecuteTransactionCommon::L2(_)
+                )
+            })
+            .count()
+    }
+
+    /// Records the composition of this miniblock (numbers of transactions, events etc.) to metrics.
+    /// Should be called once, when the miniblock is sealed.
+    pub(crate) fn record_seal_metrics(&self) {
+        MINIBLOCK_METRICS
+            .transactions_in_miniblock
+            .observe(self.executed_transactions.len());
+        MINIBLOCK_METRICS
+            .priority_transactions_in_miniblock
+            .observe(self.priority_tx_count());
+        MINIBLOCK_METRICS
+            .user_transactions_in_miniblock
+            .observe(self.user_transaction_count());
+        MINIBLOCK_METRICS
+            .events_in_miniblock
+            .observe(self.events.len());
+        MINIBLOCK_METRICS
+            .storage_logs_in_miniblock
+            .observe(self.storage_logs.len());
+        MINIBLOCK_METRICS
+            .factory_deps_in_miniblock
+            .observe(self.new_factory_deps.len());
+        MINIBLOCK_METRICS
+            .txs_encoding_size
+            .observe(self.txs_encoding_size);
+        MINIBLOCK_METRICS
+            .payload_encoding_size
+            .observe(self.payload_encoding_size);
+    }
+
+    /// Prepares all data of this miniblock that should be persisted when sealing it.
+    pub(crate) fn to_commit_batch(&self) -> CommitBatch<'_> {
+        let mut deduplicator = StorageWritesDeduplicator::new();
+        deduplicator.apply(self.storage_logs.iter().filter(|log| log.log_query.rw_flag));
+        let storage_writes = deduplicator
+            .into_modified_key_values()
+            .into_iter()
+            .map(
+                |(
+                    key,
+                    ModifiedSlot {
+                        value, tx_index, ..
+                    },
+                )| {
+                    let log = StorageLog::new_write_log(key, u256_to_h256(value));
+                    (u32::from(tx_index), log)
+                },
+            )
+            .sorted_by_key(|(tx_index, _)| *tx_index)
+            .group_by(|(tx_index, _)| *tx_index)
+            .into_iter()
+            .map(|(tx_index, logs)| (tx_index, logs.map(|(_, log)| log).collect()))
+            .collect();
+
+        CommitBatch {
+            transactions: &self.executed_transactions,
+            events: group_by_tx_index(&self.events, |event| event.location.1),
+            storage_writes,
+            factory_deps: &self.new_factory_deps,
+            system_l2_to_l1_logs: group_by_tx_index(&self.system_l2_to_l1_logs, |log| {
+                u32::from(log.0.tx_number_in_block)
+            }),
+            user_l2_to_l1_logs: group_by_tx_index(&self.user_l2_to_l1_logs, |log| {
+                u32::from(log.0.tx_number_in_block)
+            }),
+        }
+    }
+
+    /// Checks that this miniblock follows its parent: its number is the next one after the parent, the previous block hash
+    /// is the parent hash, and the timestamp doesn't decrease. Always succeeds if the parent is unknown.
+    pub(crate) fn check_parent(&self) -> Result<(), MiniblockEnvError> {
+        let Some(parent) = &self.parent else {
+            return Ok(());
+        };
+        if self.number != parent.number + 1 {
+            return Err(MiniblockEnvError::NonSequentialNumber {
+                number: self.number,
+                parent_number: parent.number,
+            });
+        }
+        if self.prev_block_hash != parent.hash {
+            return Err(MiniblockEnvError::PrevBlockHashMismatch {
+                number: self.number,
+                prev_block_hash: self.prev_block_hash,
+                parent_hash: parent.hash,
+            });
+        }
+        if self.timestamp < parent.timestamp {
+            return Err(MiniblockEnvError::DecreasingTimestamp {
+                number: self.number,
+                timestamp: self.timestamp,
+                parent_timestamp: parent.timestamp,
+            });
+        }
+        Ok(())
+    }
+
     pub(crate) fn get_miniblock_env(&self) -> L2BlockEnv {
+        // An inconsistent env would only be caught much later, e.g. as a state root mismatch.
+        let parent_check = self.check_parent();
+        debug_assert!(
+            parent_check.is_ok(),
+            "inconsistent miniblock env: {parent_check:?}"
+        );
+
         L2BlockEnv {
             number: self.number.0,
             timestamp: self.timestamp,
@@ -169,12 +386,66 @@ impl MiniblockUpdates {
     }
 }
 
+/// Groups consecutive `entries` by the transaction index in the L1 batch.
+fn group_by_tx_index<T>(entries: &[T], tx_index: impl Fn(&T) -> u32) -> Vec<(u32, Vec<&T>)> {
+    let groups = entries.iter().group_by(|&entry| tx_index(entry));
+    groups
+        .into_iter()
+        .map(|(tx_index, entries)| (tx_index, entries.collect()))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
+    use assert_matches::assert_matches;
     use multivm::vm_latest::TransactionVmExt;
+    use zksync_types::{
+        l1::{L1Tx, L1TxCommonData},
+        l2_to_l1_log::L2ToL1Log,
+        protocol_upgrade::{ProtocolUpgradeTx, ProtocolUpgradeTxCommonData},
+        web3::signing::keccak256,
+        Address, Execute, L1BatchNumber, PriorityOpId, KNOWN_CODES_STORAGE_ADDRESS, U256,
+    };
 
     use super::*;
-    use crate::state_keeper::tests::{create_execution_result, create_transaction};
+    use crate::{
+        state_keeper::tests::{create_execution_result, create_transaction, Query},
+        utils::testonly::create_miniblock,
+    };
+
+    fn create_l1_transaction(serial_id: u64) -> Transaction {
+        L1Tx {
+            execute: Execute::default(),
+            common_data: L1TxCommonData {
+                serial_id: PriorityOpId(serial_id),
+                canonical_tx_hash: H256::from_low_u64_be(serial_id),
+                ..L1TxCommonData::default()
+            },
+            received_timestamp_ms: 0,
+        }
+        .into()
+    }
+
+    fn create_accumulator() -> MiniblockUpdates {
+        MiniblockUpdates::new(
+            0,
+            MiniblockNumber(0),
+            H256::random(),
+            0,
+            ProtocolVersionId::latest(),
+        )
+    }
+
+    fn push_tx(accumulator: &mut MiniblockUpdates, tx: Transaction) {
+        accumulator.extend_from_executed_transaction(
+            tx,
+            create_execution_result(0, []),
+            BlockGasCount::default(),
+            ExecutionMetrics::default(),
+            vec![],
+            vec![],
+        );
+    }
 
     #[test]
     fn apply_empty_l2_tx() {
@@ -210,4 +481,244 @@ mod tests {
         assert_eq!(accumulator.txs_encoding_size, bootloader_encoding_size);
         assert_eq!(accumulator.payload_encoding_size, payload_encoding_size);
     }
+
+    #[test]
+    fn priority_transactions_in_mixed_block() {
+        let mut accumulator = create_accumulator();
+        let l1_txs = [create_l1_transaction(1), create_l1_transaction(2)];
+        push_tx(&mut accumulator, create_transaction(10, 100));
+        push_tx(&mut accumulator, l1_txs[0].clone());
+        push_tx(&mut accumulator, create_transaction(10, 100));
+        push_tx(&mut accumulator, l1_txs[1].clone());
+
+        assert_eq!(accumulator.executed_transactions.len(), 4);
+        assert_eq!(accumulator.priority_tx_count(), 2);
+        let priority_hashes: Vec<_> = accumulator
+            .priority_transactions()
+            .map(|tx_result| tx_result.hash)
+            .collect();
+        let expected_hashes: Vec<_> = l1_txs.iter().map(Transaction::hash).collect();
+        assert_eq!(priority_hashes, expected_hashes);
+    }
+
+    #[test]
+    fn user_transactions_in_mixed_block() {
+        let mut accumulator = create_accumulator();
+        let upgrade_tx = ProtocolUpgradeTx {
+            execute: Execute::default(),
+            common_data: ProtocolUpgradeTxCommonData {
+                canonical_tx_hash: H256::repeat_byte(0x11),
+                ..ProtocolUpgradeTxCommonData::default()
+            },
+            received_timestamp_ms: 0,
+        };
+        push_tx(&mut accumulator, upgrade_tx.into());
+        push_tx(&mut accumulator, create_transaction(10, 100));
+        push_tx(&mut accumulator, create_l1_transaction(1));
+        push_tx(&mut accumulator, create_transaction(10, 100));
+        accumulator.extend_from_fictive_transaction(
+            create_execution_result(0, []),
+            BlockGasCount::default(),
+            ExecutionMetrics::default(),
+        );
+
+        assert_eq!(accumulator.executed_transactions.len(), 4);
+        assert_eq!(accumulator.user_transaction_count(), 2);
+    }
+
+    #[test]
+    fn commit_batch_contains_all_miniblock_data() {
+        let mut accumulator = create_accumulator();
+        let commit_batch = accumulator.to_commit_batch();
+        assert!(commit_batch.transactions.is_empty());
+        assert!(commit_batch.events.is_empty());
+        assert!(commit_batch.storage_writes.is_empty());
+        assert!(commit_batch.factory_deps.is_empty());
+
+        let mut execution_result = create_execution_result(
+            0,
+            [
+                (U256::from(1), Query::InitialWrite(U256::from(1))),
+                (U256::from(2), Query::InitialWrite(U256::from(2))),
+                (U256::from(3), Query::Read(U256::from(3))),
+            ],
+        );
+        execution_result.logs.events = vec![VmEvent {
+            location: (L1BatchNumber(1), 0),
+            ..VmEvent::default()
+        }];
+        execution_result.logs.user_l2_to_l1_logs = vec![UserL2ToL1Log(L2ToL1Log::default())];
+        accumulator.extend_from_executed_transaction(
+            create_transaction(10, 100),
+            execution_result,
+            BlockGasCount::default(),
+            ExecutionMetrics::default(),
+            vec![],
+            vec![],
+        );
+
+        let bytecode = vec![0_u8; 32];
+        let bytecode_hash = hash_bytecode(&bytecode);
+        let mut tx = create_transaction(10, 100);
+        tx.execute.factory_deps = Some(vec![bytecode]);
+        let mut execution_result = create_execution_result(
+            1,
+            [(
+                U256::from(1),
+                Query::RepeatedWrite(U256::from(1), U256::from(5)),
+            )],
+        );
+        execution_result.logs.events = vec![VmEvent {
+            location: (L1BatchNumber(1), 1),
+            address: KNOWN_CODES_STORAGE_ADDRESS,
+            indexed_topics: vec![
+                H256(keccak256(b"MarkedAsKnown(bytes32,bool)")),
+                bytecode_hash,
+                H256::zero(),
+            ],
+            value: vec![],
+        }];
+        execution_result.logs.system_l2_to_l1_logs = vec![SystemL2ToL1Log(L2ToL1Log {
+            tx_number_in_block: 1,
+            ..L2ToL1Log::default()
+        })];
+        accumulator.extend_from_executed_transaction(
+            tx,
+            execution_result,
+            BlockGasCount::default(),
+            ExecutionMetrics::default(),
+            vec![],
+            vec![],
+        );
+
+        let commit_batch = accumulator.to_commit_batch();
+        assert_eq!(
+            commit_batch.transactions.len(),
+            accumulator.executed_transactions.len()
+        );
+        let event_counts: Vec<_> = commit_batch
+            .events
+            .iter()
+            .map(|(tx_index, events)| (*tx_index, events.len()))
+            .collect();
+        assert_eq!(event_counts, [(0, 1), (1, 1)]);
+        // Storage writes are deduplicated; the write to slot 1 is attributed to the last transaction writing to it.
+        let write_counts: Vec<_> = commit_batch
+            .storage_writes
+            .iter()
+            .map(|(tx_index, logs)| (*tx_index, logs.len()))
+            .collect();
+        assert_eq!(write_counts, [(0, 1), (1, 1)]);
+        assert_eq!(commit_batch.factory_deps, &accumulator.new_factory_deps);
+        assert!(commit_batch.factory_deps.contains_key(&bytecode_hash));
+        let user_log_counts: Vec<_> = commit_batch
+            .user_l2_to_l1_logs
+            .iter()
+            .map(|(tx_index, logs)| (*tx_index, logs.len()))
+            .collect();
+        assert_eq!(user_log_counts, [(0, 1)]);
+        let system_log_counts: Vec<_> = commit_batch
+            .system_l2_to_l1_logs
+            .iter()
+            .map(|(tx_index, logs)| (*tx_index, logs.len()))
+            .collect();
+        assert_eq!(system_log_counts, [(1, 1)]);
+    }
+
+    #[test]
+    fn factory_deps_shared_by_transactions() {
+        let mut accumulator = create_accumulator();
+        let bytecode = vec![0_u8; 32];
+        let bytecode_hash = hash_bytecode(&bytecode);
+        let marked_as_known_event = VmEvent {
+            location: (L1BatchNumber(1), 0),
+            address: KNOWN_CODES_STORAGE_ADDRESS,
+            indexed_topics: vec![
+                H256(keccak256(b"MarkedAsKnown(bytes32,bool)")),
+                bytecode_hash,
+                H256::zero(),
+            ],
+            value: vec![],
+        };
+
+        for i in 0..2 {
+            let mut tx = create_transaction(10, 100);
+            // Only the first transaction supplies the bytecode.
+            tx.execute.factory_deps = (i == 0).then(|| vec![bytecode.clone()]);
+            let mut execution_result = create_execution_result(i, []);
+            execution_result.logs.events = vec![marked_as_known_event.clone()];
+            accumulator.extend_from_executed_transaction(
+                tx,
+                execution_result,
+                BlockGasCount::default(),
+                ExecutionMetrics::default(),
+                vec![],
+                vec![],
+            );
+        }
+
+        assert_eq!(accumulator.executed_transactions.len(), 2);
+        assert_eq!(
+            accumulator.new_factory_deps,
+            HashMap::from([(bytecode_hash, bytecode)])
+        );
+    }
+
+    #[test]
+    fn checking_miniblock_parent() {
+        let mut parent = create_accumulator();
+        parent.timestamp = 10;
+        push_tx(&mut parent, create_transaction(10, 100));
+        let next = parent.next_miniblock(11, 1, ProtocolVersionId::latest());
+        assert_eq!(next.number, MiniblockNumber(1));
+        assert_eq!(next.prev_block_hash, parent.get_miniblock_hash());
+        next.check_parent().unwrap();
+        assert_eq!(next.get_miniblock_env().timestamp, 11);
+
+        // Equal timestamps are allowed.
+        let next = parent.next_miniblock(10, 1, ProtocolVersionId::latest());
+        next.check_parent().unwrap();
+
+        let next = parent.next_miniblock(9, 1, ProtocolVersionId::latest());
+        assert_eq!(
+            next.check_parent(),
+            Err(MiniblockEnvError::DecreasingTimestamp {
+                number: MiniblockNumber(1),
+                timestamp: 9,
+                parent_timestamp: 10,
+            })
+        );
+
+        let mut next = parent.next_miniblock(11, 1, ProtocolVersionId::latest());
+        next.number = MiniblockNumber(2);
+        assert_eq!(
+            next.check_parent(),
+            Err(MiniblockEnvError::NonSequentialNumber {
+                number: MiniblockNumber(2),
+                parent_number: MiniblockNumber(0),
+            })
+        );
+
+        let mut next = parent.next_miniblock(11, 1, ProtocolVersionId::latest());
+        next.prev_block_hash = H256::zero();
+        assert_matches!(
+            next.check_parent(),
+            Err(MiniblockEnvError::PrevBlockHashMismatch { parent_hash, .. })
+                if parent_hash == parent.get_miniblock_hash()
+        );
+
+        // Miniblocks without a known parent are not checked.
+        create_accumulator().check_parent().unwrap();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "inconsistent miniblock env")]
+    fn miniblock_env_with_decreasing_timestamp() {
+        let mut parent = create_accumulator();
+        parent.timestamp = 10;
+        parent
+            .next_miniblock(9, 1, ProtocolVersionId::latest())
+            .get_miniblock_env();
+    }
 }
@@ -149,10 +149,8 @@ impl UpdatesManager {
     /// Pushes a new miniblock with the specified timestamp into this manager. The previously
     /// held miniblock is considered sealed and is used to extend the L1 batch data.
     pub(crate) fn push_miniblock(&mut self, miniblock_params: MiniblockParams) {
-        let new_miniblock_updates = MiniblockUpdates::new(
+        let new_miniblock_updates = self.miniblock.next_miniblock(
             miniblock_params.timestamp,
-            self.miniblock.number + 1,
-            self.miniblock.get_miniblock_hash(),
             miniblock_params.virtual_blocks,
             self.protocol_version,
         );
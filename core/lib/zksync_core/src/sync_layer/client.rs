@@ -41,6 +41,15 @@ pub trait MainNodeClient: 'static + Send + Sync + fmt::Debug {
         with_transactions: bool,
     ) -> EnrichedClientResult<Option<en::SyncBlock>>;
 
+    /// Fetches blocks in the range `[from..=to]` using a single request. The main node may return fewer blocks
+    /// than requested (e.g., if some of them are not sealed yet).
+    async fn fetch_l2_block_range(
+        &self,
+        from: MiniblockNumber,
+        to: MiniblockNumber,
+        with_transactions: bool,
+    ) -> EnrichedClientResult<Vec<en::SyncBlock>>;
+
     async fn fetch_consensus_genesis(&self) -> EnrichedClientResult<Option<en::ConsensusGenesis>>;
 
     async fn fetch_genesis_config(&self) -> EnrichedClientResult<GenesisConfig>;
@@ -130,6 +139,20 @@ impl MainNodeClient for BoxedL2Client {
             .await
     }
 
+    async fn fetch_l2_block_range(
+        &self,
+        from: MiniblockNumber,
+        to: MiniblockNumber,
+        with_transactions: bool,
+    ) -> EnrichedClientResult<Vec<en::SyncBlock>> {
+        self.sync_l2_block_range(from, to, with_transactions)
+            .rpc_context("fetch_l2_block_range")
+            .with_arg("from", &from)
+            .with_arg("to", &to)
+            .with_arg("with_transactions", &with_transactions)
+            .await
+    }
+
     async fn fetch_consensus_genesis(&self) -> EnrichedClientResult<Option<en::ConsensusGenesis>> {
         self.consensus_genesis()
             .rpc_context("consensus_genesis")
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use zksync_dal::{Connection, Core, CoreDal};
 use zksync_shared_metrics::{TxStage, APP_METRICS};
 use zksync_types::{
@@ -53,8 +55,60 @@ pub(crate) struct FetchedBlock {
     pub transactions: Vec<FetchedTransaction>,
 }
 
+/// Error returned if a fetched block has an implausible timestamp, which may indicate corrupted data
+/// or a malicious main node.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum BlockTimestampError {
+    #[error(
+        "timestamp {timestamp} of miniblock #{number} is less than timestamp {prev_timestamp} of the previous miniblock"
+    )]
+    Backwards {
+        number: MiniblockNumber,
+        timestamp: u64,
+        prev_timestamp: u64,
+    },
+    #[error(
+        "timestamp {timestamp} of miniblock #{number} is more than {max_skew:?} ahead of local time {now}"
+    )]
+    TooFarInFuture {
+        number: MiniblockNumber,
+        timestamp: u64,
+        now: u64,
+        max_skew: Duration,
+    },
+}
+
 impl FetchedBlock {
-    fn compute_hash(&self, prev_miniblock_hash: H256) -> H256 {
+    /// Checks that the block timestamp doesn't go backwards compared to `prev_timestamp` (the timestamp
+    /// of the previous miniblock), and is not more than `max_skew` ahead of the local time `now`
+    /// (both timestamps are in seconds since the Unix epoch).
+    pub(crate) fn validate_timestamp(
+        &self,
+        prev_timestamp: u64,
+        now: u64,
+        max_skew: Option<Duration>,
+    ) -> Result<(), BlockTimestampError> {
+        if self.timestamp < prev_timestamp {
+            return Err(BlockTimestampError::Backwards {
+                number: self.number,
+                timestamp: self.timestamp,
+                prev_timestamp,
+            });
+        }
+        if let Some(max_skew) = max_skew {
+            if self.timestamp > now.saturating_add(max_skew.as_secs()) {
+                return Err(BlockTimestampError::TooFarInFuture {
+                    number: self.number,
+                    timestamp: self.timestamp,
+                    now,
+                    max_skew,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn compute_hash(&self, prev_miniblock_hash: H256) -> H256 {
         let mut hasher = MiniblockHasher::new(self.number, self.timestamp, prev_miniblock_hash);
         for tx in &self.transactions {
             hasher.push_tx_hash(tx.hash());
@@ -186,6 +240,7 @@ impl IoCursor {
         }
         self.next_miniblock += 1;
         self.prev_miniblock_hash = local_block_hash;
+        self.prev_miniblock_timestamp = block.timestamp;
 
         new_actions
     }
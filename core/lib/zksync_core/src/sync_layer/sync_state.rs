@@ -76,6 +76,12 @@ impl SyncState {
         self.0.borrow().is_synced().0
     }
 
+    /// Returns sync progress as a ratio of the local head to the main node head, clamped to the `[0, 1]` range.
+    /// Returns 0 if the main node head is unknown yet.
+    pub(crate) fn sync_progress(&self) -> f64 {
+        self.0.borrow().sync_progress()
+    }
+
     pub async fn run_updater(
         self,
         connection_pool: ConnectionPool<Core>,
@@ -189,6 +195,19 @@ impl SyncStateInner {
         }
     }
 
+    fn sync_progress(&self) -> f64 {
+        let Some(main_node_block) = self.main_node_block else {
+            // We cannot claim any progress until we know what to catch up with.
+            return 0.0;
+        };
+        let local_block = self.local_block.unwrap_or_default();
+        if main_node_block.0 == 0 {
+            // Only the genesis miniblock is available on the main node, so there's nothing to catch up with.
+            return 1.0;
+        }
+        (f64::from(local_block.0) / f64::from(main_node_block.0)).min(1.0)
+    }
+
     fn update_sync_metric(&self) {
         let (is_synced, lag) = self.is_synced();
         EN_METRICS.synced.set(is_synced.into());
@@ -265,6 +284,28 @@ mod tests {
         assert!(!sync_state.is_synced());
     }
 
+    #[test]
+    fn test_sync_progress() {
+        let sync_state = SyncState::default();
+        assert_eq!(sync_state.sync_progress(), 0.0);
+        sync_state.set_local_block(MiniblockNumber(0));
+        assert_eq!(sync_state.sync_progress(), 0.0);
+
+        sync_state.set_main_node_block(MiniblockNumber(0));
+        assert_eq!(sync_state.sync_progress(), 1.0);
+
+        sync_state.set_main_node_block(MiniblockNumber(100));
+        assert_eq!(sync_state.sync_progress(), 0.0);
+        sync_state.set_local_block(MiniblockNumber(25));
+        assert_eq!(sync_state.sync_progress(), 0.25);
+        sync_state.set_local_block(MiniblockNumber(100));
+        assert_eq!(sync_state.sync_progress(), 1.0);
+
+        // The local head being ahead of the main node is handled by the re-org detector.
+        sync_state.set_local_block(MiniblockNumber(120));
+        assert_eq!(sync_state.sync_progress(), 1.0);
+    }
+
     #[test]
     fn test_sync_state_doesnt_panic_on_local_block() {
         let sync_state = SyncState::default();
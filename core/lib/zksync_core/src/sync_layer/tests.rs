@@ -2,6 +2,7 @@
 
 use std::{iter, sync::Arc, time::Duration};
 
+use assert_matches::assert_matches;
 use test_casing::test_casing;
 use tokio::{sync::watch, task::JoinHandle};
 use zksync_contracts::BaseSystemContractsHashes;
@@ -14,7 +15,11 @@ use zksync_types::{
     Address, L1BatchNumber, L2ChainId, MiniblockNumber, ProtocolVersionId, Transaction, H256,
 };
 
-use super::{fetcher::FetchedTransaction, sync_action::SyncAction, *};
+use super::{
+    fetcher::{BlockTimestampError, FetchedBlock, FetchedTransaction},
+    sync_action::SyncAction,
+    *,
+};
 use crate::{
     consensus::testonly::MockMainNodeClient,
     genesis::{insert_genesis_batch, GenesisParams},
@@ -584,3 +589,62 @@ async fn external_io_with_multiple_l1_batches() {
     assert_eq!(fictive_miniblock.timestamp, 2);
     assert_eq!(fictive_miniblock.l2_tx_count, 0);
 }
+
+fn create_fetched_block(number: u32, timestamp: u64) -> FetchedBlock {
+    FetchedBlock {
+        number: MiniblockNumber(number),
+        l1_batch_number: L1BatchNumber(1),
+        last_in_batch: true,
+        protocol_version: ProtocolVersionId::latest(),
+        timestamp,
+        reference_hash: None,
+        l1_gas_price: 2,
+        l2_fair_gas_price: 3,
+        fair_pubdata_price: Some(4),
+        virtual_blocks: 1,
+        operator_address: OPERATOR_ADDRESS,
+        transactions: vec![],
+    }
+}
+
+#[test]
+fn fetched_block_with_plausible_timestamp() {
+    let max_skew = Some(Duration::from_secs(60));
+    let block = create_fetched_block(2, 100);
+    block.validate_timestamp(99, 100, max_skew).unwrap();
+    block.validate_timestamp(100, 40, max_skew).unwrap();
+    block.validate_timestamp(0, 0, None).unwrap();
+}
+
+#[test]
+fn fetched_block_with_backwards_timestamp() {
+    let block = create_fetched_block(2, 100);
+    let err = block
+        .validate_timestamp(101, 1_000, Some(Duration::from_secs(60)))
+        .unwrap_err();
+    assert_matches!(
+        err,
+        BlockTimestampError::Backwards { number, timestamp: 100, prev_timestamp: 101 }
+            if number == MiniblockNumber(2)
+    );
+    // The check doesn't depend on the skew config.
+    let err = block.validate_timestamp(101, 1_000, None).unwrap_err();
+    assert_matches!(err, BlockTimestampError::Backwards { .. });
+}
+
+#[test]
+fn fetched_block_with_timestamp_far_in_future() {
+    let max_skew = Duration::from_secs(60);
+    let block = create_fetched_block(2, 1_000);
+    let err = block
+        .validate_timestamp(99, 100, Some(max_skew))
+        .unwrap_err();
+    assert_matches!(
+        err,
+        BlockTimestampError::TooFarInFuture { number, timestamp: 1_000, now: 100, max_skew: skew }
+            if number == MiniblockNumber(2) && skew == max_skew
+    );
+    assert!(err.to_string().contains("ahead of local time"), "{err}");
+    // Skew is not checked if it's not configured.
+    block.validate_timestamp(99, 100, None).unwrap();
+}
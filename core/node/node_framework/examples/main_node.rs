@@ -24,6 +24,7 @@ use zksync_core::{
         tx_sender::{ApiContracts, TxSenderConfig},
         web3::{state::InternalApiConfig, Namespace},
     },
+    consensus::FetcherConfig,
     metadata_calculator::MetadataCalculatorConfig,
     temp_config_store::decode_yaml_repr,
 };
@@ -54,7 +55,7 @@ use zksync_node_framework::{
             caches::MempoolCacheLayer,
             server::{Web3ServerLayer, Web3ServerOptionalConfig},
             tree_api_client::TreeApiClientLayer,
-            tx_sender::{PostgresStorageCachesConfig, TxSenderLayer},
+            tx_sender::{PostgresStorageCachesConfig, TxSenderLayer, VmConcurrencyLimiterConfig},
             tx_sink::TxSinkLayer,
         },
     },
@@ -211,7 +212,12 @@ impl MainNodeBuilder {
                 network_config.zksync_network_id,
             ),
             postgres_storage_caches_config,
-            rpc_config.vm_concurrency_limit(),
+            VmConcurrencyLimiterConfig {
+                max_concurrency: rpc_config.vm_concurrency_limit(),
+                reserved_permits: rpc_config.vm_concurrency_reserved_permits(),
+                slow_acquire_threshold: rpc_config.vm_slow_acquire_threshold(),
+                max_queue_depth: rpc_config.vm_max_queue_depth,
+            },
             ApiContracts::load_from_disk(), // TODO (BFT-138): Allow to dynamically reload API contracts
         ));
         Ok(self)
@@ -387,6 +393,7 @@ impl MainNodeBuilder {
             mode: ConsensusMode::Main,
             config,
             secrets,
+            fetcher_config: FetcherConfig::default(),
         });
 
         Ok(self)
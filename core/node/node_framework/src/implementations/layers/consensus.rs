@@ -1,8 +1,10 @@
+use std::sync::Arc;
+
 use anyhow::Context as _;
 use zksync_concurrency::{ctx, scope};
 use zksync_config::configs::consensus::{ConsensusConfig, ConsensusSecrets};
 use zksync_core::{
-    consensus::{self, MainNodeConfig},
+    consensus::{self, FetcherConfig, FetcherHealth, MainNodeConfig},
     sync_layer::{ActionQueueSender, SyncState},
 };
 use zksync_dal::{ConnectionPool, Core};
@@ -10,8 +12,9 @@ use zksync_web3_decl::client::BoxedL2Client;
 
 use crate::{
     implementations::resources::{
-        action_queue::ActionQueueSenderResource, main_node_client::MainNodeClientResource,
-        pools::MasterPoolResource, sync_state::SyncStateResource,
+        action_queue::ActionQueueSenderResource, healthcheck::AppHealthCheckResource,
+        main_node_client::MainNodeClientResource, pools::MasterPoolResource,
+        sync_state::SyncStateResource,
     },
     service::{ServiceContext, StopReceiver},
     task::Task,
@@ -29,6 +32,8 @@ pub struct ConsensusLayer {
     pub mode: Mode,
     pub config: Option<ConsensusConfig>,
     pub secrets: Option<ConsensusSecrets>,
+    /// Parameters of fetching blocks from the main node. Only used in the [`Mode::External`] mode.
+    pub fetcher_config: FetcherConfig,
 }
 
 #[async_trait::async_trait]
@@ -89,11 +94,17 @@ impl WiringLayer for ConsensusLayer {
                     }
                 };
 
+                let health = FetcherHealth::default();
+                let AppHealthCheckResource(app_health) = context.get_resource_or_default().await;
+                app_health.insert_custom_component(Arc::new(health.clone()));
+
                 let task = FetcherTask {
                     config,
                     pool,
                     main_node_client,
                     sync_state,
+                    health,
+                    fetcher_config: self.fetcher_config,
                     action_queue_sender,
                 };
                 context.add_task(Box::new(task));
@@ -138,6 +149,8 @@ pub struct FetcherTask {
     pool: ConnectionPool<Core>,
     main_node_client: BoxedL2Client,
     sync_state: SyncState,
+    health: FetcherHealth,
+    fetcher_config: FetcherConfig,
     action_queue_sender: ActionQueueSender,
 }
 
@@ -161,6 +174,8 @@ impl Task for FetcherTask {
                 self.config,
                 self.pool,
                 self.sync_state,
+                self.health,
+                self.fetcher_config,
                 self.main_node_client,
                 self.action_queue_sender,
             ));
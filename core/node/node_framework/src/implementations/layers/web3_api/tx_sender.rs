@@ -1,4 +1,4 @@
-use std::{fmt, sync::Arc};
+use std::{fmt, sync::Arc, time::Duration};
 
 use zksync_core::api_server::{
     execution_sandbox::{VmConcurrencyBarrier, VmConcurrencyLimiter},
@@ -25,11 +25,22 @@ pub struct PostgresStorageCachesConfig {
     pub latest_values_cache_size: u64,
 }
 
+#[derive(Debug)]
+pub struct VmConcurrencyLimiterConfig {
+    pub max_concurrency: usize,
+    /// Number of permits out of `max_concurrency` reserved for interactive calls.
+    pub reserved_permits: usize,
+    /// Wait time after which permit acquisitions are logged as slow; `None` disables logging.
+    pub slow_acquire_threshold: Option<Duration>,
+    /// Maximum number of callers waiting for a permit; `None` doesn't limit the queue.
+    pub max_queue_depth: Option<usize>,
+}
+
 #[derive(Debug)]
 pub struct TxSenderLayer {
     tx_sender_config: TxSenderConfig,
     postgres_storage_caches_config: PostgresStorageCachesConfig,
-    max_vm_concurrency: usize,
+    vm_concurrency_limiter_config: VmConcurrencyLimiterConfig,
     api_contracts: ApiContracts,
 }
 
@@ -37,13 +48,13 @@ impl TxSenderLayer {
     pub fn new(
         tx_sender_config: TxSenderConfig,
         postgres_storage_caches_config: PostgresStorageCachesConfig,
-        max_vm_concurrency: usize,
+        vm_concurrency_limiter_config: VmConcurrencyLimiterConfig,
         api_contracts: ApiContracts,
     ) -> Self {
         Self {
             tx_sender_config,
             postgres_storage_caches_config,
-            max_vm_concurrency,
+            vm_concurrency_limiter_config,
             api_contracts,
         }
     }
@@ -85,8 +96,15 @@ impl WiringLayer for TxSenderLayer {
         }
 
         // Initialize `VmConcurrencyLimiter`.
+        let limiter_config = self.vm_concurrency_limiter_config;
         let (vm_concurrency_limiter, vm_concurrency_barrier) =
-            VmConcurrencyLimiter::new(self.max_vm_concurrency);
+            VmConcurrencyLimiter::with_reserved_permits(
+                limiter_config.max_concurrency,
+                limiter_config.reserved_permits,
+                limiter_config.slow_acquire_threshold,
+            );
+        let vm_concurrency_limiter =
+            vm_concurrency_limiter.with_max_queue_depth(limiter_config.max_queue_depth);
         context.add_task(Box::new(VmConcurrencyBarrierTask {
             barrier: vm_concurrency_barrier,
         }));
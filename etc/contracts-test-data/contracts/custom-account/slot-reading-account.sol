@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+pragma solidity ^0.8.0;
+
+import './Constants.sol';
+import './TransactionHelper.sol';
+
+import './SystemContractsCaller.sol';
+
+import './interfaces/IAccount.sol';
+import './interfaces/IPaymaster.sol';
+
+// Contract exposing its raw storage, e.g. to emulate well-known slots of a token proxy.
+contract StorageReader {
+	function readSlot(bytes32 _slot) external view returns (bytes32 value) {
+		assembly {
+			value := sload(_slot)
+		}
+	}
+}
+
+// Account reading a storage slot of another contract during validation. The contract address and the slot
+// are ABI-encoded in the transaction data. The contract can also serve as a paymaster paying for any transaction.
+contract SlotReadingAccount is IAccount, IPaymaster {
+	using TransactionHelper for Transaction;
+
+	function validateTransaction(bytes32, bytes32, Transaction calldata _transaction) external payable override returns (bytes4 magic) {
+		SystemContractsCaller.systemCallWithPropagatedRevert(
+			uint32(gasleft()),
+			address(NONCE_HOLDER_SYSTEM_CONTRACT),
+			0,
+			abi.encodeCall(INonceHolder.incrementMinNonceIfEquals, (_transaction.nonce))
+		);
+
+		(address target, bytes32 slot) = abi.decode(_transaction.data, (address, bytes32));
+		StorageReader(target).readSlot(slot);
+		magic = VALIDATION_SUCCESS_MAGIC;
+	}
+
+	function executeTransaction(bytes32, bytes32, Transaction calldata) external payable override {}
+
+	function executeTransactionFromOutside(Transaction calldata) external payable override {}
+
+	function payForTransaction(bytes32, bytes32, Transaction calldata _transaction) external payable override {
+		bool success = _transaction.payToTheBootloader();
+		require(success, "Failed to pay the fee to the operator");
+	}
+
+	function prepareForPaymaster(bytes32, bytes32, Transaction calldata) external payable override {}
+
+	function validateAndPayForPaymasterTransaction(bytes32, bytes32, Transaction calldata _transaction) external payable override returns (bytes4 magic, bytes memory context) {
+		bool success = _transaction.payToTheBootloader();
+		require(success, "Failed to transfer funds to the bootloader");
+		magic = PAYMASTER_VALIDATION_SUCCESS_MAGIC;
+		context = "";
+	}
+
+	function postTransaction(bytes calldata, Transaction calldata, bytes32, bytes32, ExecutionResult, uint256) external payable override {}
+
+	receive() external payable {}
+}